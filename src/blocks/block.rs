@@ -46,7 +46,7 @@ impl FaceDirections {
     pub fn create_face_data(
         &self,
         block: Arc<RwLock<Block>>,
-        blocks: &Vec<((i32, i32), BlockVec)>,
+        blocks: &[((i32, i32), BlockVec)],
     ) -> (Vec<BlockVertexData>, Vec<u32>) {
         let indices = self.get_indices();
 
@@ -129,19 +129,22 @@ impl Block {
         }
     }
     pub fn get_neighbour_chunks_coords(&self) -> Vec<(i32, i32)> {
-        let chunk = self.get_chunk_coords();
+        Self::neighbour_chunks_coords(&self.position, self.get_chunk_coords())
+    }
+    // Chunks whose mesh depends on the block at the relative position
+    pub fn neighbour_chunks_coords(position: &Vec3, chunk: (i32, i32)) -> Vec<(i32, i32)> {
         let mut neighbour_chunks = vec![];
 
-        if self.position.x == 15.0 {
+        if position.x == 15.0 {
             neighbour_chunks.push((chunk.0 + 1, chunk.1));
         }
-        if self.position.x == 0.0 {
+        if position.x == 0.0 {
             neighbour_chunks.push((chunk.0 - 1, chunk.1));
         }
-        if self.position.z == 15.0 {
+        if position.z == 15.0 {
             neighbour_chunks.push((chunk.0, chunk.1 + 1));
         }
-        if self.position.z == 0.0 {
+        if position.z == 0.0 {
             neighbour_chunks.push((chunk.0, chunk.1 - 1));
         }
         neighbour_chunks
@@ -11,6 +11,8 @@ pub struct BlockTypeConfigs {
     // Integers representing the nth texture to use.
     pub textures: [FaceTexture; 3], // 1: Lateral texture, 2: Top texture, 3: Bottom texture
     pub is_translucent: bool,
+    // Seconds needed to mine it
+    pub hardness: f32,
 }
 
 impl BlockTypeConfigs {
@@ -20,38 +22,45 @@ impl BlockTypeConfigs {
                 id: 0,
                 textures: [FaceTexture(6), FaceTexture(7), FaceTexture(8)],
                 is_translucent: false,
+                hardness: 0.6,
             },
             BlockType::Dirt => BlockTypeConfigs {
                 id: 1,
                 textures: [FaceTexture(0), FaceTexture(0), FaceTexture(0)],
                 is_translucent: false,
+                hardness: 0.5,
             },
 
             BlockType::Water => BlockTypeConfigs {
                 id: 2,
                 textures: [FaceTexture(1), FaceTexture(1), FaceTexture(1)],
                 is_translucent: true,
+                hardness: f32::INFINITY,
             },
 
             BlockType::Wood => BlockTypeConfigs {
                 id: 3,
                 textures: [FaceTexture(4), FaceTexture(5), FaceTexture(5)],
                 is_translucent: false,
+                hardness: 2.0,
             },
             BlockType::Leaf => BlockTypeConfigs {
                 id: 4,
                 textures: [FaceTexture(2), FaceTexture(2), FaceTexture(2)],
                 is_translucent: false,
+                hardness: 0.2,
             },
             BlockType::Stone => BlockTypeConfigs {
                 id: 5,
                 textures: [FaceTexture(3), FaceTexture(3), FaceTexture(3)],
                 is_translucent: false,
+                hardness: 3.0,
             },
             BlockType::Sand => BlockTypeConfigs {
                 id: 6,
                 textures: [FaceTexture(9), FaceTexture(9), FaceTexture(9)],
                 is_translucent: false,
+                hardness: 0.5,
             },
         }
     }
@@ -89,6 +98,23 @@ impl BlockType {
             _ => panic!("Invalid id"),
         }
     }
+    // Case insensitive name, like "stone", or numeric id
+    pub fn from_name(name: &str) -> Option<BlockType> {
+        if let Ok(id) = name.parse::<u32>() {
+            return (id <= Self::MAX_ID).then(|| Self::from_id(id));
+        }
+        (0..=Self::MAX_ID)
+            .map(Self::from_id)
+            .find(|block_type| format!("{:?}", block_type).eq_ignore_ascii_case(name))
+    }
+    // Seconds needed to mine the block, there are no tools to speed it up
+    pub fn mining_time(&self) -> f32 {
+        self.get_config().hardness
+    }
+    // Whether entities collide with this block
+    pub fn is_solid(&self) -> bool {
+        *self != BlockType::Water
+    }
 }
 fn calc_scalar(y: u32, t: Threshold) -> f32 {
     (y as f32 - t[0] as f32) / (t[1] as f32 - t[0] as f32)
@@ -1,5 +1,7 @@
-use crate::persistence::{Loadable, Saveable};
-use crate::player::Player;
+use crate::entity::Entity;
+use crate::mesh_pool::ChunkMeshes;
+use crate::persistence::{self, Loadable, Saveable};
+use crate::player::Camera;
 use crate::utils::math_utils::Plane;
 use crate::world::{ChunkMap, RNG_SEED, WATER_HEIGHT_LEVEL};
 use crate::{
@@ -17,29 +19,57 @@ use rand::{Rng, SeedableRng};
 use std::any::Any;
 use std::error::Error;
 use std::sync::{Arc, RwLock};
-use wgpu::util::DeviceExt;
 
 pub type BlockVec = Arc<RwLock<Vec<Vec<Option<Arc<RwLock<Block>>>>>>>;
+// Position in the chunk and type of a block, chunks are saved and sent to clients as lists of these
+pub type BlockEntry = ([u8; 3], BlockType);
 
 #[derive(Debug)]
 pub struct Chunk {
     pub x: i32,
     pub y: i32,
     pub blocks: BlockVec,
-    pub indices: u32,
-    pub water_indices: u32,
-    pub device: Arc<wgpu::Device>,
-    pub queue: Arc<wgpu::Queue>,
     pub noise_data: Arc<NoiseData>,
-    pub chunk_bind_group: wgpu::BindGroup,
-    pub chunk_position_buffer: wgpu::Buffer,
-    pub chunk_index_buffer: Option<wgpu::Buffer>,
-    pub chunk_vertex_buffer: Option<wgpu::Buffer>,
-    pub chunk_water_vertex_buffer: Option<wgpu::Buffer>,
-    pub chunk_water_index_buffer: Option<wgpu::Buffer>,
     pub outside_blocks: Vec<Arc<RwLock<Block>>>,
-    pub visible: bool,
     pub modified: bool, // if true, it will be saved
+    // Entities saved with the chunk, the world takes them while the chunk is loaded
+    pub entities: Vec<Entity>,
+}
+
+// Vertices of a chunk, built on the CPU so that headless worlds never need a GPU
+#[derive(Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<BlockVertexData>,
+    pub indices: Vec<u32>,
+    // Water is drawn by the translucent pipeline, after the opaque blocks
+    pub water_vertices: Vec<BlockVertexData>,
+    pub water_indices: Vec<u32>,
+}
+
+// GPU resources shared by every chunk, a world without one is headless
+#[derive(Debug)]
+pub struct ChunkRenderer {
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
+    pub chunk_data_layout: Arc<wgpu::BindGroupLayout>,
+    pub meshes: ChunkMeshes,
+}
+
+impl ChunkRenderer {
+    pub fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        let chunk_data_layout =
+            Arc::new(device.create_bind_group_layout(&Chunk::get_bind_group_layout()));
+        Self {
+            meshes: ChunkMeshes::new(
+                Arc::clone(&device),
+                Arc::clone(&queue),
+                Arc::clone(&chunk_data_layout),
+            ),
+            device,
+            queue,
+            chunk_data_layout,
+        }
+    }
 }
 
 impl Chunk {
@@ -67,7 +97,9 @@ impl Chunk {
         let y_blocks = blocks_borrow
             .get_mut(((block_r_position.x * CHUNK_SIZE as f32) + block_r_position.z) as usize)
             .expect("Cannot delete oob block");
-        y_blocks[block_r_position.y as usize] = None;
+        if let Some(block) = y_blocks.get_mut(block_r_position.y as usize) {
+            *block = None;
+        }
         self.modified = true;
     }
     pub fn block_type_at(&self, position: &glam::Vec3) -> Option<BlockType> {
@@ -106,27 +138,13 @@ impl Chunk {
     pub fn is_outside_chunk(position: &glam::Vec3) -> bool {
         position.x < 0.0
             || position.x >= CHUNK_SIZE as f32
-            || position.z < 0.0 || position.z >= CHUNK_SIZE as f32
+            || position.z < 0.0
+            || position.z >= CHUNK_SIZE as f32
     }
     pub fn is_outside_bounds(position: &glam::Vec3) -> bool {
         position.y < 0.0
     }
-    /*
-    Return tuple:
-    0: vertex indices     , 1: water vertex indices
-    2: vertex buffer      , 3: index buffer
-    4: water vertex buffer, 5: water index buffer */
-    pub fn build_mesh(
-        &self,
-        other_chunks: ChunkMap,
-    ) -> (
-        u32,
-        u32,
-        wgpu::Buffer,
-        wgpu::Buffer,
-        wgpu::Buffer,
-        wgpu::Buffer,
-    ) {
+    pub fn build_mesh(&self, other_chunks: ChunkMap) -> ChunkMesh {
         let mut water_vertex: Vec<BlockVertexData> = vec![];
         let mut water_indices: Vec<u32> = vec![];
         let mut vertex: Vec<BlockVertexData> = vec![];
@@ -143,106 +161,96 @@ impl Chunk {
         }
 
         for region in self.blocks.read().unwrap().iter() {
-            for y in 0..region.len() {
-                if let Some(block_ptr) = &region[y] {
-                    let block = block_ptr.read().unwrap();
-                    let position = block.position;
-                    let faces = FaceDirections::all();
-
-                    for face in faces.iter() {
-                        // For water block types, we only care about the top face
-                        if block.block_type == BlockType::Water && *face != FaceDirections::Top {
-                            continue;
-                        }
-                        let mut is_visible = true;
-                        let face_position = face.get_normal_vector() + position;
-
-                        if Chunk::is_outside_bounds(&face_position) {
-                            is_visible = false;
-                        } else if Chunk::is_outside_chunk(&face_position) {
-                            let target_chunk_x =
-                                self.x + (f32::floor(face_position.x / CHUNK_SIZE as f32) as i32);
-                            let target_chunk_y =
-                                self.y + (f32::floor(face_position.z / CHUNK_SIZE as f32) as i32);
-
-                            let target_block = glam::vec3(
-                                (face_position.x + CHUNK_SIZE as f32) % CHUNK_SIZE as f32,
-                                face_position.y,
-                                (face_position.z + CHUNK_SIZE as f32) % CHUNK_SIZE as f32,
-                            );
-
-                            let other_chunks_brw = other_chunks.read().unwrap();
-                            let target_chunk =
-                                other_chunks_brw.get(&(target_chunk_x, target_chunk_y));
-                            // let target_chunk = other_chunks.iter().find(|c| {
-                            //     let c = c.read().unwrap();
-                            //     c.x == target_chunk_x && c.y == target_chunk_y
-                            // });
-                            // If there's a chunk loaded in memory then check that, else it means we're on a edge and we can
-                            // Calculate the block's height when the chunk gets generated
-                            // TODO: Check for saved file chunk
-                            match target_chunk {
-                                Some(chunk) => {
-                                    let chunk = chunk.read().unwrap();
-                                    if chunk.exists_block_at(&target_block) {
-                                        is_visible = false;
-
-                                        if chunk.block_type_at(&target_block)
-                                            == Some(BlockType::Water)
-                                            && block.block_type != BlockType::Water
-                                        {
-                                            is_visible = true;
-                                        }
+            for block_ptr in region.iter().flatten() {
+                let block = block_ptr.read().unwrap();
+                let position = block.position;
+                let faces = FaceDirections::all();
+
+                for face in faces.iter() {
+                    // For water block types, we only care about the top face
+                    if block.block_type == BlockType::Water && *face != FaceDirections::Top {
+                        continue;
+                    }
+                    let mut is_visible = true;
+                    let face_position = face.get_normal_vector() + position;
+
+                    if Chunk::is_outside_bounds(&face_position) {
+                        is_visible = false;
+                    } else if Chunk::is_outside_chunk(&face_position) {
+                        let target_chunk_x =
+                            self.x + (f32::floor(face_position.x / CHUNK_SIZE as f32) as i32);
+                        let target_chunk_y =
+                            self.y + (f32::floor(face_position.z / CHUNK_SIZE as f32) as i32);
+
+                        let target_block = glam::vec3(
+                            (face_position.x + CHUNK_SIZE as f32) % CHUNK_SIZE as f32,
+                            face_position.y,
+                            (face_position.z + CHUNK_SIZE as f32) % CHUNK_SIZE as f32,
+                        );
+
+                        let other_chunks_brw = other_chunks.read().unwrap();
+                        let target_chunk = other_chunks_brw.get(&(target_chunk_x, target_chunk_y));
+                        // let target_chunk = other_chunks.iter().find(|c| {
+                        //     let c = c.read().unwrap();
+                        //     c.x == target_chunk_x && c.y == target_chunk_y
+                        // });
+                        // If there's a chunk loaded in memory then check that, else it means we're on a edge and we can
+                        // Calculate the block's height when the chunk gets generated
+                        // TODO: Check for saved file chunk
+                        match target_chunk {
+                            Some(chunk) => {
+                                let chunk = chunk.read().unwrap();
+                                if chunk.exists_block_at(&target_block) {
+                                    is_visible = false;
+
+                                    if chunk.block_type_at(&target_block) == Some(BlockType::Water)
+                                        && block.block_type != BlockType::Water
+                                    {
+                                        is_visible = true;
                                     }
                                 }
-                                None => {
-                                    let h = Chunk::get_height_value(
-                                        target_chunk_x,
-                                        target_chunk_y,
-                                        target_block.x as u32,
-                                        target_block.z as u32,
-                                        self.noise_data.clone(),
-                                    );
-
-                                    if face_position.y as u32 <= h {
-                                        is_visible = false
-                                    };
-                                }
                             }
-                        } else if self.exists_block_at(&face_position) {
-                            is_visible = false;
-                            // This can be a oneline if, but it gets very hard to read
-                            if self.block_type_at(&face_position) == Some(BlockType::Water)
-                                && block.block_type != BlockType::Water
-                            {
-                                is_visible = true;
+                            None => {
+                                let h = Chunk::get_height_value(
+                                    target_chunk_x,
+                                    target_chunk_y,
+                                    target_block.x as u32,
+                                    target_block.z as u32,
+                                    self.noise_data.clone(),
+                                );
+
+                                if face_position.y as u32 <= h {
+                                    is_visible = false
+                                };
                             }
                         }
+                    } else if self.exists_block_at(&face_position) {
+                        is_visible = false;
+                        // This can be a oneline if, but it gets very hard to read
+                        if self.block_type_at(&face_position) == Some(BlockType::Water)
+                            && block.block_type != BlockType::Water
+                        {
+                            is_visible = true;
+                        }
+                    }
 
-                        if is_visible {
-                            let (mut vertex_data, index_data) =
-                                face.create_face_data(block_ptr.clone(), &adjacent_chunks);
-                            match block.block_type {
-                                BlockType::Water => {
-                                    water_vertex.append(&mut vertex_data);
-                                    let indices_offset = water_vertex.len() as u32 - 4;
-                                    water_indices.append(
-                                        &mut index_data
-                                            .iter()
-                                            .map(|i| i + indices_offset)
-                                            .collect(),
-                                    )
-                                }
-                                _ => {
-                                    vertex.append(&mut vertex_data);
-                                    let indices_offset = vertex.len() as u32 - 4;
-                                    indices.append(
-                                        &mut index_data
-                                            .iter()
-                                            .map(|i| i + indices_offset)
-                                            .collect(),
-                                    )
-                                }
+                    if is_visible {
+                        let (mut vertex_data, index_data) =
+                            face.create_face_data(block_ptr.clone(), &adjacent_chunks);
+                        match block.block_type {
+                            BlockType::Water => {
+                                water_vertex.append(&mut vertex_data);
+                                let indices_offset = water_vertex.len() as u32 - 4;
+                                water_indices.append(
+                                    &mut index_data.iter().map(|i| i + indices_offset).collect(),
+                                )
+                            }
+                            _ => {
+                                vertex.append(&mut vertex_data);
+                                let indices_offset = vertex.len() as u32 - 4;
+                                indices.append(
+                                    &mut index_data.iter().map(|i| i + indices_offset).collect(),
+                                )
                             }
                         }
                     }
@@ -250,44 +258,12 @@ impl Chunk {
             }
         }
 
-        let chunk_vertex_buffer =
-            self.device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    contents: bytemuck::cast_slice(&vertex),
-                    label: Some(&format!("chunk-vertex-{}-{}", self.x, self.y)),
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                });
-        let chunk_index_buffer =
-            self.device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    contents: bytemuck::cast_slice(&indices),
-                    label: Some(&format!("chunk-vertex-{}-{}", self.x, self.y)),
-                    usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-                });
-
-        let chunk_water_vertex_buffer =
-            self.device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    contents: bytemuck::cast_slice(&water_vertex),
-                    label: Some(&format!("water-chunk-vertex-{}-{}", self.x, self.y)),
-                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-                });
-        let chunk_water_index_buffer =
-            self.device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    contents: bytemuck::cast_slice(&water_indices),
-                    label: Some(&format!("water-chunk-vertex-{}-{}", self.x, self.y)),
-                    usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-                });
-
-        (
-            indices.len() as u32,
-            water_indices.len() as u32,
-            chunk_vertex_buffer,
-            chunk_index_buffer,
-            chunk_water_vertex_buffer,
-            chunk_water_index_buffer,
-        )
+        ChunkMesh {
+            vertices: vertex,
+            indices,
+            water_vertices: water_vertex,
+            water_indices,
+        }
     }
     pub fn get_bind_group_layout() -> wgpu::BindGroupLayoutDescriptor<'static> {
         wgpu::BindGroupLayoutDescriptor {
@@ -296,7 +272,7 @@ impl Chunk {
                 binding: 0,
                 visibility: wgpu::ShaderStages::VERTEX,
                 ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
                     has_dynamic_offset: false,
                     min_binding_size: None,
                 },
@@ -426,50 +402,49 @@ impl Chunk {
     }
     // https://www.lighthouse3d.com/tutorials/view-frustum-culling/
     // Note: we don't compute the top and bottom planes, only far,near,right,left
-    pub fn is_visible(&self, player: Arc<RwLock<Player>>) -> bool {
-        let player = player.read().unwrap();
-        let forward = player.camera.get_forward_dir();
-        let right = player.camera.get_right_dir();
-        let halfvside = player.camera.zfar / f32::tan(player.camera.fovy / 2.0);
-        let halfhside = halfvside * player.camera.aspect_ratio;
-        let front_mult_far = player.camera.zfar * forward;
+    pub fn is_visible(x: i32, y: i32, camera: &Camera) -> bool {
+        let forward = camera.get_forward_dir();
+        let right = camera.get_right_dir();
+        let halfvside = camera.zfar / f32::tan(camera.fovy / 2.0);
+        let halfhside = halfvside * camera.aspect_ratio;
+        let front_mult_far = camera.zfar * forward;
 
         let chunk_points = [
             (
-                (self.x as f32) * CHUNK_SIZE as f32,
-                (self.y as f32) * CHUNK_SIZE as f32,
+                (x as f32) * CHUNK_SIZE as f32,
+                (y as f32) * CHUNK_SIZE as f32,
             ),
             (
-                (self.x as f32 + 1.0) * CHUNK_SIZE as f32,
-                (self.y as f32) * CHUNK_SIZE as f32,
+                (x as f32 + 1.0) * CHUNK_SIZE as f32,
+                (y as f32) * CHUNK_SIZE as f32,
             ),
             (
-                (self.x as f32) * CHUNK_SIZE as f32,
-                (self.y as f32 + 1.0) * CHUNK_SIZE as f32,
+                (x as f32) * CHUNK_SIZE as f32,
+                (y as f32 + 1.0) * CHUNK_SIZE as f32,
             ),
             (
-                (self.x as f32 + 1.0) * CHUNK_SIZE as f32,
-                (self.y as f32 + 1.0) * CHUNK_SIZE as f32,
+                (x as f32 + 1.0) * CHUNK_SIZE as f32,
+                (y as f32 + 1.0) * CHUNK_SIZE as f32,
             ),
         ];
 
         let near_plane = Plane {
-            point: player.camera.eye + player.camera.znear * forward,
+            point: camera.eye + camera.znear * forward,
             normal: forward,
         };
         let far_plane = Plane {
-            point: player.camera.eye + front_mult_far,
+            point: camera.eye + front_mult_far,
             normal: -forward,
         };
         let right_plane = Plane {
-            point: player.camera.eye,
+            point: camera.eye,
             normal: glam::vec3(0.0, 1.0, 0.0)
-                .cross(player.camera.eye - (front_mult_far + right * halfhside))
+                .cross(camera.eye - (front_mult_far + right * halfhside))
                 .normalize(),
         };
         let left_plane = Plane {
-            point: player.camera.eye,
-            normal: (player.camera.eye - (front_mult_far - right * halfhside))
+            point: camera.eye,
+            normal: (camera.eye - (front_mult_far - right * halfhside))
                 .cross(glam::vec3(0.0, 1.0, 0.0))
                 .normalize(),
         };
@@ -484,56 +459,27 @@ impl Chunk {
             })
     }
 
-    pub fn new(
-        x: i32,
-        y: i32,
-        noise_data: Arc<NoiseData>,
-        device: Arc<wgpu::Device>,
-        queue: Arc<wgpu::Queue>,
-        chunk_data_layout: Arc<wgpu::BindGroupLayout>,
-    ) -> Chunk {
+    pub fn new(x: i32, y: i32, noise_data: Arc<NoiseData>) -> Chunk {
         let mut was_loaded = false;
+        let mut entities = vec![];
 
-        let blocks = if let Ok(blocks) = Self::load(Box::new((x, y))) {
+        let blocks = if let Ok((blocks, saved_entities)) = Self::load(Box::new((x, y))) {
             was_loaded = true;
+            entities = saved_entities;
             blocks
         } else {
             Self::create_blocks_data(x, y, noise_data.clone())
         };
 
-        let chunk_position_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            contents: bytemuck::cast_slice(&[x, y]),
-            label: Some(&format!("chunk-position-{x}-{y}")),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let chunk_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: chunk_data_layout.as_ref(),
-            label: Some(&format!("chunk-bg-{x}-{y}")),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: chunk_position_buffer.as_entire_binding(),
-            }],
-        });
-
         let mut chunk = Chunk {
-            modified: false,
-            chunk_water_index_buffer: None,
-            chunk_water_vertex_buffer: None,
+            // Saved again even if no block changes, so that the entities that left aren't loaded back
+            modified: !entities.is_empty(),
+            entities,
             blocks,
             x,
             y,
-            device,
-            queue,
             noise_data,
-            chunk_vertex_buffer: None,
-            chunk_index_buffer: None,
-            chunk_bind_group,
-            chunk_position_buffer,
-            indices: 0,
-            water_indices: 0,
             outside_blocks: vec![],
-            visible: true,
         };
 
         if !was_loaded {
@@ -541,84 +487,104 @@ impl Chunk {
         }
         chunk
     }
+    // A chunk whose blocks come from elsewhere, like a server, it is never generated nor saved
+    pub fn from_entries(
+        x: i32,
+        y: i32,
+        entries: &[BlockEntry],
+        noise_data: Arc<NoiseData>,
+    ) -> Chunk {
+        Chunk {
+            modified: false,
+            entities: vec![],
+            blocks: Chunk::blocks_from_entries(x, y, entries),
+            x,
+            y,
+            noise_data,
+            outside_blocks: vec![],
+        }
+    }
+    // Every block of the chunk, column by column from the bottom up
+    pub fn block_entries(&self) -> Vec<BlockEntry> {
+        let mut entries = vec![];
+        for col in self.blocks.read().unwrap().iter() {
+            for block_ptr in col.iter().flatten() {
+                let blockbrw = block_ptr.read().unwrap();
+                let position = blockbrw.position;
+                entries.push((
+                    [position.x as u8, position.y as u8, position.z as u8],
+                    blockbrw.block_type,
+                ));
+            }
+        }
+        entries
+    }
+    pub fn blocks_from_entries(x: i32, y: i32, entries: &[BlockEntry]) -> BlockVec {
+        let size = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        let blocks: BlockVec = Arc::new(RwLock::new(vec![vec![]; size]));
+        for &([bx, by, bz], block_type) in entries {
+            let block = Block::new(
+                glam::vec3(bx as f32, by as f32, bz as f32),
+                (x, y),
+                block_type,
+            );
+            let y_blocks =
+                &mut blocks.write().unwrap()[((bx as u32 * CHUNK_SIZE) + bz as u32) as usize];
+            if y_blocks.len() <= by as usize {
+                y_blocks.resize(by as usize + 1, None);
+            }
+            y_blocks[by as usize] = Some(Arc::new(RwLock::new(block)));
+        }
+        blocks
+    }
 }
 
 impl Saveable<Chunk> for Chunk {
     fn save(&self) -> Result<(), Box<dyn Error>> {
-        if std::fs::create_dir("data").is_ok() {
-            println!("Created dir");
-        }
+        persistence::create_world_dir()?;
         let mut data = String::new();
 
-        for col in self.blocks.read().unwrap().iter() {
-            for block in col.iter() {
-                if let Some(block_ptr) = block {
-                    let blockbrw = block_ptr.read().unwrap();
-                    data += &format!(
-                        "{},{},{},{}\n",
-                        blockbrw.position.x,
-                        blockbrw.position.y,
-                        blockbrw.position.z,
-                        blockbrw.block_type.to_id()
-                    );
-                }
-            }
+        for ([x, y, z], block_type) in self.block_entries() {
+            data += &format!("{},{},{},{}\n", x, y, z, block_type.to_id());
+        }
+        for entity in self.entities.iter() {
+            data += &entity.to_line();
+            data.push('\n');
         }
 
-        let chunk_file_name = format!("data/chunk{}_{}", self.x, self.y);
-        std::fs::write(chunk_file_name.clone(), data.as_bytes())?;
+        let chunk_file_name = format!("chunk{}_{}", self.x, self.y);
+        std::fs::write(persistence::world_path(&chunk_file_name), data.as_bytes())?;
 
         Ok(())
     }
 }
 
-impl Loadable<BlockVec> for Chunk {
-    fn load(args: Box<dyn Any>) -> Result<BlockVec, Box<dyn Error>> {
+impl Loadable<(BlockVec, Vec<Entity>)> for Chunk {
+    fn load(args: Box<dyn Any>) -> Result<(BlockVec, Vec<Entity>), Box<dyn Error>> {
         if let Ok(chunk_position) = args.downcast::<(i32, i32)>() {
-            for entry in std::fs::read_dir("data")? {
-                let file = entry?;
-                let filename_chunk = file.file_name();
-                let mut coords = filename_chunk
-                    .to_str()
-                    .unwrap()
-                    .split('k')
-                    .last()
-                    .expect("Invalid filename")
-                    .split('_');
-                let x = coords.next().unwrap().parse::<i32>()?;
-                let y = coords.next().unwrap().parse::<i32>()?;
-
-                let size = (CHUNK_SIZE * CHUNK_SIZE) as usize;
-                let blocks: BlockVec = Arc::new(RwLock::new(vec![vec![]; size]));
-                if *chunk_position == (x, y) {
-                    let file_contents = std::fs::read_to_string(format!("data/chunk{}_{}", x, y))?;
-                    for line in file_contents.lines() {
-                        let mut i = line.split(',');
-                        let bx = i.next().unwrap().parse::<u32>()?;
-                        let by = i.next().unwrap().parse::<u32>()?;
-                        let bz = i.next().unwrap().parse::<u32>()?;
-                        let block_type = i.next().unwrap().parse::<u32>()?;
-                        let block_type = BlockType::from_id(block_type);
-
-                        let block = Block::new(
-                            glam::vec3(bx as f32, by as f32, bz as f32),
-                            (x, y),
-                            block_type,
-                        );
-                        let y_blocks =
-                            &mut blocks.write().unwrap()[((bx * CHUNK_SIZE) + bz) as usize];
-                        let start_len = y_blocks.len();
-
-                        for i in start_len..=by as usize {
-                            if i >= y_blocks.len() {
-                                y_blocks.push(None);
-                            }
-                        }
-                        y_blocks[by as usize] = Some(Arc::new(RwLock::new(block)));
+            let (x, y) = *chunk_position;
+            let file_contents =
+                std::fs::read_to_string(persistence::world_path(&format!("chunk{}_{}", x, y)))?;
+
+            let mut entries = vec![];
+            let mut entities = vec![];
+            for line in file_contents.lines() {
+                if Entity::is_line(line) {
+                    // A bad entity is dropped rather than losing the chunk's blocks
+                    match Entity::from_line(line) {
+                        Ok(entity) => entities.push(entity),
+                        Err(err) => eprintln!("Skipping entity in chunk {x},{y}: {err}"),
                     }
-                    return Ok(blocks);
+                    continue;
                 }
+                let mut i = line.split(',');
+                let bx = i.next().unwrap().parse::<u8>()?;
+                let by = i.next().unwrap().parse::<u8>()?;
+                let bz = i.next().unwrap().parse::<u8>()?;
+                let block_type = i.next().unwrap().parse::<u32>()?;
+                entries.push(([bx, by, bz], BlockType::from_id(block_type)));
             }
+            return Ok((Chunk::blocks_from_entries(x, y, &entries), entities));
         }
         Err("Not valid args".into())
     }
@@ -1,3 +1,5 @@
+use crate::blocks::block::FaceDirections;
+
 #[derive(Debug, Clone)]
 pub struct CollisionBox {
     pub min_x: f32,
@@ -85,12 +87,6 @@ impl Ray {
     }
 }
 
-#[derive(Debug)]
-pub struct RayResult {
-    pub points: Vec<glam::Vec3>,
-    pub collision: CollisionBox,
-}
-
 impl CollisionPoint {
     pub fn new(x: f32, y: f32, z: f32) -> CollisionPoint {
         CollisionPoint { x, y, z }
@@ -144,9 +140,151 @@ impl CollisionBox {
             && self.min_z <= other.max_z
             && self.max_z >= other.min_z
     }
+    // Like `intersects`, but boxes that only touch on a face don't overlap
+    pub fn overlaps(&self, other: &CollisionBox) -> bool {
+        self.min_x < other.max_x
+            && self.max_x > other.min_x
+            && self.min_y < other.max_y
+            && self.max_y > other.min_y
+            && self.min_z < other.max_z
+            && self.max_z > other.min_z
+    }
     pub fn intersects_direction() {
         todo!()
     }
+    pub fn min(&self) -> glam::Vec3 {
+        glam::vec3(self.min_x, self.min_y, self.min_z)
+    }
+    pub fn max(&self) -> glam::Vec3 {
+        glam::vec3(self.max_x, self.max_y, self.max_z)
+    }
+    // Block positions of every cell the box overlaps. Touching a face doesn't count as overlapping.
+    pub fn overlapping_blocks(&self) -> Vec<glam::Vec3> {
+        let (min, max) = (self.min(), self.max());
+        let mut blocks = vec![];
+        for x in cell_range(min.x, max.x) {
+            for y in cell_range(min.y, max.y) {
+                for z in cell_range(min.z, max.z) {
+                    blocks.push(glam::vec3(x as f32, y as f32, z as f32));
+                }
+            }
+        }
+        blocks
+    }
+}
+
+// Tolerance used so that boxes resting exactly on a block face are not considered inside it.
+const SWEEP_EPSILON: f32 = 1e-4;
+
+fn cell_range(min: f32, max: f32) -> std::ops::RangeInclusive<i32> {
+    f32::floor(min + SWEEP_EPSILON) as i32..=f32::floor(max - SWEEP_EPSILON) as i32
+}
+
+/* Swept AABB against the voxel grid.
+Moves the box along a single axis (0: x, 1: y, 2: z) and returns how far it can travel before touching
+a block for which `is_solid` returns true. The returned distance has the same sign as `delta`. */
+pub fn sweep_axis<F>(collision_box: &CollisionBox, axis: usize, delta: f32, is_solid: &F) -> f32
+where
+    F: Fn(glam::Vec3) -> bool,
+{
+    if delta == 0.0 {
+        return 0.0;
+    }
+    let (min, max) = (collision_box.min(), collision_box.max());
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+
+    let hits_layer = |layer: i32| {
+        cell_range(min[a], max[a]).any(|i| {
+            cell_range(min[b], max[b]).any(|j| {
+                let mut block = glam::Vec3::ZERO;
+                block[axis] = layer as f32;
+                block[a] = i as f32;
+                block[b] = j as f32;
+                is_solid(block)
+            })
+        })
+    };
+
+    if delta > 0.0 {
+        let first = f32::floor(max[axis] - SWEEP_EPSILON) as i32 + 1;
+        let last = f32::floor(max[axis] + delta - SWEEP_EPSILON) as i32;
+        for layer in first..=last {
+            if hits_layer(layer) {
+                return f32::max(layer as f32 - max[axis], 0.0);
+            }
+        }
+    } else {
+        let first = f32::floor(min[axis] + SWEEP_EPSILON) as i32 - 1;
+        let last = f32::floor(min[axis] + delta + SWEEP_EPSILON) as i32;
+        for layer in (last..=first).rev() {
+            if hits_layer(layer) {
+                return f32::min(layer as f32 + 1.0 - min[axis], 0.0);
+            }
+        }
+    }
+    delta
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SweepResult {
+    // The movement that was actually applied
+    pub movement: glam::Vec3,
+    // Which axes were stopped by a block
+    pub blocked: glam::BVec3,
+}
+
+// Moves the box by `delta`, resolving one axis at a time (y first, so that landing happens before sliding).
+pub fn sweep<F>(collision_box: &CollisionBox, delta: glam::Vec3, is_solid: &F) -> SweepResult
+where
+    F: Fn(glam::Vec3) -> bool,
+{
+    let mut collision_box = collision_box.clone();
+    let mut result = SweepResult::default();
+
+    for axis in [1, 0, 2] {
+        let moved = sweep_axis(&collision_box, axis, delta[axis], is_solid);
+        if moved != delta[axis] {
+            result.blocked.set(axis, true);
+        }
+        let mut offset = glam::Vec3::ZERO;
+        offset[axis] = moved;
+        collision_box = collision_box + offset;
+        result.movement[axis] = moved;
+    }
+    result
+}
+
+// Like `sweep`, but a box on the ground that walks into a block at most `step_height` high climbs on it
+pub fn sweep_with_step<F>(
+    collision_box: &CollisionBox,
+    delta: glam::Vec3,
+    step_height: f32,
+    is_solid: &F,
+) -> SweepResult
+where
+    F: Fn(glam::Vec3) -> bool,
+{
+    let result = sweep(collision_box, delta, is_solid);
+    if !(result.blocked.x || result.blocked.z) || step_height <= 0.0 {
+        return result;
+    }
+    let up = sweep_axis(collision_box, 1, step_height, is_solid);
+    let raised = collision_box.clone() + glam::vec3(0.0, up, 0.0);
+    let horizontal = sweep(&raised, glam::vec3(delta.x, 0.0, delta.z), is_solid);
+    let moved = raised.clone() + horizontal.movement;
+    let fall = f32::min(delta.y, 0.0) - up;
+    let down = sweep_axis(&moved, 1, fall, is_solid);
+
+    let stepped_distance = horizontal.movement.length_squared();
+    let distance = glam::vec3(result.movement.x, 0.0, result.movement.z).length_squared();
+    if stepped_distance > distance {
+        SweepResult {
+            movement: horizontal.movement + glam::vec3(0.0, up + down, 0.0),
+            blocked: glam::BVec3::new(horizontal.blocked.x, down != fall, horizontal.blocked.z),
+        }
+    } else {
+        result
+    }
 }
 
 impl std::ops::Add<glam::Vec3> for CollisionBox {
@@ -163,3 +301,170 @@ impl std::ops::Add<glam::Vec3> for CollisionBox {
         )
     }
 }
+
+#[derive(Debug, Clone, Copy)]
+pub struct BlockHit {
+    // Absolute position of the block that was hit
+    pub block_position: glam::Vec3,
+    // Face of the block the ray entered through
+    pub face: FaceDirections,
+    pub point: glam::Vec3,
+    pub distance: f32,
+}
+
+fn face_from_step(axis: usize, step: f32) -> FaceDirections {
+    // The ray enters through the face pointing against the direction it travels
+    match (axis, step > 0.0) {
+        (0, true) => FaceDirections::Left,
+        (0, false) => FaceDirections::Right,
+        (1, true) => FaceDirections::Bottom,
+        (1, false) => FaceDirections::Top,
+        (2, true) => FaceDirections::Front,
+        _ => FaceDirections::Back,
+    }
+}
+
+/* Voxel traversal (Amanatides & Woo, "A Fast Voxel Traversal Algorithm for Ray Tracing").
+Walks the grid cells crossed by the ray in order and returns the first block for which `is_hit` returns
+true, up to `max_distance`. */
+pub fn raycast_blocks<F>(
+    origin: glam::Vec3,
+    direction: glam::Vec3,
+    max_distance: f32,
+    is_hit: &F,
+) -> Option<BlockHit>
+where
+    F: Fn(glam::Vec3) -> bool,
+{
+    let direction = direction.normalize_or_zero();
+    if direction == glam::Vec3::ZERO {
+        return None;
+    }
+    let mut block = origin.floor();
+    let mut step = glam::Vec3::ZERO;
+    let mut t_max = glam::Vec3::splat(f32::INFINITY);
+    let mut t_delta = glam::Vec3::splat(f32::INFINITY);
+
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1.0;
+            t_delta[axis] = 1.0 / direction[axis];
+            t_max[axis] = (block[axis] + 1.0 - origin[axis]) / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1.0;
+            t_delta[axis] = -1.0 / direction[axis];
+            t_max[axis] = (block[axis] - origin[axis]) / direction[axis];
+        }
+    }
+
+    // If the origin is already inside a block, report the face the ray is leaving from
+    let dominant_axis = (0..3)
+        .max_by(|a, b| direction[*a].abs().total_cmp(&direction[*b].abs()))
+        .unwrap();
+    let mut face = face_from_step(dominant_axis, -step[dominant_axis]);
+    let mut distance = 0.0;
+
+    loop {
+        if is_hit(block) {
+            return Some(BlockHit {
+                block_position: block,
+                face,
+                point: origin + direction * distance,
+                distance,
+            });
+        }
+        let axis = if t_max.x < t_max.y {
+            if t_max.x < t_max.z {
+                0
+            } else {
+                2
+            }
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+        if t_max[axis] > max_distance {
+            return None;
+        }
+        distance = t_max[axis];
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        face = face_from_step(axis, step[axis]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{raycast_blocks, sweep, sweep_axis, CollisionBox};
+    use crate::blocks::block::FaceDirections;
+
+    // A flat floor whose top face is at y = 1.0, plus a wall at x = 3
+    fn is_solid(block: glam::Vec3) -> bool {
+        block.y == 0.0 || (block.x == 3.0 && block.y < 3.0)
+    }
+
+    #[test]
+    fn should_overlap_only_when_sharing_volume() {
+        let block = CollisionBox::from_block_position(0.0, 0.0, 0.0);
+        assert!(block.overlaps(&CollisionBox::new(0.5, 0.5, 0.5, 1.0, 1.0, 1.0)));
+        assert!(block.overlaps(&CollisionBox::new(-1.0, -1.0, -1.0, 3.0, 3.0, 3.0)));
+        // Touching faces don't overlap
+        assert!(!block.overlaps(&CollisionBox::from_block_position(1.0, 0.0, 0.0)));
+        assert!(!block.overlaps(&CollisionBox::new(0.2, 1.0, 0.2, 0.6, 1.8, 0.6)));
+        // Apart on a single axis is enough
+        assert!(!block.overlaps(&CollisionBox::new(0.0, 0.0, 2.0, 1.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn should_land_on_the_floor() {
+        let player = CollisionBox::new(0.2, 1.5, 0.2, 0.6, 1.8, 0.6);
+        let moved = sweep_axis(&player, 1, -2.0, &is_solid);
+        assert!((moved + 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn should_not_be_blocked_by_the_block_it_stands_on() {
+        let player = CollisionBox::new(0.2, 1.0, 0.2, 0.6, 1.8, 0.6);
+        let result = sweep(&player, glam::vec3(1.0, 0.0, 0.0), &is_solid);
+        assert_eq!(result.movement.x, 1.0);
+        assert!(!result.blocked.x);
+    }
+
+    #[test]
+    fn should_stop_against_a_wall() {
+        let player = CollisionBox::new(1.2, 1.0, 0.2, 0.6, 1.8, 0.6);
+        let result = sweep(&player, glam::vec3(5.0, 0.0, 0.0), &is_solid);
+        assert!((result.movement.x - 1.2).abs() < 1e-5);
+        assert!(result.blocked.x);
+    }
+
+    #[test]
+    fn should_stop_against_a_ceiling() {
+        let ceiling = |block: glam::Vec3| block.y == 4.0;
+        let player = CollisionBox::new(0.2, 1.0, 0.2, 0.6, 1.8, 0.6);
+        let result = sweep(&player, glam::vec3(0.0, 2.0, 0.0), &ceiling);
+        assert!((result.movement.y - 1.2).abs() < 1e-5);
+        assert!(result.blocked.y);
+    }
+
+    #[test]
+    fn should_raycast_to_the_first_block_and_face() {
+        let origin = glam::vec3(0.5, 1.5, 0.5);
+        let hit = raycast_blocks(origin, glam::vec3(1.0, 0.0, 0.0), 5.0, &is_solid).unwrap();
+        assert_eq!(hit.block_position, glam::vec3(3.0, 1.0, 0.0));
+        assert_eq!(hit.face, FaceDirections::Left);
+        assert!((hit.distance - 2.5).abs() < 1e-5);
+        assert!((hit.point.x - 3.0).abs() < 1e-5);
+
+        let hit = raycast_blocks(origin, glam::vec3(0.0, -1.0, 0.2), 5.0, &is_solid).unwrap();
+        assert_eq!(hit.block_position, glam::vec3(0.0, 0.0, 0.0));
+        assert_eq!(hit.face, FaceDirections::Top);
+    }
+
+    #[test]
+    fn should_not_raycast_past_the_max_distance() {
+        let origin = glam::vec3(0.5, 1.5, 0.5);
+        assert!(raycast_blocks(origin, glam::vec3(1.0, 0.0, 0.0), 2.0, &is_solid).is_none());
+    }
+}
@@ -0,0 +1,204 @@
+use std::error::Error;
+
+use glam::Vec3;
+
+use crate::blocks::block_type::BlockType;
+use crate::console::{expect_args, CommandRegistry};
+use crate::entity::mob::MobKind;
+use crate::inventory::MAX_STACK_SIZE;
+use crate::player::{GameMode, BLOCK_COLLISION_OFFSET};
+use crate::state::State;
+use crate::world::CHUNK_HEIGHT;
+
+// Farthest /tp goes from the origin, keeps the chunk coordinates far from overflowing
+const MAX_TELEPORT_DISTANCE: f32 = 1_000_000.0;
+
+pub fn register_builtins(commands: &mut CommandRegistry<State>) {
+    commands.register("help", "/help", "Lists the commands", |state, args| {
+        expect_args(args, 0, 0)?;
+        let help = state
+            .console
+            .commands
+            .commands()
+            .iter()
+            .map(|command| format!("{} - {}", command.usage, command.description))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Ok(Some(help))
+    });
+    commands.register(
+        "tp",
+        "/tp <x> <y> <z>",
+        "Teleports to the position, ~ is relative to the current one",
+        |state, args| {
+            expect_args(args, 3, 3)?;
+            let mut player = state.player.write().unwrap();
+            let current = player.get_eye_position();
+            let (horizontal, vertical) = (MAX_TELEPORT_DISTANCE, CHUNK_HEIGHT as f32);
+            let position = Vec3::new(
+                parse_coordinate(args[0], current.x, -horizontal, horizontal)?,
+                parse_coordinate(args[1], current.y, 0.0, vertical)?,
+                parse_coordinate(args[2], current.z, -horizontal, horizontal)?,
+            );
+            player.teleport(position - BLOCK_COLLISION_OFFSET);
+            Ok(Some(format!(
+                "Teleported to {:.1} {:.1} {:.1}",
+                position.x, position.y, position.z
+            )))
+        },
+    );
+    commands.register(
+        "time",
+        "/time set <day|noon|night|midnight|hour> | /time query",
+        "Sets or shows the time of day",
+        |state, args| {
+            expect_args(args, 1, 2)?;
+            match args {
+                ["set", value] => {
+                    state.world.set_time(parse_hours(value)?);
+                    Ok(Some(format!(
+                        "Time set to {}",
+                        format_time(state.world.time)
+                    )))
+                }
+                ["query"] => Ok(Some(format!("Time is {}", format_time(state.world.time)))),
+                _ => Err("Expected set or query".into()),
+            }
+        },
+    );
+    commands.register(
+        "give",
+        "/give <block> [count]",
+        "Adds blocks to the inventory",
+        |state, args| {
+            expect_args(args, 1, 2)?;
+            let block_type =
+                BlockType::from_name(args[0]).ok_or(format!("Unknown block {}", args[0]))?;
+            let count = match args.get(1) {
+                Some(count) => count.parse::<u32>()?,
+                None => MAX_STACK_SIZE,
+            };
+            let leftover = state
+                .player
+                .write()
+                .unwrap()
+                .inventory
+                .add(block_type, count);
+            Ok(Some(format!("Gave {} {:?}", count - leftover, block_type)))
+        },
+    );
+    commands.register(
+        "gamemode",
+        "/gamemode <creative|survival>",
+        "Changes the game mode",
+        |state, args| {
+            expect_args(args, 1, 1)?;
+            let game_mode = GameMode::from_name(args[0])
+                .ok_or_else(|| format!("Unknown game mode {}", args[0]))?;
+            if state.connection.is_some() {
+                return Err("The server decides the game mode".into());
+            }
+            state.player.write().unwrap().set_game_mode(game_mode);
+            Ok(Some(format!("Game mode: {:?}", game_mode)))
+        },
+    );
+    commands.register(
+        "summon",
+        "/summon <mob>",
+        "Spawns a mob where the player stands",
+        |state, args| {
+            expect_args(args, 1, 1)?;
+            if state.connection.is_some() {
+                return Err("Mobs are only spawned by the server".into());
+            }
+            let kind = MobKind::from_name(&args[0].to_lowercase())
+                .ok_or(format!("Unknown mob {}", args[0]))?;
+            let collision = state.player.read().unwrap().get_collision();
+            let center = collision.center();
+            let feet = Vec3::new(center.x, collision.min_y, center.z);
+            state.world.spawn_mob(kind, feet);
+            Ok(Some(format!("Summoned a {}", kind.name())))
+        },
+    );
+    commands.register("seed", "/seed", "Shows the world seed", |state, args| {
+        expect_args(args, 0, 0)?;
+        Ok(Some(format!("Seed: {}", state.world.seed)))
+    });
+    commands.register(
+        "save",
+        "/save",
+        "Saves the world and the player",
+        |state, args| {
+            expect_args(args, 0, 0)?;
+            if state.connection.is_some() {
+                return Err("The server saves its own world".into());
+            }
+            state.save_state();
+            Ok(Some("Saved the game".to_string()))
+        },
+    );
+}
+
+// Absolute value, or offset from current when prefixed with ~, clamped between min and max
+fn parse_coordinate(value: &str, current: f32, min: f32, max: f32) -> Result<f32, Box<dyn Error>> {
+    let coordinate = match value.strip_prefix('~') {
+        Some("") => current,
+        Some(offset) => current + offset.parse::<f32>()?,
+        None => value.parse::<f32>()?,
+    };
+    if !coordinate.is_finite() {
+        return Err(format!("Invalid coordinate {value}").into());
+    }
+    Ok(coordinate.clamp(min, max))
+}
+
+// Named time of day or hour, wrapped into a day by World::set_time
+fn parse_hours(value: &str) -> Result<f32, Box<dyn Error>> {
+    let hours = match value {
+        "day" => 8.0,
+        "noon" => 12.0,
+        "night" => 20.0,
+        "midnight" => 0.0,
+        hours => hours.parse::<f32>()?,
+    };
+    if !hours.is_finite() {
+        return Err(format!("Invalid hour {value}").into());
+    }
+    Ok(hours)
+}
+
+fn format_time(hours: f32) -> String {
+    let minutes = (hours * 60.0) as u32;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coordinates_can_be_relative() {
+        assert_eq!(parse_coordinate("12.5", 3.0, -100.0, 100.0).unwrap(), 12.5);
+        assert_eq!(parse_coordinate("~", 3.0, -100.0, 100.0).unwrap(), 3.0);
+        assert_eq!(parse_coordinate("~-1", 3.0, -100.0, 100.0).unwrap(), 2.0);
+        assert!(parse_coordinate("~x", 3.0, -100.0, 100.0).is_err());
+    }
+
+    #[test]
+    fn hours_are_names_or_finite_numbers() {
+        assert_eq!(parse_hours("noon").unwrap(), 12.0);
+        assert_eq!(parse_hours("30").unwrap(), 30.0);
+        for value in ["nan", "inf", "-inf", "dusk"] {
+            assert!(parse_hours(value).is_err());
+        }
+    }
+
+    #[test]
+    fn coordinates_stay_in_the_world() {
+        assert_eq!(parse_coordinate("1e30", 3.0, -100.0, 100.0).unwrap(), 100.0);
+        assert_eq!(parse_coordinate("~-500", 3.0, 0.0, 100.0).unwrap(), 0.0);
+        for value in ["nan", "inf", "~-inf", "~NaN"] {
+            assert!(parse_coordinate(value, 3.0, -100.0, 100.0).is_err());
+        }
+    }
+}
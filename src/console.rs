@@ -0,0 +1,255 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::rc::Rc;
+
+use crate::state::State;
+
+// Messages kept in the scrollback
+const MAX_MESSAGES: usize = 100;
+const MAX_INPUT_LENGTH: usize = 100;
+// Seconds a message stays on screen while the console is closed
+pub const MESSAGE_VISIBLE_TIME: f32 = 10.0;
+
+// Output printed to the console, if any
+pub type CommandResult = Result<Option<String>, Box<dyn Error>>;
+pub type CommandHandler<C> = Rc<dyn Fn(&mut C, &[&str]) -> CommandResult>;
+// A command and the arguments it was called with
+pub type Invocation<'a, C> = (Command<C>, Vec<&'a str>);
+
+pub struct Command<C> {
+    pub name: String,
+    // Shown when the command fails, e.g. "/tp <x> <y> <z>"
+    pub usage: String,
+    pub description: String,
+    handler: CommandHandler<C>,
+}
+
+impl<C> Clone for Command<C> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            usage: self.usage.clone(),
+            description: self.description.clone(),
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+impl<C> Command<C> {
+    pub fn run(&self, context: &mut C, args: &[&str]) -> CommandResult {
+        (self.handler)(context, args)
+    }
+}
+
+// Slash commands, run against a context (the game's State)
+pub struct CommandRegistry<C> {
+    commands: HashMap<String, Command<C>>,
+}
+
+impl<C> Default for CommandRegistry<C> {
+    fn default() -> Self {
+        Self {
+            commands: HashMap::new(),
+        }
+    }
+}
+
+impl<C> CommandRegistry<C> {
+    // Registers the command under name (without the slash), replacing any previous one
+    pub fn register<F>(&mut self, name: &str, usage: &str, description: &str, handler: F)
+    where
+        F: Fn(&mut C, &[&str]) -> CommandResult + 'static,
+    {
+        self.commands.insert(
+            name.to_lowercase(),
+            Command {
+                name: name.to_lowercase(),
+                usage: usage.to_string(),
+                description: description.to_string(),
+                handler: Rc::new(handler),
+            },
+        );
+    }
+    pub fn get(&self, name: &str) -> Option<&Command<C>> {
+        self.commands.get(&name.to_lowercase())
+    }
+    // Sorted by name
+    pub fn commands(&self) -> Vec<&Command<C>> {
+        let mut commands = self.commands.values().collect::<Vec<_>>();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+    // Finds the command of a "/name arg arg" line. The command is cloned so that it can be run
+    // with a context that owns the registry.
+    pub fn parse<'a>(&self, line: &'a str) -> Result<Invocation<'a, C>, Box<dyn Error>> {
+        let mut words = line
+            .trim()
+            .strip_prefix('/')
+            .ok_or("Commands start with /")?
+            .split_whitespace();
+        let name = words.next().ok_or("Missing command name")?;
+        let command = self
+            .get(name)
+            .ok_or_else(|| format!("Unknown command /{name}, try /help"))?;
+        Ok((command.clone(), words.collect()))
+    }
+}
+
+// Fails unless the number of arguments is between min and max
+pub fn expect_args(args: &[&str], min: usize, max: usize) -> Result<(), Box<dyn Error>> {
+    if args.len() < min || args.len() > max {
+        return Err("Wrong number of arguments".into());
+    }
+    Ok(())
+}
+
+pub struct ConsoleMessage {
+    pub text: String,
+    // Seconds since it was printed
+    pub age: f32,
+}
+
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    // Newest last
+    pub messages: VecDeque<ConsoleMessage>,
+    pub commands: CommandRegistry<State>,
+    // Submitted lines, newest last
+    history: Vec<String>,
+    // Index in history while browsing it with the arrow keys
+    history_position: Option<usize>,
+}
+
+impl Console {
+    // Opens the console with the input set to prefix, e.g. "/" when opened with the slash key
+    pub fn open(&mut self, prefix: &str) {
+        self.open = true;
+        self.input = prefix.to_string();
+        self.history_position = None;
+    }
+    pub fn close(&mut self) {
+        self.open = false;
+        self.input.clear();
+        self.history_position = None;
+    }
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            self.messages.push_back(ConsoleMessage {
+                text: line.to_string(),
+                age: 0.0,
+            });
+        }
+        while self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars().filter(|c| !c.is_control()) {
+            if self.input.chars().count() >= MAX_INPUT_LENGTH {
+                break;
+            }
+            self.input.push(c);
+        }
+    }
+    pub fn backspace(&mut self) {
+        self.input.pop();
+    }
+    pub fn history_previous(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let position = match self.history_position {
+            Some(position) => position.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_position = Some(position);
+        self.input = self.history[position].clone();
+    }
+    pub fn history_next(&mut self) {
+        let Some(position) = self.history_position else {
+            return;
+        };
+        if position + 1 < self.history.len() {
+            self.history_position = Some(position + 1);
+            self.input = self.history[position + 1].clone();
+        } else {
+            self.history_position = None;
+            self.input.clear();
+        }
+    }
+    // Closes the console and returns the typed line, if it isn't blank
+    pub fn submit(&mut self) -> Option<String> {
+        let line = self.input.trim().to_string();
+        self.close();
+        if line.is_empty() {
+            return None;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        Some(line)
+    }
+    pub fn update(&mut self, delta_time: f32) {
+        for message in self.messages.iter_mut() {
+            message.age += delta_time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_parses_and_runs_commands() {
+        let mut registry = CommandRegistry::<Vec<String>>::default();
+        registry.register("Echo", "/echo <text>", "Repeats the text", |log, args| {
+            expect_args(args, 1, usize::MAX)?;
+            log.push(args.join(" "));
+            Ok(None)
+        });
+
+        let mut log = vec![];
+        let (command, args) = registry.parse("  /echo hello   world ").unwrap();
+        command.run(&mut log, &args).unwrap();
+        assert_eq!(log, vec!["hello world"]);
+
+        let (command, args) = registry.parse("/ECHO").unwrap();
+        assert!(command.run(&mut log, &args).is_err());
+        assert!(registry.parse("/missing").is_err());
+        assert!(registry.parse("echo").is_err());
+    }
+
+    #[test]
+    fn history_is_browsed_with_the_arrow_keys() {
+        let mut console = Console::default();
+        for line in ["/seed", "hello"] {
+            console.open("");
+            console.type_text(line);
+            assert_eq!(console.submit().as_deref(), Some(line));
+        }
+        console.open("");
+        console.history_previous();
+        assert_eq!(console.input, "hello");
+        console.history_previous();
+        console.history_previous();
+        assert_eq!(console.input, "/seed");
+        console.history_next();
+        console.history_next();
+        assert_eq!(console.input, "");
+    }
+
+    #[test]
+    fn input_ignores_control_characters() {
+        let mut console = Console::default();
+        console.open("/");
+        console.type_text("tp\r\u{8}");
+        assert_eq!(console.input, "/tp");
+        console.backspace();
+        assert_eq!(console.input, "/t");
+        console.open("");
+        assert_eq!(console.submit(), None);
+    }
+}
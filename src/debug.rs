@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use glam::Vec3;
+
+use crate::blocks::block::FaceDirections;
+use crate::blocks::block_type::BlockType;
+use crate::jobs::{JobStats, JobSystem};
+use crate::player::Player;
+use crate::state::State;
+
+// Seconds over which the frame rate is averaged
+const FRAME_STATS_WINDOW: f32 = 1.0;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FrameStats {
+    frames: u32,
+    elapsed: f32,
+    pub fps: u32,
+    // Average frame time in seconds
+    pub frame_time: f32,
+}
+
+impl FrameStats {
+    pub fn record(&mut self, delta_time: f32) {
+        self.frames += 1;
+        self.elapsed += delta_time;
+        if self.elapsed >= FRAME_STATS_WINDOW {
+            self.fps = (self.frames as f32 / self.elapsed).round() as u32;
+            self.frame_time = self.elapsed / self.frames as f32;
+            self.frames = 0;
+            self.elapsed = 0.0;
+        }
+    }
+}
+
+// Durations of a repeated task, like building chunk meshes
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TimingStats {
+    pub count: u32,
+    pub last: Duration,
+    pub max: Duration,
+    total: Duration,
+}
+
+impl TimingStats {
+    pub fn record(&mut self, duration: Duration) {
+        self.count += 1;
+        self.last = duration;
+        self.max = self.max.max(duration);
+        self.total += duration;
+    }
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count
+        }
+    }
+}
+
+// Everything shown by the debug overlay
+pub struct DebugInfo {
+    pub frame_stats: FrameStats,
+    pub position: Vec3,
+    pub chunk: (i32, i32),
+    pub yaw: f32,
+    pub pitch: f32,
+    pub facing: Option<(Vec3, BlockType, FaceDirections)>,
+    pub loaded_chunks: usize,
+    pub visible_chunks: usize,
+    pub indices: u64,
+    pub water_indices: u64,
+    pub draw_calls: usize,
+    pub loading_chunks: usize,
+    pub jobs: JobStats,
+    pub mesh_timings: TimingStats,
+}
+
+impl DebugInfo {
+    pub fn collect(state: &State, player: &Player) -> Self {
+        let facing = player
+            .facing_block
+            .as_ref()
+            .zip(player.facing_face)
+            .map(|(block, face)| {
+                let block = block.read().unwrap();
+                (block.absolute_position, block.block_type, face)
+            });
+
+        let mut info = DebugInfo {
+            frame_stats: state.frame_stats,
+            position: player.get_eye_position(),
+            chunk: player.calc_current_chunk(),
+            yaw: player.camera.yaw,
+            pitch: player.camera.pitch,
+            facing,
+            loaded_chunks: 0,
+            visible_chunks: 0,
+            indices: 0,
+            water_indices: 0,
+            draw_calls: 0,
+            loading_chunks: state.world.pending_chunks.len(),
+            jobs: state
+                .world
+                .jobs
+                .as_ref()
+                .map_or(JobStats::default(), JobSystem::stats),
+            mesh_timings: *state.world.mesh_timings.read().unwrap(),
+        };
+        info.loaded_chunks = state.world.chunks.read().unwrap().len();
+        let meshes = state.world.renderer().meshes.snapshot();
+        for chunk in meshes.chunks.iter() {
+            if chunk.visible {
+                info.visible_chunks += 1;
+            }
+            info.indices += chunk.indices as u64;
+            info.water_indices += chunk.water_indices as u64;
+        }
+        info.draw_calls = meshes.draw_calls();
+        info
+    }
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{} fps ({:.2} ms)",
+                self.frame_stats.fps,
+                self.frame_stats.frame_time * 1000.0
+            ),
+            format!(
+                "XYZ: {:.2} / {:.2} / {:.2}",
+                self.position.x, self.position.y, self.position.z
+            ),
+            format!("Chunk: {} {}", self.chunk.0, self.chunk.1),
+            format!(
+                "Facing: yaw {:.1} pitch {:.1}",
+                self.yaw.to_degrees(),
+                self.pitch.to_degrees()
+            ),
+        ];
+        lines.push(match self.facing {
+            Some((position, block_type, face)) => format!(
+                "Block: {} {} {} {:?} ({:?})",
+                position.x, position.y, position.z, block_type, face
+            ),
+            None => "Block: none".to_string(),
+        });
+        lines.extend([
+            String::new(),
+            format!(
+                "Chunks: {} loaded, {} loading, {} visible",
+                self.loaded_chunks, self.loading_chunks, self.visible_chunks
+            ),
+            format!(
+                "Indices: {} solid, {} water ({} triangles)",
+                self.indices,
+                self.water_indices,
+                (self.indices + self.water_indices) / 3
+            ),
+            format!("Chunk draw calls: {}", self.draw_calls),
+            format!(
+                "Jobs: {} queued, {} running, {} done, {} cancelled",
+                self.jobs.queued, self.jobs.running, self.jobs.completed, self.jobs.cancelled
+            ),
+            format!(
+                "Meshes: {} built, last {:.2} ms, avg {:.2} ms, max {:.2} ms",
+                self.mesh_timings.count,
+                self.mesh_timings.last.as_secs_f64() * 1000.0,
+                self.mesh_timings.average().as_secs_f64() * 1000.0,
+                self.mesh_timings.max.as_secs_f64() * 1000.0
+            ),
+        ]);
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_stats_average_over_the_window() {
+        let mut stats = FrameStats::default();
+        for _ in 0..3 {
+            stats.record(0.25);
+        }
+        assert_eq!(stats.fps, 0);
+        stats.record(0.25);
+        assert_eq!(stats.fps, 4);
+        assert_eq!(stats.frame_time, 0.25);
+    }
+
+    #[test]
+    fn timing_stats() {
+        let mut stats = TimingStats::default();
+        assert_eq!(stats.average(), Duration::ZERO);
+        stats.record(Duration::from_millis(2));
+        stats.record(Duration::from_millis(4));
+        assert_eq!(stats.average(), Duration::from_millis(3));
+        assert_eq!(stats.max, Duration::from_millis(4));
+        assert_eq!(stats.last, Duration::from_millis(4));
+    }
+}
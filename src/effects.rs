@@ -13,7 +13,7 @@ pub mod ao {
     }
     pub(crate) fn from_vertex_position(
         vertex_position: &glam::Vec3,
-        blocks_positions: &Vec<((i32, i32), BlockVec)>,
+        blocks_positions: &[((i32, i32), BlockVec)],
     ) -> u8 {
         let side1_position = *vertex_position + glam::vec3(1.0, 1.0, 0.0);
         let side2_position = *vertex_position + glam::vec3(0.0, 1.0, 1.0);
@@ -46,11 +46,9 @@ pub mod ao {
             }) {
                 let blocks = blocks.read().unwrap();
                 let ycol = &blocks[((position.x * CHUNK_SIZE as f32) + position.z) as usize];
-                if let Some(place) = ycol.get(position.y as usize) {
-                    if let Some(block) = place {
-                        if block.read().unwrap().block_type != BlockType::Water {
-                            *val = true
-                        }
+                if let Some(Some(block)) = ycol.get(position.y as usize) {
+                    if block.read().unwrap().block_type != BlockType::Water {
+                        *val = true
                     }
                 }
             }
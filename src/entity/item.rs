@@ -0,0 +1,62 @@
+use glam::{vec3, Vec3};
+
+use crate::blocks::block_type::BlockType;
+
+use super::{Entity, EntityKind};
+
+pub const ITEM_SIZE: f32 = 0.25;
+// Seconds before a dropped item can be collected, so that it's seen popping out of the block
+pub const PICKUP_DELAY: f32 = 0.5;
+pub const PICKUP_RADIUS: f32 = 1.5;
+pub const DESPAWN_TIME: f32 = 300.0;
+const POP_VELOCITY: f32 = 4.0;
+// Deceleration of items sliding on the ground
+pub const ITEM_FRICTION: f32 = 8.0;
+
+impl Entity {
+    // Item popping out of the block at the position, waiting for the player to pick it up
+    pub fn dropped_item(block_type: BlockType, block_position: Vec3) -> Self {
+        let center = block_position + Vec3::splat(0.5);
+        let mut item = Entity::new(
+            EntityKind::Item(block_type),
+            center - vec3(0.0, ITEM_SIZE / 2.0, 0.0),
+        );
+        item.velocity = vec3(0.0, POP_VELOCITY, 0.0);
+        item
+    }
+    pub fn can_be_picked_up(&self, collector: Vec3) -> bool {
+        matches!(self.kind, EntityKind::Item(_))
+            && self.age >= PICKUP_DELAY
+            && self.center().distance(collector) <= PICKUP_RADIUS
+    }
+    // Items left on the ground for too long disappear
+    pub fn is_expired(&self) -> bool {
+        matches!(self.kind, EntityKind::Item(_)) && self.age >= DESPAWN_TIME
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn falls_on_the_ground() {
+        let is_solid = |block: Vec3| (block.y < 0.0).then_some(BlockType::Stone);
+        let mut item = Entity::dropped_item(BlockType::Dirt, vec3(0.0, 2.5, 0.0));
+        for _ in 0..120 {
+            item.update(1.0 / 60.0, &is_solid);
+        }
+        assert!((item.get_collision().min().y - 0.0).abs() < 0.01);
+        assert_eq!(item.velocity.y, 0.0);
+    }
+
+    #[test]
+    fn pickup_waits_for_delay() {
+        let mut item = Entity::dropped_item(BlockType::Dirt, Vec3::ZERO);
+        let center = item.center();
+        assert!(!item.can_be_picked_up(center));
+        item.age = PICKUP_DELAY;
+        assert!(item.can_be_picked_up(center));
+        assert!(!item.can_be_picked_up(center + vec3(PICKUP_RADIUS * 2.0, 0.0, 0.0)));
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+
+use glam::{vec2, vec3, IVec3, Vec3};
+use rand::Rng;
+
+use crate::blocks::block_type::BlockType;
+use crate::player::PLAYER_EYE_HEIGHT;
+
+// Chance of walking somewhere rather than idling when a mob picks what to do next
+const WALK_CHANCE: f64 = 0.6;
+// Seconds spent walking or idling before picking again
+const WALK_TIME: std::ops::Range<f32> = 4.0..8.0;
+const IDLE_TIME: std::ops::Range<f32> = 2.0..6.0;
+// Max blocks away from the mob on each axis of the places it wanders to
+const WANDER_RANGE: i32 = 8;
+// Players holding the mob's food are followed from this far, until the mob is close enough
+const FOLLOW_RANGE: f32 = 10.0;
+const FOLLOW_DISTANCE: f32 = 2.0;
+// Sprinting players closer than this scare the mob away
+const FLEE_RANGE: f32 = 6.0;
+const FLEE_DISTANCE: f32 = 10.0;
+// Seconds before the path to a moving player is searched again
+const REPATH_TIME: f32 = 1.0;
+// Horizontal distance to the center of a block for it to be reached
+const WAYPOINT_RADIUS: f32 = 0.35;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MobKind {
+    Pig,
+}
+
+impl MobKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MobKind::Pig => "pig",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "pig" => Some(MobKind::Pig),
+            _ => None,
+        }
+    }
+    // Width and height of the collision box
+    pub fn size(&self) -> (f32, f32) {
+        match self {
+            MobKind::Pig => (0.9, 0.9),
+        }
+    }
+    // Blocks of room the mob needs to walk somewhere
+    pub fn height_in_blocks(&self) -> i32 {
+        self.size().1.ceil() as i32
+    }
+    pub fn walk_speed(&self) -> f32 {
+        match self {
+            MobKind::Pig => 1.5,
+        }
+    }
+    // Block that the mob follows players holding it
+    pub fn food(&self) -> BlockType {
+        match self {
+            MobKind::Pig => BlockType::Grass,
+        }
+    }
+}
+
+// What mobs know about a player around them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NearbyPlayer {
+    // Center of the bottom of the player, in collision space
+    pub feet: Vec3,
+    pub held: Option<BlockType>,
+    pub is_sprinting: bool,
+}
+
+impl NearbyPlayer {
+    pub fn from_eye(eye: Vec3, held: Option<BlockType>, is_sprinting: bool) -> Self {
+        Self {
+            feet: eye - vec3(0.0, PLAYER_EYE_HEIGHT, 0.0),
+            held,
+            is_sprinting,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behaviour {
+    Idle,
+    Wander,
+    Follow,
+    Flee,
+}
+
+// Passive mob that wanders around, follows the players holding its food and flees the ones running at it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mob {
+    pub kind: MobKind,
+    pub behaviour: Behaviour,
+    // Yaw the mob walks towards, None while it stands still
+    pub heading: Option<f32>,
+    // Blocks left to walk through, in collision space
+    pub path: VecDeque<IVec3>,
+    // Path search the mob waits for, the world hands over its result
+    pub pending_path: Option<u64>,
+    // Seconds before picking what to do next
+    timer: f32,
+}
+
+impl Mob {
+    pub fn new(kind: MobKind) -> Self {
+        Self {
+            kind,
+            behaviour: Behaviour::Idle,
+            heading: None,
+            path: VecDeque::new(),
+            pending_path: None,
+            timer: 0.0,
+        }
+    }
+    // Picks what to do from the players around, and steers towards the next block of the path.
+    // Returns the block to search a path to when the mob wants to go somewhere new.
+    pub fn think(
+        &mut self,
+        delta_time: f32,
+        position: Vec3,
+        players: &[NearbyPlayer],
+    ) -> Option<IVec3> {
+        self.timer -= delta_time;
+        let feet = position.floor().as_ivec3();
+        let closest = |wanted: &dyn Fn(&NearbyPlayer) -> bool, range: f32| {
+            players
+                .iter()
+                .filter(|player| wanted(player))
+                .map(|player| (player, player.feet.distance(position)))
+                .filter(|(_, distance)| *distance <= range)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+        };
+        let threat = closest(&|player| player.is_sprinting, FLEE_RANGE);
+        let leader = closest(
+            &|player| player.held == Some(self.kind.food()),
+            FOLLOW_RANGE,
+        );
+
+        let goal = if let Some((threat, _)) = threat {
+            if self.behaviour == Behaviour::Flee && self.timer > 0.0 {
+                None
+            } else {
+                self.start(Behaviour::Flee, REPATH_TIME);
+                let away = (position - threat.feet) * vec3(1.0, 0.0, 1.0);
+                let away = away.try_normalize().unwrap_or(Vec3::X) * FLEE_DISTANCE;
+                Some(feet + away.round().as_ivec3())
+            }
+        } else if let Some((leader, distance)) = leader {
+            if distance <= FOLLOW_DISTANCE {
+                self.start(Behaviour::Follow, 0.0);
+                self.stop();
+                None
+            } else if self.behaviour == Behaviour::Follow && self.timer > 0.0 {
+                None
+            } else {
+                self.start(Behaviour::Follow, REPATH_TIME);
+                Some(leader.feet.floor().as_ivec3())
+            }
+        } else if self.timer <= 0.0 || matches!(self.behaviour, Behaviour::Follow | Behaviour::Flee)
+        {
+            let mut rng = rand::thread_rng();
+            if self.behaviour == Behaviour::Idle && rng.gen_bool(WALK_CHANCE) {
+                self.start(Behaviour::Wander, rng.gen_range(WALK_TIME));
+                let offset = IVec3::new(
+                    rng.gen_range(-WANDER_RANGE..=WANDER_RANGE),
+                    0,
+                    rng.gen_range(-WANDER_RANGE..=WANDER_RANGE),
+                );
+                Some(feet + offset)
+            } else {
+                self.start(Behaviour::Idle, rng.gen_range(IDLE_TIME));
+                self.stop();
+                None
+            }
+        } else {
+            None
+        };
+        self.steer(position);
+        goal
+    }
+    fn start(&mut self, behaviour: Behaviour, time: f32) {
+        self.behaviour = behaviour;
+        self.timer = time;
+    }
+    // Drops the path, and the one being searched
+    fn stop(&mut self) {
+        self.path.clear();
+        self.pending_path = None;
+    }
+    // Walks the path from its block closest to the mob, as it may have moved during the search
+    pub fn set_path(&mut self, path: Vec<IVec3>, position: Vec3) {
+        let closest = path
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                let a = waypoint_center(**a).distance(position);
+                let b = waypoint_center(**b).distance(position);
+                a.total_cmp(&b)
+            })
+            .map_or(0, |(index, _)| index);
+        self.path = path.into_iter().skip(closest).collect();
+        self.pending_path = None;
+    }
+    // Drops the blocks of the path that are reached, and heads to the next one
+    fn steer(&mut self, position: Vec3) {
+        while let Some(waypoint) = self.path.front() {
+            let offset = waypoint_center(*waypoint) - position;
+            if vec2(offset.x, offset.z).length() > WAYPOINT_RADIUS {
+                break;
+            }
+            self.path.pop_front();
+        }
+        self.heading = self.path.front().map(|waypoint| {
+            let offset = waypoint_center(*waypoint) - position;
+            offset.z.atan2(offset.x)
+        });
+    }
+}
+
+// Where a mob stands in the middle of the block, in collision space
+fn waypoint_center(block: IVec3) -> Vec3 {
+    block.as_vec3() + vec3(0.5, 0.0, 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::ivec3;
+
+    #[test]
+    fn mobs_follow_food_and_flee_sprinting_players() {
+        let mut pig = Mob::new(MobKind::Pig);
+        let position = vec3(0.5, 10.0, 0.5);
+        let mut player = NearbyPlayer {
+            feet: vec3(6.5, 10.0, 0.5),
+            held: Some(BlockType::Grass),
+            is_sprinting: false,
+        };
+        assert_eq!(pig.think(0.1, position, &[player]), Some(ivec3(6, 10, 0)));
+        assert_eq!(pig.behaviour, Behaviour::Follow);
+        // The path isn't searched again until the player had time to move
+        assert_eq!(pig.think(0.1, position, &[player]), None);
+
+        pig.set_path(vec![ivec3(1, 10, 0), ivec3(2, 10, 0)], position);
+        pig.think(0.1, position, &[player]);
+        assert_eq!(pig.heading, Some(0.0));
+
+        player.feet = vec3(3.5, 10.0, 0.5);
+        player.is_sprinting = true;
+        assert_eq!(pig.think(0.1, position, &[player]), Some(ivec3(-10, 10, 0)));
+        assert_eq!(pig.behaviour, Behaviour::Flee);
+    }
+}
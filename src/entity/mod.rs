@@ -0,0 +1,259 @@
+pub mod item;
+pub mod mob;
+pub mod pathfinding;
+pub mod spawning;
+
+use std::error::Error;
+use std::f32::consts::{PI, TAU};
+
+use glam::{vec2, vec3, Vec3};
+
+use crate::blocks::block_type::BlockType;
+use crate::collision::{self, CollisionBox};
+use crate::player::{
+    self, AIR_ACCELERATION, BUOYANCY, GRAVITY, GROUND_ACCELERATION, JUMP_VELOCITY,
+    MAX_PHYSICS_STEP, STEP_HEIGHT, SWIM_VERTICAL_ACCELERATION, TERMINAL_VELOCITY, WATER_DRAG,
+};
+
+use self::item::{ITEM_FRICTION, ITEM_SIZE};
+use self::mob::{Mob, MobKind};
+
+// Radians per second a mob turns towards where it walks
+const TURN_SPEED: f32 = 5.0;
+// Lines of the chunk files that hold an entity start with this
+const SAVE_PREFIX: &str = "entity";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntityKind {
+    // Block waiting for a player to pick it up
+    Item(BlockType),
+    Mob(Mob),
+}
+
+// Anything that moves in the world besides the players, it uses the same physics as them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entity {
+    // Center of the bottom of the box, in collision space
+    pub position: Vec3,
+    pub velocity: Vec3,
+    // Direction the entity faces, like the camera's yaw
+    pub yaw: f32,
+    pub age: f32,
+    pub on_ground: bool,
+    pub in_water: bool,
+    // Horizontal distance moved so far, drives the walk animation
+    pub walked: f32,
+    pub kind: EntityKind,
+}
+
+impl Entity {
+    pub fn new(kind: EntityKind, position: Vec3) -> Self {
+        Self {
+            position,
+            velocity: Vec3::ZERO,
+            yaw: 0.0,
+            age: 0.0,
+            on_ground: false,
+            in_water: false,
+            walked: 0.0,
+            kind,
+        }
+    }
+    pub fn mob(kind: MobKind, position: Vec3) -> Self {
+        Entity::new(EntityKind::Mob(Mob::new(kind)), position)
+    }
+    // Width and height of the collision box
+    pub fn size(&self) -> (f32, f32) {
+        match &self.kind {
+            EntityKind::Item(_) => (ITEM_SIZE, ITEM_SIZE),
+            EntityKind::Mob(mob) => mob.kind.size(),
+        }
+    }
+    pub fn get_collision(&self) -> CollisionBox {
+        let (width, height) = self.size();
+        CollisionBox::new(
+            self.position.x - width / 2.0,
+            self.position.y,
+            self.position.z - width / 2.0,
+            width,
+            height,
+            width,
+        )
+    }
+    pub fn center(&self) -> Vec3 {
+        self.position + vec3(0.0, self.size().1 / 2.0, 0.0)
+    }
+    pub fn update<F>(&mut self, delta_time: f32, block_type_at: &F)
+    where
+        F: Fn(Vec3) -> Option<BlockType>,
+    {
+        self.age += delta_time;
+        let mut remaining = delta_time;
+        while remaining > 0.0 {
+            let step = f32::min(remaining, MAX_PHYSICS_STEP);
+            self.physics_step(step, block_type_at);
+            remaining -= step;
+        }
+    }
+    fn physics_step<F>(&mut self, delta_time: f32, block_type_at: &F)
+    where
+        F: Fn(Vec3) -> Option<BlockType>,
+    {
+        let is_solid = |position: Vec3| block_type_at(position).is_some_and(|b| b.is_solid());
+        let collision = self.get_collision();
+        let submerged = player::submerged_fraction(&collision, block_type_at);
+        self.in_water = submerged > 0.0;
+
+        let (target, acceleration, step_height) = match &self.kind {
+            EntityKind::Item(_) => {
+                let friction = if self.on_ground { ITEM_FRICTION } else { 0.0 };
+                (Vec3::ZERO, friction, 0.0)
+            }
+            EntityKind::Mob(mob) => {
+                let acceleration = if self.on_ground {
+                    GROUND_ACCELERATION
+                } else {
+                    AIR_ACCELERATION
+                };
+                let target = match mob.heading {
+                    Some(heading) => {
+                        self.yaw = turn_towards(self.yaw, heading, TURN_SPEED * delta_time);
+                        vec3(self.yaw.cos(), 0.0, self.yaw.sin()) * mob.kind.walk_speed()
+                    }
+                    None => Vec3::ZERO,
+                };
+                (target, acceleration, STEP_HEIGHT)
+            }
+        };
+        self.velocity = player::accelerate(self.velocity, target, acceleration, delta_time);
+
+        if self.in_water {
+            self.velocity.y += (BUOYANCY * submerged - GRAVITY) * delta_time;
+            // Mobs paddle to keep their head out of the water
+            if matches!(self.kind, EntityKind::Mob(_)) {
+                self.velocity.y += SWIM_VERTICAL_ACCELERATION * submerged * delta_time;
+            }
+            self.velocity *= f32::max(1.0 - WATER_DRAG * delta_time, 0.0);
+        } else {
+            self.velocity.y -= GRAVITY * delta_time;
+        }
+        self.velocity.y = f32::max(self.velocity.y, -TERMINAL_VELOCITY);
+
+        let delta = self.velocity * delta_time;
+        let result = if self.on_ground {
+            collision::sweep_with_step(&collision, delta, step_height, &is_solid)
+        } else {
+            collision::sweep(&collision, delta, &is_solid)
+        };
+        self.on_ground = result.blocked.y && delta.y <= 0.0;
+        let against_wall = result.blocked.x || result.blocked.z;
+        for axis in 0..3 {
+            if result.blocked.test(axis) {
+                self.velocity[axis] = 0.0;
+            }
+        }
+        // Mobs jump over the blocks too high to step on
+        if let EntityKind::Mob(mob) = &self.kind {
+            if mob.heading.is_some() && against_wall && self.on_ground {
+                self.velocity.y = JUMP_VELOCITY;
+            }
+        }
+        self.position += result.movement;
+        self.walked += vec2(result.movement.x, result.movement.z).length();
+    }
+    // Line of the chunk files that holds the entity
+    pub fn to_line(&self) -> String {
+        let kind = match &self.kind {
+            EntityKind::Item(block_type) => format!("item:{}", block_type.to_id()),
+            EntityKind::Mob(mob) => mob.kind.name().to_string(),
+        };
+        let Entity {
+            position: p,
+            velocity: v,
+            ..
+        } = self;
+        format!(
+            "{SAVE_PREFIX},{kind},{},{},{},{},{},{},{},{}",
+            p.x, p.y, p.z, v.x, v.y, v.z, self.yaw, self.age
+        )
+    }
+    pub fn is_line(line: &str) -> bool {
+        line.starts_with(SAVE_PREFIX)
+    }
+    pub fn from_line(line: &str) -> Result<Self, Box<dyn Error>> {
+        let mut values = line.split(',');
+        if values.next() != Some(SAVE_PREFIX) {
+            return Err("Not an entity".into());
+        }
+        let kind = match values.next().ok_or("Missing entity kind")? {
+            item if item.starts_with("item:") => {
+                let id = item["item:".len()..].parse::<u32>()?;
+                if id > BlockType::MAX_ID {
+                    return Err(format!("Unknown block id {id}").into());
+                }
+                EntityKind::Item(BlockType::from_id(id))
+            }
+            name => EntityKind::Mob(Mob::new(
+                MobKind::from_name(name).ok_or(format!("Unknown entity {name}"))?,
+            )),
+        };
+        let mut next = || -> Result<f32, Box<dyn Error>> {
+            Ok(values
+                .next()
+                .ok_or("Missing entity value")?
+                .parse::<f32>()?)
+        };
+        let position = vec3(next()?, next()?, next()?);
+        let mut entity = Entity::new(kind, position);
+        entity.velocity = vec3(next()?, next()?, next()?);
+        entity.yaw = next()?;
+        entity.age = next()?;
+        Ok(entity)
+    }
+}
+
+// Turns from the angle towards the target by at most max_turn, the short way around
+fn turn_towards(angle: f32, target: f32, max_turn: f32) -> f32 {
+    let difference = (target - angle + PI).rem_euclid(TAU) - PI;
+    angle + difference.clamp(-max_turn, max_turn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entities_are_saved_as_lines() {
+        let mut pig = Entity::mob(MobKind::Pig, vec3(1.5, 40.0, -3.25));
+        pig.velocity = vec3(0.5, -1.0, 0.0);
+        pig.yaw = 1.0;
+        pig.age = 12.5;
+        let item = Entity::dropped_item(BlockType::Stone, vec3(2.0, 3.0, 4.0));
+        for entity in [pig, item] {
+            let line = entity.to_line();
+            assert!(Entity::is_line(&line));
+            assert_eq!(Entity::from_line(&line).unwrap(), entity);
+        }
+        assert!(Entity::from_line("entity,dragon,0,0,0,0,0,0,0,0").is_err());
+        assert!(Entity::from_line("entity,pig,0,0").is_err());
+        assert!(Entity::from_line("entity,item:99,0,0,0,0,0,0,0,0").is_err());
+    }
+
+    #[test]
+    fn mobs_jump_on_blocks_in_their_way() {
+        // Ground at y = 0, with a wall one block high from x = 2
+        let block_type_at = |block: Vec3| {
+            (block.y < 0.0 || (block.x >= 2.0 && block.y < 1.0)).then_some(BlockType::Stone)
+        };
+        let mut pig = Entity::mob(MobKind::Pig, vec3(0.5, 0.0, 0.5));
+        for _ in 0..240 {
+            if let EntityKind::Mob(mob) = &mut pig.kind {
+                mob.heading = Some(0.0);
+            }
+            pig.physics_step(1.0 / 60.0, &block_type_at);
+        }
+        assert!(pig.position.x > 3.0);
+        assert!((pig.position.y - 1.0).abs() < 0.01);
+        assert!(pig.walked > 2.5);
+    }
+}
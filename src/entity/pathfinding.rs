@@ -0,0 +1,230 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+
+use glam::{ivec3, IVec3, Vec3};
+
+use crate::blocks::block_type::BlockType;
+use crate::jobs::{JobHandle, JobResult, JobSystem, Priority};
+use crate::world::{ChunkMap, World};
+
+// Nodes explored before giving up, the path to the closest one is returned then
+pub const MAX_NODES: usize = 1024;
+// Blocks a mob walks down without going around
+const MAX_DROP: i32 = 2;
+// Extra cost of jumping on a block, so that flat paths are preferred
+const JUMP_COST: u32 = 1;
+const DIRECTIONS: [IVec3; 4] = [
+    ivec3(1, 0, 0),
+    ivec3(-1, 0, 0),
+    ivec3(0, 0, 1),
+    ivec3(0, 0, -1),
+];
+
+// Whether a mob `height` blocks tall can stand with its feet in the block: on solid ground, out of the water
+pub fn is_walkable<F>(feet: IVec3, height: i32, block_type_at: &F) -> bool
+where
+    F: Fn(Vec3) -> Option<BlockType>,
+{
+    block_type_at((feet - IVec3::Y).as_vec3()).is_some_and(|b| b.is_solid())
+        && (0..height).all(|dy| is_free(feet + IVec3::Y * dy, block_type_at))
+}
+
+fn is_free<F>(block: IVec3, block_type_at: &F) -> bool
+where
+    F: Fn(Vec3) -> Option<BlockType>,
+{
+    block_type_at(block.as_vec3()).is_none()
+}
+
+// Where a mob standing in the block can go in one move, with the cost of the move
+fn neighbours<F>(feet: IVec3, height: i32, block_type_at: &F) -> Vec<(IVec3, u32)>
+where
+    F: Fn(Vec3) -> Option<BlockType>,
+{
+    let mut neighbours = vec![];
+    for direction in DIRECTIONS {
+        let next = feet + direction;
+        if is_walkable(next, height, block_type_at) {
+            neighbours.push((next, 1));
+        } else if !is_free(next, block_type_at) {
+            // Jumping needs room above the head before moving over the block
+            if is_free(feet + IVec3::Y * height, block_type_at)
+                && is_walkable(next + IVec3::Y, height, block_type_at)
+            {
+                neighbours.push((next + IVec3::Y, 1 + JUMP_COST));
+            }
+        } else if (0..height).all(|dy| is_free(next + IVec3::Y * dy, block_type_at)) {
+            // Walking off the edge, only if the ground below is close and dry
+            for drop in 1..=MAX_DROP {
+                let below = next - IVec3::Y * drop;
+                if is_walkable(below, height, block_type_at) {
+                    neighbours.push((below, 1));
+                    break;
+                }
+                if !is_free(below, block_type_at) {
+                    break;
+                }
+            }
+        }
+    }
+    neighbours
+}
+
+fn distance(a: IVec3, b: IVec3) -> u32 {
+    let difference = (a - b).abs();
+    (difference.x + difference.y + difference.z) as u32
+}
+
+// A* search over the blocks a mob can stand in, from the block its feet are in to the goal.
+// Returns the blocks to walk through, without the start, or None if the mob can't get any closer.
+pub fn find_path<F>(start: IVec3, goal: IVec3, height: i32, block_type_at: &F) -> Option<Vec<IVec3>>
+where
+    F: Fn(Vec3) -> Option<BlockType>,
+{
+    let mut open = BinaryHeap::from([Reverse((distance(start, goal), 0, start.to_array()))]);
+    let mut came_from: HashMap<IVec3, IVec3> = HashMap::new();
+    let mut costs = HashMap::from([(start, 0)]);
+    let mut closest = start;
+
+    while let Some(Reverse((_, cost, node))) = open.pop() {
+        let node = IVec3::from_array(node);
+        if cost > costs[&node] {
+            continue;
+        }
+        if distance(node, goal) < distance(closest, goal) {
+            closest = node;
+        }
+        if node == goal || costs.len() >= MAX_NODES {
+            break;
+        }
+        for (next, move_cost) in neighbours(node, height, block_type_at) {
+            let next_cost = cost + move_cost;
+            if costs.get(&next).is_none_or(|&known| next_cost < known) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, node);
+                open.push(Reverse((
+                    next_cost + distance(next, goal),
+                    next_cost,
+                    next.to_array(),
+                )));
+            }
+        }
+    }
+
+    if closest == start {
+        return None;
+    }
+    let mut path = vec![closest];
+    while let Some(&previous) = came_from.get(path.last().unwrap()) {
+        if previous == start {
+            break;
+        }
+        path.push(previous);
+    }
+    path.reverse();
+    Some(path)
+}
+
+type Path = Option<Vec<IVec3>>;
+
+// Path searches of the mobs, run in the background so that they don't hold up the tick.
+// The results are collected on the next ticks.
+#[derive(Default)]
+pub struct PathJobs {
+    searches: HashMap<u64, JobHandle<Path>>,
+    next_ticket: u64,
+}
+
+impl PathJobs {
+    // Starts searching a path, returns the ticket its result comes with
+    pub fn request(
+        &mut self,
+        jobs: &JobSystem,
+        chunks: &ChunkMap,
+        start: IVec3,
+        goal: IVec3,
+        height: i32,
+    ) -> u64 {
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        let chunks = Arc::clone(chunks);
+        let handle = jobs.spawn(Priority::BACKGROUND, move || {
+            let block_type_at = |block: Vec3| World::block_type_in(&chunks, &block);
+            find_path(start, goal, height, &block_type_at)
+        });
+        self.searches.insert(ticket, handle);
+        ticket
+    }
+    // For the mobs that changed their mind or left the world
+    pub fn cancel(&mut self, ticket: u64) {
+        if let Some(handle) = self.searches.remove(&ticket) {
+            handle.cancel();
+        }
+    }
+    pub fn finished(&mut self) -> Vec<(u64, Path)> {
+        let mut finished = vec![];
+        self.searches
+            .retain(|ticket, handle| match handle.try_result() {
+                JobResult::Pending => true,
+                JobResult::Done(path) => {
+                    finished.push((*ticket, path));
+                    false
+                }
+                JobResult::Lost => false,
+            });
+        finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Flat ground at y = 0 with a pond in x = 3..=4, z = -5..=5, and a block to jump on at (2, 1, 8)
+    fn block_type_at(block: Vec3) -> Option<BlockType> {
+        let block = block.floor().as_ivec3();
+        if block.y < 0 && (3..=4).contains(&block.x) && (-5..=5).contains(&block.z) {
+            Some(if block.y == -1 {
+                BlockType::Water
+            } else {
+                BlockType::Stone
+            })
+        } else if block.y < 0 || block == ivec3(2, 0, 8) {
+            Some(BlockType::Stone)
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn paths_go_around_water() {
+        let path = find_path(ivec3(0, 0, 0), ivec3(7, 0, 0), 1, &block_type_at).unwrap();
+        assert_eq!(path.last(), Some(&ivec3(7, 0, 0)));
+        for step in path.iter() {
+            assert!(is_walkable(*step, 1, &block_type_at));
+            assert!(!(3..=4).contains(&step.x) || step.z.abs() > 5);
+        }
+    }
+
+    #[test]
+    fn paths_jump_one_block() {
+        let path = find_path(ivec3(0, 0, 8), ivec3(2, 1, 8), 1, &block_type_at).unwrap();
+        assert_eq!(path, vec![ivec3(1, 0, 8), ivec3(2, 1, 8)]);
+        // Two blocks high walls can't be jumped over
+        let wall = |block: Vec3| {
+            (block.y < 0.0 || (block.x >= 1.0 && block.y < 2.0)).then_some(BlockType::Stone)
+        };
+        assert_eq!(find_path(ivec3(0, 0, 0), ivec3(1, 2, 0), 1, &wall), None);
+    }
+
+    #[test]
+    fn paths_avoid_high_drops() {
+        // A cliff three blocks high from x = 1
+        let cliff = |block: Vec3| {
+            let ground = if block.x >= 1.0 { -3.0 } else { 0.0 };
+            (block.y < ground).then_some(BlockType::Stone)
+        };
+        assert_eq!(find_path(ivec3(0, 0, 0), ivec3(3, -3, 0), 1, &cliff), None);
+    }
+}
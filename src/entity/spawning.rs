@@ -0,0 +1,89 @@
+use std::f32::consts::TAU;
+use std::ops::Range;
+
+use glam::vec2;
+use rand::Rng;
+
+use crate::blocks::block_type::BlockType;
+
+use super::mob::{MobKind, NearbyPlayer};
+
+// Seconds between two spawn attempts
+pub const SPAWN_INTERVAL: f32 = 2.0;
+// No mob spawns naturally while there are this many in the loaded chunks
+pub const MOB_CAP: usize = 12;
+// Horizontal distance to a player of the columns where mobs spawn, out of sight but still loaded
+const SPAWN_DISTANCE: Range<f32> = 24.0..48.0;
+const MOUNTAIN_HEIGHT: i32 = 60;
+
+// The terrain has no biome map, the biome of a column comes from its surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+    Ocean,
+    Beach,
+    Plains,
+    Forest,
+    Mountains,
+}
+
+impl Biome {
+    // `surface` is the top block of the column, at the height `y`
+    pub fn from_surface(surface: BlockType, y: i32) -> Self {
+        match surface {
+            BlockType::Water => Biome::Ocean,
+            BlockType::Sand => Biome::Beach,
+            BlockType::Leaf | BlockType::Wood => Biome::Forest,
+            BlockType::Stone => Biome::Mountains,
+            _ if y >= MOUNTAIN_HEIGHT => Biome::Mountains,
+            _ => Biome::Plains,
+        }
+    }
+}
+
+impl MobKind {
+    pub fn spawns_in(&self, biome: Biome) -> bool {
+        match self {
+            MobKind::Pig => biome == Biome::Plains,
+        }
+    }
+    // Light the mob needs to spawn, from 0 in the dark to 1 under the sun at noon
+    pub fn min_spawn_light(&self) -> f32 {
+        match self {
+            MobKind::Pig => 0.5,
+        }
+    }
+    pub fn can_spawn(&self, surface: BlockType, y: i32, light: f32) -> bool {
+        self.spawns_in(Biome::from_surface(surface, y)) && light >= self.min_spawn_light()
+    }
+}
+
+// Random column around one of the players where a mob may spawn
+pub fn spawn_column<R: Rng>(rng: &mut R, players: &[NearbyPlayer]) -> Option<(i32, i32)> {
+    if players.is_empty() {
+        return None;
+    }
+    let player = players[rng.gen_range(0..players.len())];
+    let angle = rng.gen_range(0.0..TAU);
+    let column = vec2(player.feet.x, player.feet.z)
+        + vec2(angle.cos(), angle.sin()) * rng.gen_range(SPAWN_DISTANCE);
+    let column = column.floor().as_ivec2();
+    // Mobs don't appear right next to another player either
+    let near_player = players.iter().any(|player| {
+        vec2(player.feet.x, player.feet.z).distance(column.as_vec2()) < SPAWN_DISTANCE.start
+    });
+    (!near_player).then_some((column.x, column.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pigs_spawn_on_lit_plains() {
+        assert!(MobKind::Pig.can_spawn(BlockType::Grass, 20, 1.0));
+        assert!(!MobKind::Pig.can_spawn(BlockType::Grass, 20, 0.1));
+        assert!(!MobKind::Pig.can_spawn(BlockType::Sand, 4, 1.0));
+        assert!(!MobKind::Pig.can_spawn(BlockType::Water, 5, 1.0));
+        assert!(!MobKind::Pig.can_spawn(BlockType::Grass, MOUNTAIN_HEIGHT, 1.0));
+    }
+}
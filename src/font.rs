@@ -0,0 +1,73 @@
+use glam::{vec2, Vec2};
+
+// Metrics of the bitmap font in assets/font.png. It has the printable ASCII characters
+// in a grid of 8x8 pixel cells, starting from the space.
+pub const GLYPH_WIDTH: f32 = 5.0;
+pub const GLYPH_HEIGHT: f32 = 8.0;
+// Horizontal distance between glyphs and vertical distance between lines
+pub const ADVANCE: f32 = 6.0;
+pub const LINE_HEIGHT: f32 = 10.0;
+
+const CELL_SIZE: f32 = 8.0;
+const ATLAS_COLUMNS: u32 = 16;
+const ATLAS_WIDTH: f32 = 128.0;
+const ATLAS_HEIGHT: f32 = 48.0;
+const FIRST_CHAR: u32 = ' ' as u32;
+const LAST_CHAR: u32 = '~' as u32;
+// Drawn for characters that aren't in the font
+const FALLBACK_CHAR: char = '?';
+
+pub fn has_glyph(c: char) -> bool {
+    (FIRST_CHAR..=LAST_CHAR).contains(&(c as u32))
+}
+
+// Texture coordinates of the glyph, ordered bottom left, top left, top right, bottom right
+pub fn glyph_tex_coords(c: char) -> [[f32; 2]; 4] {
+    let c = if has_glyph(c) { c } else { FALLBACK_CHAR };
+    let index = c as u32 - FIRST_CHAR;
+    let left = (index % ATLAS_COLUMNS) as f32 * CELL_SIZE / ATLAS_WIDTH;
+    let top = (index / ATLAS_COLUMNS) as f32 * CELL_SIZE / ATLAS_HEIGHT;
+    let right = left + GLYPH_WIDTH / ATLAS_WIDTH;
+    let bottom = top + GLYPH_HEIGHT / ATLAS_HEIGHT;
+    [[left, bottom], [left, top], [right, top], [right, bottom]]
+}
+
+// Size in unscaled pixels of the text, which can have multiple lines
+pub fn text_size(text: &str) -> Vec2 {
+    let lines = text.split('\n');
+    let (count, longest) = lines.fold((0, 0), |(count, longest), line| {
+        (count + 1, usize::max(longest, line.chars().count()))
+    });
+    vec2(
+        f32::max(longest as f32 * ADVANCE - (ADVANCE - GLYPH_WIDTH), 0.0),
+        count as f32 * LINE_HEIGHT - (LINE_HEIGHT - GLYPH_HEIGHT),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_are_laid_out_in_rows() {
+        let space = glyph_tex_coords(' ');
+        assert_eq!(space[1], [0.0, 0.0]);
+
+        // 'A' is the 34th glyph, in the third row
+        let a = glyph_tex_coords('A');
+        assert_eq!(
+            a[1],
+            [CELL_SIZE / ATLAS_WIDTH, 2.0 * CELL_SIZE / ATLAS_HEIGHT]
+        );
+    }
+
+    #[test]
+    fn unknown_chars_fall_back() {
+        assert_eq!(glyph_tex_coords('é'), glyph_tex_coords(FALLBACK_CHAR));
+    }
+
+    #[test]
+    fn measures_multiline_text() {
+        assert_eq!(text_size("ab\nc"), vec2(11.0, 18.0));
+    }
+}
@@ -0,0 +1,306 @@
+use glam::{vec2, Vec2};
+
+use crate::input::Binding;
+
+// Trigger travel, from 0 to 1, at which a trigger counts as pressed
+const TRIGGER_PRESS_THRESHOLD: f32 = 0.5;
+
+// Digital buttons, named after their position so that every controller layout maps the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    // Analog triggers, pressed once past TRIGGER_PRESS_THRESHOLD
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 16] = [
+        GamepadButton::South,
+        GamepadButton::East,
+        GamepadButton::North,
+        GamepadButton::West,
+        GamepadButton::LeftBumper,
+        GamepadButton::RightBumper,
+        GamepadButton::LeftTrigger,
+        GamepadButton::RightTrigger,
+        GamepadButton::Select,
+        GamepadButton::Start,
+        GamepadButton::LeftStick,
+        GamepadButton::RightStick,
+        GamepadButton::DPadUp,
+        GamepadButton::DPadDown,
+        GamepadButton::DPadLeft,
+        GamepadButton::DPadRight,
+    ];
+    pub fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+    pub fn from_name(name: &str) -> Option<GamepadButton> {
+        GamepadButton::ALL
+            .into_iter()
+            .find(|button| button.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    // Sticks go from -1 to 1, right and up being positive
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    // Triggers go from 0 to 1
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    ButtonPressed(GamepadButton),
+    ButtonReleased(GamepadButton),
+    AxisChanged(GamepadAxis, f32),
+    Disconnected,
+}
+
+// Analog state of the controllers, merged together
+#[derive(Debug, Default)]
+pub struct GamepadState {
+    left_stick: Vec2,
+    right_stick: Vec2,
+    left_trigger: f32,
+    right_trigger: f32,
+}
+
+impl GamepadState {
+    // Updates the analog state and returns the button presses (true) and releases it causes
+    pub fn handle_event(&mut self, event: GamepadEvent) -> Vec<(Binding, bool)> {
+        match event {
+            GamepadEvent::ButtonPressed(button) => vec![(Binding::Gamepad(button), true)],
+            GamepadEvent::ButtonReleased(button) => vec![(Binding::Gamepad(button), false)],
+            GamepadEvent::AxisChanged(axis, value) => {
+                let (trigger, button) = match axis {
+                    GamepadAxis::LeftStickX => {
+                        self.left_stick.x = value;
+                        return vec![];
+                    }
+                    GamepadAxis::LeftStickY => {
+                        self.left_stick.y = value;
+                        return vec![];
+                    }
+                    GamepadAxis::RightStickX => {
+                        self.right_stick.x = value;
+                        return vec![];
+                    }
+                    GamepadAxis::RightStickY => {
+                        self.right_stick.y = value;
+                        return vec![];
+                    }
+                    GamepadAxis::LeftTrigger => {
+                        (&mut self.left_trigger, GamepadButton::LeftTrigger)
+                    }
+                    GamepadAxis::RightTrigger => {
+                        (&mut self.right_trigger, GamepadButton::RightTrigger)
+                    }
+                };
+                let was_pressed = *trigger >= TRIGGER_PRESS_THRESHOLD;
+                *trigger = value;
+                let is_pressed = value >= TRIGGER_PRESS_THRESHOLD;
+                if was_pressed == is_pressed {
+                    return vec![];
+                }
+                vec![(Binding::Gamepad(button), is_pressed)]
+            }
+            // Releases everything, the controller won't send the releases anymore
+            GamepadEvent::Disconnected => {
+                *self = GamepadState::default();
+                GamepadButton::ALL
+                    .into_iter()
+                    .map(|button| (Binding::Gamepad(button), false))
+                    .collect()
+            }
+        }
+    }
+    // Left stick, x to the right and y forward
+    pub fn movement(&self, dead_zone: f32) -> Vec2 {
+        apply_dead_zone(self.left_stick, dead_zone)
+    }
+    // Right stick, x to the right and y downwards like the mouse
+    pub fn look(&self, dead_zone: f32) -> Vec2 {
+        let look = apply_dead_zone(self.right_stick, dead_zone);
+        vec2(look.x, -look.y)
+    }
+}
+
+// Ignores the stick near its center, rescaling the rest so that the output still starts from 0
+fn apply_dead_zone(stick: Vec2, dead_zone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= dead_zone {
+        return Vec2::ZERO;
+    }
+    let scaled = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    stick / length * scaled
+}
+
+// Reads the connected controllers, only built with the "gamepad" feature as it needs libudev on linux
+#[cfg(feature = "gamepad")]
+pub struct Gamepads {
+    // None when the platform's gamepad API failed to initialize
+    gilrs: Option<gilrs::Gilrs>,
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(err) => {
+                eprintln!("Gamepads are unavailable: {err}");
+                None
+            }
+        };
+        Self { gilrs }
+    }
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        use gilrs::{Axis, Button, EventType};
+
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return vec![];
+        };
+        let button = |button| {
+            let button = match button {
+                Button::South => GamepadButton::South,
+                Button::East => GamepadButton::East,
+                Button::North => GamepadButton::North,
+                Button::West => GamepadButton::West,
+                Button::LeftTrigger => GamepadButton::LeftBumper,
+                Button::RightTrigger => GamepadButton::RightBumper,
+                Button::Select => GamepadButton::Select,
+                Button::Start => GamepadButton::Start,
+                Button::LeftThumb => GamepadButton::LeftStick,
+                Button::RightThumb => GamepadButton::RightStick,
+                Button::DPadUp => GamepadButton::DPadUp,
+                Button::DPadDown => GamepadButton::DPadDown,
+                Button::DPadLeft => GamepadButton::DPadLeft,
+                Button::DPadRight => GamepadButton::DPadRight,
+                // The analog triggers are read from their value instead
+                _ => return None,
+            };
+            Some(button)
+        };
+        let mut events = vec![];
+        while let Some(gilrs::Event { event, .. }) = gilrs.next_event() {
+            let event = match event {
+                EventType::ButtonPressed(pressed, _) => {
+                    button(pressed).map(GamepadEvent::ButtonPressed)
+                }
+                EventType::ButtonReleased(released, _) => {
+                    button(released).map(GamepadEvent::ButtonReleased)
+                }
+                EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                    Some(GamepadEvent::AxisChanged(GamepadAxis::LeftTrigger, value))
+                }
+                EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                    Some(GamepadEvent::AxisChanged(GamepadAxis::RightTrigger, value))
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    let axis = match axis {
+                        Axis::LeftStickX => Some(GamepadAxis::LeftStickX),
+                        Axis::LeftStickY => Some(GamepadAxis::LeftStickY),
+                        Axis::RightStickX => Some(GamepadAxis::RightStickX),
+                        Axis::RightStickY => Some(GamepadAxis::RightStickY),
+                        _ => None,
+                    };
+                    axis.map(|axis| GamepadEvent::AxisChanged(axis, value))
+                }
+                EventType::Disconnected => Some(GamepadEvent::Disconnected),
+                _ => None,
+            };
+            events.extend(event);
+        }
+        events
+    }
+}
+
+// Without the "gamepad" feature there are never any controllers
+#[cfg(not(feature = "gamepad"))]
+pub struct Gamepads;
+
+#[cfg(not(feature = "gamepad"))]
+impl Gamepads {
+    pub fn new() -> Self {
+        Self
+    }
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        vec![]
+    }
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Action, Bindings};
+
+    #[test]
+    fn sticks_have_a_dead_zone() {
+        let mut gamepad = GamepadState::default();
+        gamepad.handle_event(GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, 0.1));
+        assert_eq!(gamepad.movement(0.2), Vec2::ZERO);
+
+        gamepad.handle_event(GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, 0.6));
+        assert!((gamepad.movement(0.2).y - 0.5).abs() < 1e-6);
+        gamepad.handle_event(GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, 1.0));
+        assert_eq!(gamepad.movement(0.2), vec2(0.0, 1.0));
+
+        gamepad.handle_event(GamepadEvent::AxisChanged(GamepadAxis::RightStickY, 1.0));
+        assert_eq!(gamepad.look(0.2), vec2(0.0, -1.0));
+    }
+
+    #[test]
+    fn triggers_press_the_bound_actions() {
+        let bindings = Bindings::default();
+        let mut gamepad = GamepadState::default();
+        let mut actions = |event| {
+            gamepad
+                .handle_event(event)
+                .into_iter()
+                .flat_map(|(binding, pressed)| {
+                    bindings
+                        .actions(binding)
+                        .into_iter()
+                        .map(move |action| (action, pressed))
+                })
+                .collect::<Vec<_>>()
+        };
+        let right_trigger = |value| GamepadEvent::AxisChanged(GamepadAxis::RightTrigger, value);
+
+        assert_eq!(actions(right_trigger(0.3)), vec![]);
+        assert_eq!(actions(right_trigger(0.8)), vec![(Action::Break, true)]);
+        assert_eq!(actions(right_trigger(0.9)), vec![]);
+        assert_eq!(actions(right_trigger(0.0)), vec![(Action::Break, false)]);
+        assert_eq!(
+            actions(GamepadEvent::ButtonPressed(GamepadButton::RightBumper)),
+            vec![(Action::NextSlot, true)]
+        );
+        assert!(actions(GamepadEvent::Disconnected).contains(&(Action::Jump, false)));
+    }
+}
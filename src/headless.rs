@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::persistence;
+use crate::player::DEFAULT_SPAWN_POINT;
+use crate::settings::RENDER_DISTANCE_RANGE;
+use crate::utils::ChunkFromPosition;
+use crate::world::World;
+
+// Simulation steps per second when running in real time
+pub const TICKS_PER_SECOND: u32 = 20;
+// Modified chunks are saved every this many ticks
+const AUTOSAVE_TICKS: u64 = 20 * 60;
+const DEFAULT_RENDER_DISTANCE: u32 = 4;
+
+pub const USAGE: &str =
+    "Usage: rustycraft --headless --world <name> [--ticks <count>] [--render-distance <chunks>]";
+
+// Command line options of the headless mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessOptions {
+    pub world: String,
+    // Runs this many ticks as fast as possible then exits, runs in real time forever when None
+    pub ticks: Option<u64>,
+    pub render_distance: u32,
+}
+
+impl HeadlessOptions {
+    // Parses the arguments following the program name, "--headless" included
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut world = None;
+        let mut ticks = None;
+        let mut render_distance = DEFAULT_RENDER_DISTANCE;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--headless" => {}
+                "--world" => world = Some(value()?.clone()),
+                "--ticks" => ticks = Some(value()?.parse()?),
+                "--render-distance" => {
+                    let (min, max) = RENDER_DISTANCE_RANGE;
+                    render_distance = value()?.parse::<u32>()?.clamp(min, max);
+                }
+                arg => return Err(format!("Unknown argument {arg}").into()),
+            }
+        }
+        let world = world.ok_or("Missing --world")?;
+        if !persistence::is_valid_world_name(&world) {
+            return Err(format!("Invalid world name {world}").into());
+        }
+        Ok(Self {
+            world,
+            ticks,
+            render_distance,
+        })
+    }
+}
+
+// Generates or loads the world around the spawn point and simulates it without a window or GPU
+pub fn run(options: HeadlessOptions) -> Result<(), Box<dyn Error>> {
+    persistence::set_world(&options.world);
+    persistence::create_world_dir()?;
+
+    let mut world = World::headless();
+    world.chunks_per_row = options.render_distance * 2;
+    let start = Instant::now();
+    world.load_chunks_around(DEFAULT_SPAWN_POINT.get_chunk_from_position_absolute());
+    println!(
+        "Loaded {} chunks of world \"{}\" in {:.2?}",
+        world.chunks.read().unwrap().len(),
+        options.world,
+        start.elapsed()
+    );
+
+    let tick_duration = Duration::from_secs(1) / TICKS_PER_SECOND;
+    let mut tick = 0;
+    while options.ticks.is_none_or(|ticks| tick < ticks) {
+        let tick_start = Instant::now();
+        world.tick(tick_duration.as_secs_f32(), &[]);
+        tick += 1;
+        if tick % AUTOSAVE_TICKS == 0 {
+            world.save_state();
+        }
+        if options.ticks.is_none() {
+            if let Some(remaining) = tick_duration.checked_sub(tick_start.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+    }
+
+    world.save_state();
+    world.dispose();
+    println!("Ran {tick} ticks, the world is saved");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let options = HeadlessOptions::parse(&args(
+            "--headless --world test --ticks 10 --render-distance 99",
+        ))
+        .unwrap();
+        assert_eq!(options.world, "test");
+        assert_eq!(options.ticks, Some(10));
+        assert_eq!(options.render_distance, RENDER_DISTANCE_RANGE.1);
+
+        assert!(HeadlessOptions::parse(&args("--headless")).is_err());
+        assert!(HeadlessOptions::parse(&args("--headless --world ../data")).is_err());
+        assert!(HeadlessOptions::parse(&args("--headless --world test --ticks")).is_err());
+    }
+}
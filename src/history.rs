@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+
+use crate::blocks::block_type::BlockType;
+
+pub const MAX_HISTORY_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockEdit {
+    // Absolute position of the block
+    pub position: glam::Vec3,
+    // None means air
+    pub previous: Option<BlockType>,
+    pub new: Option<BlockType>,
+}
+
+// Every edit made by a single action, undone and redone together
+pub type EditGroup = Vec<BlockEdit>;
+
+// Journal of block edits that supports undo and redo. Edits recorded between `begin_group` and `end_group`
+// form a single entry, otherwise every edit is its own entry.
+pub struct EditHistory {
+    undo_stack: VecDeque<EditGroup>,
+    redo_stack: Vec<EditGroup>,
+    open_group: EditGroup,
+    // Groups can be nested, the edits are committed once the outermost one is closed
+    group_depth: u32,
+    max_length: usize,
+}
+
+impl EditHistory {
+    pub fn new(max_length: usize) -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: vec![],
+            open_group: vec![],
+            group_depth: 0,
+            max_length,
+        }
+    }
+    pub fn begin_group(&mut self) {
+        self.group_depth += 1;
+    }
+    pub fn end_group(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth == 0 {
+            let group = std::mem::take(&mut self.open_group);
+            self.commit(group);
+        }
+    }
+    pub fn record(&mut self, edit: BlockEdit) {
+        if edit.previous == edit.new {
+            return;
+        }
+        if self.group_depth > 0 {
+            self.open_group.push(edit);
+        } else {
+            self.commit(vec![edit]);
+        }
+    }
+    fn commit(&mut self, group: EditGroup) {
+        if group.is_empty() {
+            return;
+        }
+        // A new action invalidates whatever was undone before it
+        self.redo_stack.clear();
+        self.undo_stack.push_back(group);
+        while self.undo_stack.len() > self.max_length {
+            self.undo_stack.pop_front();
+        }
+    }
+    // Group that undo would return, left on its stack
+    pub fn next_undo(&self) -> Option<&EditGroup> {
+        self.undo_stack.back()
+    }
+    pub fn next_redo(&self) -> Option<&EditGroup> {
+        self.redo_stack.last()
+    }
+    // Returns the group to revert, it's moved to the redo stack
+    pub fn undo(&mut self) -> Option<EditGroup> {
+        let group = self.undo_stack.pop_back()?;
+        self.redo_stack.push(group.clone());
+        Some(group)
+    }
+    // Returns the group to re-apply, it's moved back to the undo stack
+    pub fn redo(&mut self) -> Option<EditGroup> {
+        let group = self.redo_stack.pop()?;
+        self.undo_stack.push_back(group.clone());
+        Some(group)
+    }
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+    pub fn redo_len(&self) -> usize {
+        self.redo_stack.len()
+    }
+}
+
+impl Default for EditHistory {
+    fn default() -> Self {
+        Self::new(MAX_HISTORY_LENGTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockEdit, EditHistory};
+    use crate::blocks::block_type::BlockType;
+
+    fn edit(x: f32, previous: Option<BlockType>, new: Option<BlockType>) -> BlockEdit {
+        BlockEdit {
+            position: glam::vec3(x, 0.0, 0.0),
+            previous,
+            new,
+        }
+    }
+
+    #[test]
+    fn should_undo_and_redo_groups() {
+        let mut history = EditHistory::new(10);
+        history.record(edit(0.0, None, Some(BlockType::Dirt)));
+        history.begin_group();
+        history.record(edit(1.0, None, Some(BlockType::Stone)));
+        history.record(edit(2.0, None, Some(BlockType::Stone)));
+        history.end_group();
+
+        assert_eq!(history.undo_len(), 2);
+        assert_eq!(history.next_undo().unwrap().len(), 2);
+        assert_eq!(history.undo().unwrap().len(), 2);
+        assert_eq!(history.next_undo().unwrap().len(), 1);
+        assert_eq!(history.redo_len(), 1);
+        assert_eq!(history.redo().unwrap().len(), 2);
+        assert_eq!(history.undo_len(), 2);
+    }
+
+    #[test]
+    fn should_clear_redo_on_new_edits() {
+        let mut history = EditHistory::new(10);
+        history.record(edit(0.0, None, Some(BlockType::Dirt)));
+        history.undo();
+        history.record(edit(1.0, Some(BlockType::Sand), None));
+        assert_eq!(history.redo_len(), 0);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn should_drop_the_oldest_entries() {
+        let mut history = EditHistory::new(3);
+        for i in 0..5 {
+            history.record(edit(i as f32, None, Some(BlockType::Dirt)));
+        }
+        assert_eq!(history.undo_len(), 3);
+        assert_eq!(history.undo().unwrap()[0].position.x, 4.0);
+    }
+}
@@ -0,0 +1,621 @@
+use bytemuck::{Pod, Zeroable};
+use glam::{vec2, Mat4, Vec2, Vec3};
+
+use crate::blocks::block::{FaceDirections, TexturedBlock};
+use crate::console::{Console, MESSAGE_VISIBLE_TIME};
+use crate::font;
+use crate::inventory::{HOTBAR_SIZE, MAX_STACK_SIZE};
+use crate::menu::{Button, Menu};
+use crate::player::{GameMode, Player};
+use crate::settings::Settings;
+use crate::vitals::{MAX_BREATH, MAX_HEALTH};
+
+// The HUD is laid out for this screen height and scaled by whole steps on bigger screens
+const REFERENCE_HEIGHT: f32 = 360.0;
+
+const SLOT_SIZE: f32 = 20.0;
+const SLOT_BORDER: f32 = 1.0;
+const ICON_PADDING: f32 = 3.0;
+const HOTBAR_MARGIN: f32 = 4.0;
+const CROSSHAIR_SIZE: f32 = 9.0;
+const CROSSHAIR_THICKNESS: f32 = 1.0;
+const BAR_ICON_SIZE: f32 = 8.0;
+const BAR_ICON_SPACING: f32 = 1.0;
+// Health and breath are drawn as icons worth this many points each
+const POINTS_PER_ICON: f32 = 2.0;
+
+const SLOT_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.45];
+const SELECTED_SLOT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const CROSSHAIR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+const HEALTH_COLOR: [f32; 4] = [0.85, 0.1, 0.1, 1.0];
+const BREATH_COLOR: [f32; 4] = [0.3, 0.6, 1.0, 1.0];
+const EMPTY_ICON_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 0.6];
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const SHADOW_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 1.0];
+const DEBUG_BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const DEBUG_MARGIN: f32 = 2.0;
+const DEBUG_PADDING: f32 = 1.0;
+const CONSOLE_MARGIN: f32 = 4.0;
+const CONSOLE_WIDTH: f32 = 220.0;
+const CONSOLE_PADDING: f32 = 1.0;
+const CONSOLE_MAX_LINES: usize = 10;
+// Seconds over which messages fade out once their visible time is over
+const CONSOLE_FADE_TIME: f32 = 1.0;
+const CONSOLE_BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const BUTTON_HEIGHT: f32 = 16.0;
+const BUTTON_SPACING: f32 = 4.0;
+// Space between the title and the first row of buttons
+const MENU_TITLE_MARGIN: f32 = 24.0;
+const MENU_BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.55];
+const BUTTON_COLOR: [f32; 4] = [0.25, 0.25, 0.25, 0.9];
+const SELECTED_BUTTON_COLOR: [f32; 4] = [0.4, 0.4, 0.55, 0.9];
+const MESSAGE_COLOR: [f32; 4] = [1.0, 0.4, 0.4, 1.0];
+const NAMEPLATE_PADDING: f32 = 1.0;
+const NAMEPLATE_BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.35];
+
+// How the fragment shader colors a vertex
+pub const MODE_SOLID: u32 = 0;
+pub const MODE_BLOCK_ATLAS: u32 = 1;
+pub const MODE_FONT: u32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomCenter,
+    BottomRight,
+}
+
+// Rectangle in pixels, with the origin at the top left of the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub position: Vec2,
+    pub size: Vec2,
+}
+
+impl Rect {
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Self { position, size }
+    }
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.position).all() && point.cmplt(self.position + self.size).all()
+    }
+    // Shrinks the rectangle by amount on every side
+    pub fn inset(&self, amount: f32) -> Rect {
+        Rect::new(
+            self.position + Vec2::splat(amount),
+            (self.size - Vec2::splat(amount * 2.0)).max(Vec2::ZERO),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HudLayout {
+    pub width: f32,
+    pub height: f32,
+    pub scale: f32,
+}
+
+impl HudLayout {
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            scale: f32::max((height / REFERENCE_HEIGHT).floor(), 1.0),
+        }
+    }
+    // Places a widget of the given unscaled size, offset is measured from the anchor towards the screen's center
+    pub fn place(&self, anchor: Anchor, offset: Vec2, size: Vec2) -> Rect {
+        let size = size * self.scale;
+        let offset = offset * self.scale;
+        let position = match anchor {
+            Anchor::TopLeft => offset,
+            Anchor::TopRight => vec2(self.width - size.x - offset.x, offset.y),
+            Anchor::Center => vec2(self.width - size.x, self.height - size.y) / 2.0 + offset,
+            Anchor::BottomLeft => vec2(offset.x, self.height - size.y - offset.y),
+            Anchor::BottomCenter => vec2(
+                (self.width - size.x) / 2.0 + offset.x,
+                self.height - size.y - offset.y,
+            ),
+            Anchor::BottomRight => vec2(
+                self.width - size.x - offset.x,
+                self.height - size.y - offset.y,
+            ),
+        };
+        Rect::new(position, size)
+    }
+    // Screen position of a point in the world, None when it's behind the camera or off screen
+    pub fn project(&self, view_projection: Mat4, point: Vec3) -> Option<Vec2> {
+        let clip = view_projection * point.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 {
+            return None;
+        }
+        Some(vec2(
+            (ndc.x + 1.0) / 2.0 * self.width,
+            (1.0 - ndc.y) / 2.0 * self.height,
+        ))
+    }
+    fn clip_space_position(&self, point: Vec2) -> [f32; 2] {
+        [
+            point.x / self.width * 2.0 - 1.0,
+            1.0 - point.y / self.height * 2.0,
+        ]
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct HudVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+    pub mode: u32,
+}
+
+// Every HUD quad of a frame, drawn with a single draw call
+pub struct HudBatch {
+    pub layout: HudLayout,
+    pub vertices: Vec<HudVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl HudBatch {
+    pub fn new(layout: HudLayout) -> Self {
+        Self {
+            layout,
+            vertices: vec![],
+            indices: vec![],
+        }
+    }
+    pub fn quad(&mut self, rect: Rect, color: [f32; 4]) {
+        self.push_quad(rect, [[0.0, 0.0]; 4], color, MODE_SOLID);
+    }
+    // tex_coords are ordered bottom left, top left, top right, bottom right, like `TexturedBlock::get_texcoords`
+    pub fn textured_quad(&mut self, rect: Rect, tex_coords: [[f32; 2]; 4], mode: u32) {
+        self.push_quad(rect, tex_coords, WHITE, mode);
+    }
+    fn push_quad(&mut self, rect: Rect, tex_coords: [[f32; 2]; 4], color: [f32; 4], mode: u32) {
+        let Rect { position, size } = rect;
+        let corners = [
+            position + vec2(0.0, size.y),
+            position,
+            position + vec2(size.x, 0.0),
+            position + size,
+        ];
+        let base = self.vertices.len() as u32;
+        for (corner, uv) in corners.iter().zip(tex_coords) {
+            self.vertices.push(HudVertex {
+                position: self.layout.clip_space_position(*corner),
+                uv,
+                color,
+                mode,
+            });
+        }
+        self.indices
+            .extend([0, 1, 2, 0, 2, 3].map(|index| base + index));
+    }
+    // Outline drawn inside the rectangle
+    pub fn border(&mut self, rect: Rect, thickness: f32, color: [f32; 4]) {
+        let Rect { position, size } = rect;
+        self.quad(Rect::new(position, vec2(size.x, thickness)), color);
+        self.quad(
+            Rect::new(
+                position + vec2(0.0, size.y - thickness),
+                vec2(size.x, thickness),
+            ),
+            color,
+        );
+        self.quad(Rect::new(position, vec2(thickness, size.y)), color);
+        self.quad(
+            Rect::new(
+                position + vec2(size.x - thickness, 0.0),
+                vec2(thickness, size.y),
+            ),
+            color,
+        );
+    }
+    // Size on screen of the text
+    pub fn text_size(&self, text: &str) -> Vec2 {
+        font::text_size(text) * self.layout.scale
+    }
+    // Draws the text with its top left corner at the position, the shadow is offset by one pixel
+    pub fn text(&mut self, text: &str, position: Vec2, color: [f32; 4], shadow: bool) {
+        if shadow {
+            let shadow_color = [SHADOW_COLOR[0], SHADOW_COLOR[1], SHADOW_COLOR[2], color[3]];
+            self.glyphs(
+                text,
+                position + Vec2::splat(self.layout.scale),
+                shadow_color,
+            );
+        }
+        self.glyphs(text, position, color);
+    }
+    fn glyphs(&mut self, text: &str, position: Vec2, color: [f32; 4]) {
+        let scale = self.layout.scale;
+        let glyph_size = vec2(font::GLYPH_WIDTH, font::GLYPH_HEIGHT) * scale;
+        for (row, line) in text.split('\n').enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                if c == ' ' {
+                    continue;
+                }
+                let offset = vec2(
+                    column as f32 * font::ADVANCE,
+                    row as f32 * font::LINE_HEIGHT,
+                );
+                let rect = Rect::new(position + offset * scale, glyph_size);
+                self.push_quad(rect, font::glyph_tex_coords(c), color, MODE_FONT);
+            }
+        }
+    }
+}
+
+pub fn build_hud(layout: HudLayout, player: &Player) -> HudBatch {
+    let mut batch = HudBatch::new(layout);
+    add_crosshair(&mut batch);
+    let hotbar = add_hotbar(&mut batch, player);
+    if player.game_mode == GameMode::Survival {
+        add_vitals(&mut batch, player, hotbar);
+    }
+    batch
+}
+
+// Lines of text in the top left corner, each one over its own translucent background
+pub fn add_debug_overlay(batch: &mut HudBatch, lines: &[String]) {
+    let scale = batch.layout.scale;
+    let padding = DEBUG_PADDING * scale;
+    let mut position = Vec2::splat(DEBUG_MARGIN * scale);
+    for line in lines {
+        if !line.is_empty() {
+            let size = batch.text_size(line);
+            batch.quad(
+                Rect::new(position, size + Vec2::splat(padding * 2.0)),
+                DEBUG_BACKGROUND_COLOR,
+            );
+            batch.text(line, position + padding, WHITE, false);
+        }
+        position.y += (font::GLYPH_HEIGHT + DEBUG_PADDING * 2.0) * scale;
+    }
+}
+
+// Names centered above their points in the world, the farthest ones are drawn first so that the nearest end up on top
+pub fn add_nameplates(batch: &mut HudBatch, view_projection: Mat4, nameplates: &[(Vec3, &str)]) {
+    let mut placed = nameplates
+        .iter()
+        .filter_map(|(point, name)| {
+            let depth = (view_projection * point.extend(1.0)).w;
+            let anchor = batch.layout.project(view_projection, *point)?;
+            Some((depth, anchor, *name))
+        })
+        .collect::<Vec<_>>();
+    placed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let padding = NAMEPLATE_PADDING * batch.layout.scale;
+    for (_, anchor, name) in placed {
+        let size = batch.text_size(name);
+        let position = anchor - vec2(size.x / 2.0, size.y);
+        batch.quad(
+            Rect::new(position - padding, size + Vec2::splat(padding * 2.0)),
+            NAMEPLATE_BACKGROUND_COLOR,
+        );
+        batch.text(name, position, WHITE, false);
+    }
+}
+
+// Recent messages in the bottom left corner, with the input line below them while the console is open
+pub fn add_console(batch: &mut HudBatch, console: &Console) {
+    let layout = batch.layout;
+    let scale = layout.scale;
+    let line_height = (font::GLYPH_HEIGHT + CONSOLE_PADDING * 2.0) * scale;
+    let line_size = vec2(CONSOLE_WIDTH, font::GLYPH_HEIGHT + CONSOLE_PADDING * 2.0);
+    let mut line = layout.place(
+        Anchor::BottomLeft,
+        vec2(CONSOLE_MARGIN, CONSOLE_MARGIN),
+        line_size,
+    );
+    let padding = Vec2::splat(CONSOLE_PADDING * scale);
+
+    if console.open {
+        batch.quad(line, CONSOLE_BACKGROUND_COLOR);
+        batch.text(
+            &format!("> {}_", console.input),
+            line.position + padding,
+            WHITE,
+            true,
+        );
+    }
+    for message in console.messages.iter().rev().take(CONSOLE_MAX_LINES) {
+        let alpha = if console.open {
+            1.0
+        } else {
+            ((MESSAGE_VISIBLE_TIME + CONSOLE_FADE_TIME - message.age) / CONSOLE_FADE_TIME)
+                .clamp(0.0, 1.0)
+        };
+        if alpha <= 0.0 {
+            break;
+        }
+        line.position.y -= line_height;
+        let mut background = CONSOLE_BACKGROUND_COLOR;
+        background[3] *= alpha;
+        batch.quad(line, background);
+        batch.text(
+            &message.text,
+            line.position + padding,
+            [1.0, 1.0, 1.0, alpha],
+            true,
+        );
+    }
+}
+
+// Screen rectangles of the menu buttons, centered on the screen row by row
+pub fn layout_menu(layout: HudLayout, rows: Vec<Vec<Button>>) -> Vec<(Rect, Button)> {
+    let total_height = rows.len() as f32 * (BUTTON_HEIGHT + BUTTON_SPACING) - BUTTON_SPACING;
+    let mut buttons = vec![];
+    for (i, row) in rows.into_iter().enumerate() {
+        let y = i as f32 * (BUTTON_HEIGHT + BUTTON_SPACING) + (BUTTON_HEIGHT - total_height) / 2.0;
+        let row_width = row
+            .iter()
+            .map(|button| button.width + BUTTON_SPACING)
+            .sum::<f32>()
+            - BUTTON_SPACING;
+        let mut x = -row_width / 2.0;
+        for button in row {
+            let offset = vec2(x + button.width / 2.0, y);
+            let rect = layout.place(Anchor::Center, offset, vec2(button.width, BUTTON_HEIGHT));
+            x += button.width + BUTTON_SPACING;
+            buttons.push((rect, button));
+        }
+    }
+    buttons
+}
+
+pub fn add_menu(batch: &mut HudBatch, menu: &Menu, settings: &Settings) {
+    let layout = batch.layout;
+    batch.quad(
+        Rect::new(Vec2::ZERO, vec2(layout.width, layout.height)),
+        MENU_BACKGROUND_COLOR,
+    );
+    let buttons = layout_menu(layout, menu.rows(settings));
+    let top = buttons
+        .first()
+        .map_or(layout.height / 2.0, |(rect, _)| rect.position.y);
+
+    let title = menu.title();
+    let title_size = batch.text_size(&title);
+    let title_y = top - MENU_TITLE_MARGIN * layout.scale;
+    batch.text(
+        &title,
+        vec2((layout.width - title_size.x) / 2.0, title_y),
+        WHITE,
+        true,
+    );
+    if let Some(message) = &menu.message {
+        let size = batch.text_size(message);
+        batch.text(
+            message,
+            vec2(
+                (layout.width - size.x) / 2.0,
+                title_y + (font::LINE_HEIGHT + BUTTON_SPACING) * layout.scale,
+            ),
+            MESSAGE_COLOR,
+            true,
+        );
+    }
+
+    for (i, (rect, button)) in buttons.iter().enumerate() {
+        if i == menu.selected {
+            batch.quad(*rect, SELECTED_BUTTON_COLOR);
+            batch.border(*rect, layout.scale, WHITE);
+        } else {
+            batch.quad(*rect, BUTTON_COLOR);
+        }
+        let size = batch.text_size(&button.label);
+        batch.text(
+            &button.label,
+            rect.position + (rect.size - size) / 2.0,
+            WHITE,
+            true,
+        );
+    }
+}
+
+fn add_crosshair(batch: &mut HudBatch) {
+    let layout = batch.layout;
+    let horizontal = layout.place(
+        Anchor::Center,
+        Vec2::ZERO,
+        vec2(CROSSHAIR_SIZE, CROSSHAIR_THICKNESS),
+    );
+    let vertical = layout.place(
+        Anchor::Center,
+        Vec2::ZERO,
+        vec2(CROSSHAIR_THICKNESS, CROSSHAIR_SIZE),
+    );
+    batch.quad(horizontal, CROSSHAIR_COLOR);
+    batch.quad(vertical, CROSSHAIR_COLOR);
+}
+
+// Returns the rectangle taken by the hotbar
+fn add_hotbar(batch: &mut HudBatch, player: &Player) -> Rect {
+    let layout = batch.layout;
+    let hotbar = layout.place(
+        Anchor::BottomCenter,
+        vec2(0.0, HOTBAR_MARGIN),
+        vec2(SLOT_SIZE * HOTBAR_SIZE as f32, SLOT_SIZE),
+    );
+    let slot_size = SLOT_SIZE * layout.scale;
+
+    for (i, slot) in player.inventory.hotbar().iter().enumerate() {
+        let rect = Rect::new(
+            hotbar.position + vec2(i as f32 * slot_size, 0.0),
+            Vec2::splat(slot_size),
+        );
+        batch.quad(rect.inset(SLOT_BORDER * layout.scale), SLOT_COLOR);
+        if i == player.inventory.selected_slot {
+            batch.border(rect, SLOT_BORDER * layout.scale, SELECTED_SLOT_COLOR);
+        }
+
+        let Some(stack) = slot else {
+            continue;
+        };
+        let icon = rect.inset(ICON_PADDING * layout.scale);
+        let tex_coords = stack.block_type.get_texcoords(FaceDirections::Front);
+        batch.textured_quad(icon, tex_coords, MODE_BLOCK_ATLAS);
+        if stack.count > 1 {
+            let count = stack.count.min(MAX_STACK_SIZE).to_string();
+            let size = batch.text_size(&count);
+            batch.text(
+                &count,
+                rect.position + rect.size - size - layout.scale,
+                WHITE,
+                true,
+            );
+        }
+    }
+    hotbar
+}
+
+// Health on the left and breath on the right, above the hotbar
+fn add_vitals(batch: &mut HudBatch, player: &Player, hotbar: Rect) {
+    let scale = batch.layout.scale;
+    let y = hotbar.position.y - (BAR_ICON_SIZE + BAR_ICON_SPACING * 2.0) * scale;
+
+    let health_icons = (MAX_HEALTH / POINTS_PER_ICON) as usize;
+    add_bar(
+        batch,
+        vec2(hotbar.position.x, y),
+        health_icons,
+        player.vitals.health / POINTS_PER_ICON,
+        HEALTH_COLOR,
+        false,
+    );
+
+    if player.head_in_water || player.vitals.breath < MAX_BREATH {
+        let breath_icons = (MAX_BREATH / POINTS_PER_ICON) as usize;
+        add_bar(
+            batch,
+            vec2(hotbar.position.x + hotbar.size.x, y),
+            breath_icons,
+            player.vitals.breath / POINTS_PER_ICON,
+            BREATH_COLOR,
+            true,
+        );
+    }
+}
+
+// Row of icons filled up to `filled`, which can be fractional. Right aligned bars start from the right.
+fn add_bar(
+    batch: &mut HudBatch,
+    start: Vec2,
+    icons: usize,
+    filled: f32,
+    color: [f32; 4],
+    right_aligned: bool,
+) {
+    let scale = batch.layout.scale;
+    let icon_size = BAR_ICON_SIZE * scale;
+    let step = (BAR_ICON_SIZE + BAR_ICON_SPACING) * scale;
+    for i in 0..icons {
+        let x = if right_aligned {
+            start.x - icon_size - i as f32 * step
+        } else {
+            start.x + i as f32 * step
+        };
+        let rect = Rect::new(vec2(x, start.y), Vec2::splat(icon_size));
+        batch.quad(rect, EMPTY_ICON_COLOR);
+
+        let fill = (filled - i as f32).clamp(0.0, 1.0);
+        if fill > 0.0 {
+            let mut inner = rect.inset(scale);
+            inner.size.x *= fill;
+            batch.quad(inner, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::vec3;
+
+    #[test]
+    fn scale_grows_in_whole_steps() {
+        assert_eq!(HudLayout::new(640.0, 300.0).scale, 1.0);
+        assert_eq!(HudLayout::new(1280.0, 720.0).scale, 2.0);
+        assert_eq!(HudLayout::new(1920.0, 1079.0).scale, 2.0);
+    }
+
+    #[test]
+    fn points_are_projected_to_the_screen() {
+        let layout = HudLayout::new(800.0, 400.0);
+        let view = Mat4::look_at_lh(Vec3::ZERO, Vec3::Z, Vec3::Y);
+        let view_projection = Mat4::perspective_lh(1.0, 2.0, 0.1, 100.0) * view;
+        assert_eq!(
+            layout.project(view_projection, vec3(0.0, 0.0, 10.0)),
+            Some(vec2(400.0, 200.0))
+        );
+        let above = layout
+            .project(view_projection, vec3(0.0, 1.0, 10.0))
+            .unwrap();
+        assert!(above.y < 200.0);
+        assert_eq!(layout.project(view_projection, vec3(0.0, 0.0, -10.0)), None);
+        assert_eq!(
+            layout.project(view_projection, vec3(100.0, 0.0, 10.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn anchors_place_widgets_inside_the_screen() {
+        let layout = HudLayout::new(800.0, 360.0);
+        let size = vec2(10.0, 20.0);
+        assert_eq!(
+            layout.place(Anchor::Center, Vec2::ZERO, size).position,
+            vec2(395.0, 170.0)
+        );
+        assert_eq!(
+            layout
+                .place(Anchor::BottomRight, vec2(5.0, 5.0), size)
+                .position,
+            vec2(785.0, 335.0)
+        );
+        assert_eq!(
+            layout
+                .place(Anchor::BottomCenter, Vec2::ZERO, size)
+                .position,
+            vec2(395.0, 340.0)
+        );
+    }
+
+    #[test]
+    fn quads_are_in_clip_space() {
+        let mut batch = HudBatch::new(HudLayout::new(200.0, 100.0));
+        batch.quad(Rect::new(Vec2::ZERO, vec2(200.0, 100.0)), WHITE);
+        let positions = batch
+            .vertices
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            vec![[-1.0, -1.0], [-1.0, 1.0], [1.0, 1.0], [1.0, -1.0]]
+        );
+        assert_eq!(batch.indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn text_has_a_quad_per_glyph() {
+        let mut batch = HudBatch::new(HudLayout::new(200.0, 100.0));
+        batch.text("a b", Vec2::ZERO, WHITE, false);
+        assert_eq!(batch.vertices.len(), 2 * 4);
+
+        batch.text("a", Vec2::ZERO, WHITE, true);
+        assert_eq!(batch.vertices.len(), 4 * 4);
+        assert!(batch.vertices.iter().all(|vertex| vertex.mode == MODE_FONT));
+    }
+}
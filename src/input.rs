@@ -0,0 +1,576 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use glam::{Vec2, Vec3};
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+use crate::gamepad::GamepadButton;
+use crate::inventory::HOTBAR_SIZE;
+
+// Things the player can do, each one bound to any number of keys, mouse buttons or scroll directions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    FlyUp,
+    FlyDown,
+    Jump,
+    Sprint,
+    Crouch,
+    Break,
+    Place,
+    NextSlot,
+    PreviousSlot,
+    // Hotbar slot index, from 0
+    HotbarSlot(usize),
+    ToggleFly,
+    ToggleGameMode,
+    ToggleDebug,
+    Undo,
+    Redo,
+    OpenChat,
+    OpenCommand,
+    SelectFirstCorner,
+    SelectSecondCorner,
+    RegionFill,
+    RegionClear,
+    RegionReplace,
+    RegionHollow,
+    RegionWalls,
+    Copy,
+    Paste,
+    Rotate,
+    Mirror,
+}
+
+impl Action {
+    // Every action, in the order shown on the controls screen
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::MoveForward,
+            Action::MoveBackward,
+            Action::MoveLeft,
+            Action::MoveRight,
+            Action::FlyUp,
+            Action::FlyDown,
+            Action::Jump,
+            Action::Sprint,
+            Action::Crouch,
+            Action::Break,
+            Action::Place,
+            Action::NextSlot,
+            Action::PreviousSlot,
+        ];
+        actions.extend((0..HOTBAR_SIZE).map(Action::HotbarSlot));
+        actions.extend([
+            Action::ToggleFly,
+            Action::ToggleGameMode,
+            Action::ToggleDebug,
+            Action::Undo,
+            Action::Redo,
+            Action::OpenChat,
+            Action::OpenCommand,
+            Action::SelectFirstCorner,
+            Action::SelectSecondCorner,
+            Action::RegionFill,
+            Action::RegionClear,
+            Action::RegionReplace,
+            Action::RegionHollow,
+            Action::RegionWalls,
+            Action::Copy,
+            Action::Paste,
+            Action::Rotate,
+            Action::Mirror,
+        ]);
+        actions
+    }
+    // Name in the settings file
+    pub fn key(&self) -> String {
+        match self {
+            Action::MoveForward => "move_forward".to_string(),
+            Action::MoveBackward => "move_backward".to_string(),
+            Action::MoveLeft => "move_left".to_string(),
+            Action::MoveRight => "move_right".to_string(),
+            Action::FlyUp => "fly_up".to_string(),
+            Action::FlyDown => "fly_down".to_string(),
+            Action::Jump => "jump".to_string(),
+            Action::Sprint => "sprint".to_string(),
+            Action::Crouch => "crouch".to_string(),
+            Action::Break => "break".to_string(),
+            Action::Place => "place".to_string(),
+            Action::NextSlot => "next_slot".to_string(),
+            Action::PreviousSlot => "previous_slot".to_string(),
+            Action::HotbarSlot(slot) => format!("hotbar_{}", slot + 1),
+            Action::ToggleFly => "toggle_fly".to_string(),
+            Action::ToggleGameMode => "toggle_game_mode".to_string(),
+            Action::ToggleDebug => "toggle_debug".to_string(),
+            Action::Undo => "undo".to_string(),
+            Action::Redo => "redo".to_string(),
+            Action::OpenChat => "open_chat".to_string(),
+            Action::OpenCommand => "open_command".to_string(),
+            Action::SelectFirstCorner => "select_first_corner".to_string(),
+            Action::SelectSecondCorner => "select_second_corner".to_string(),
+            Action::RegionFill => "region_fill".to_string(),
+            Action::RegionClear => "region_clear".to_string(),
+            Action::RegionReplace => "region_replace".to_string(),
+            Action::RegionHollow => "region_hollow".to_string(),
+            Action::RegionWalls => "region_walls".to_string(),
+            Action::Copy => "copy".to_string(),
+            Action::Paste => "paste".to_string(),
+            Action::Rotate => "rotate".to_string(),
+            Action::Mirror => "mirror".to_string(),
+        }
+    }
+    pub fn from_key(key: &str) -> Option<Action> {
+        Action::all().into_iter().find(|action| action.key() == key)
+    }
+    pub fn label(&self) -> String {
+        match self {
+            Action::HotbarSlot(slot) => format!("Hotbar slot {}", slot + 1),
+            action => {
+                let key = action.key().replace('_', " ");
+                key[..1].to_uppercase() + &key[1..]
+            }
+        }
+    }
+    // Held actions stay active while any of their bindings is down, the others fire once per press
+    pub fn is_held(&self) -> bool {
+        matches!(
+            self,
+            Action::MoveForward
+                | Action::MoveBackward
+                | Action::MoveLeft
+                | Action::MoveRight
+                | Action::FlyUp
+                | Action::FlyDown
+                | Action::Jump
+                | Action::Sprint
+                | Action::Crouch
+                | Action::Break
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    ScrollUp,
+    ScrollDown,
+    Gamepad(GamepadButton),
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        // Keys that can be bound, by the name used in the settings file
+        const KEY_NAMES: &[(&str, KeyCode)] = &[$((stringify!($key), KeyCode::$key)),*];
+    };
+}
+
+key_names!(
+    KeyA,
+    KeyB,
+    KeyC,
+    KeyD,
+    KeyE,
+    KeyF,
+    KeyG,
+    KeyH,
+    KeyI,
+    KeyJ,
+    KeyK,
+    KeyL,
+    KeyM,
+    KeyN,
+    KeyO,
+    KeyP,
+    KeyQ,
+    KeyR,
+    KeyS,
+    KeyT,
+    KeyU,
+    KeyV,
+    KeyW,
+    KeyX,
+    KeyY,
+    KeyZ,
+    Digit0,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Space,
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Insert,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    ShiftLeft,
+    ShiftRight,
+    ControlLeft,
+    ControlRight,
+    AltLeft,
+    AltRight,
+    CapsLock,
+    BracketLeft,
+    BracketRight,
+    Slash,
+    Backslash,
+    Minus,
+    Equal,
+    Comma,
+    Period,
+    Semicolon,
+    Quote,
+    Backquote,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadEnter,
+);
+
+impl Binding {
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => KEY_NAMES
+                .iter()
+                .find(|(_, code)| code == key)
+                .map_or_else(|| format!("{:?}", key), |(name, _)| name.to_string()),
+            Binding::Mouse(MouseButton::Left) => "MouseLeft".to_string(),
+            Binding::Mouse(MouseButton::Right) => "MouseRight".to_string(),
+            Binding::Mouse(MouseButton::Middle) => "MouseMiddle".to_string(),
+            Binding::Mouse(MouseButton::Back) => "MouseBack".to_string(),
+            Binding::Mouse(MouseButton::Forward) => "MouseForward".to_string(),
+            Binding::Mouse(MouseButton::Other(button)) => format!("Mouse{button}"),
+            Binding::ScrollUp => "ScrollUp".to_string(),
+            Binding::ScrollDown => "ScrollDown".to_string(),
+            Binding::Gamepad(button) => format!("Pad{}", button.name()),
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Binding> {
+        let binding = match name {
+            "MouseLeft" => Binding::Mouse(MouseButton::Left),
+            "MouseRight" => Binding::Mouse(MouseButton::Right),
+            "MouseMiddle" => Binding::Mouse(MouseButton::Middle),
+            "MouseBack" => Binding::Mouse(MouseButton::Back),
+            "MouseForward" => Binding::Mouse(MouseButton::Forward),
+            "ScrollUp" => Binding::ScrollUp,
+            "ScrollDown" => Binding::ScrollDown,
+            name => match name.strip_prefix("Mouse").map(str::parse::<u16>) {
+                Some(Ok(button)) => Binding::Mouse(MouseButton::Other(button)),
+                _ => match name.strip_prefix("Pad") {
+                    Some(button) => Binding::Gamepad(GamepadButton::from_name(button)?),
+                    None => {
+                        let (_, key) = KEY_NAMES.iter().find(|(key_name, _)| *key_name == name)?;
+                        Binding::Key(*key)
+                    }
+                },
+            },
+        };
+        Some(binding)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Binding::*;
+        use GamepadButton::*;
+        let mut bindings = HashMap::from([
+            (Action::MoveForward, vec![Key(KeyCode::KeyW)]),
+            (Action::MoveBackward, vec![Key(KeyCode::KeyS)]),
+            (Action::MoveLeft, vec![Key(KeyCode::KeyA)]),
+            (Action::MoveRight, vec![Key(KeyCode::KeyD)]),
+            (Action::FlyUp, vec![Key(KeyCode::KeyE), Gamepad(DPadUp)]),
+            (Action::FlyDown, vec![Key(KeyCode::KeyQ), Gamepad(DPadDown)]),
+            (Action::Jump, vec![Key(KeyCode::Space), Gamepad(South)]),
+            (
+                Action::Sprint,
+                vec![Key(KeyCode::ControlLeft), Gamepad(LeftStick)],
+            ),
+            (Action::Crouch, vec![Key(KeyCode::ShiftLeft), Gamepad(East)]),
+            (
+                Action::Break,
+                vec![Mouse(MouseButton::Left), Gamepad(RightTrigger)],
+            ),
+            (
+                Action::Place,
+                vec![Mouse(MouseButton::Right), Gamepad(LeftTrigger)],
+            ),
+            (
+                Action::NextSlot,
+                vec![Key(KeyCode::KeyJ), ScrollUp, Gamepad(RightBumper)],
+            ),
+            (
+                Action::PreviousSlot,
+                vec![Key(KeyCode::KeyK), ScrollDown, Gamepad(LeftBumper)],
+            ),
+            (Action::ToggleFly, vec![Key(KeyCode::KeyG), Gamepad(North)]),
+            (Action::ToggleGameMode, vec![Key(KeyCode::F4)]),
+            (Action::ToggleDebug, vec![Key(KeyCode::F3)]),
+            (Action::Undo, vec![Key(KeyCode::KeyZ), Gamepad(DPadLeft)]),
+            (Action::Redo, vec![Key(KeyCode::KeyY), Gamepad(DPadRight)]),
+            (Action::OpenChat, vec![Key(KeyCode::KeyT), Gamepad(Select)]),
+            (Action::OpenCommand, vec![Key(KeyCode::Slash)]),
+            (Action::SelectFirstCorner, vec![Key(KeyCode::BracketLeft)]),
+            (Action::SelectSecondCorner, vec![Key(KeyCode::BracketRight)]),
+            (Action::RegionFill, vec![Key(KeyCode::KeyF)]),
+            (Action::RegionClear, vec![Key(KeyCode::Delete)]),
+            (Action::RegionReplace, vec![Key(KeyCode::KeyN)]),
+            (Action::RegionHollow, vec![Key(KeyCode::KeyH)]),
+            (Action::RegionWalls, vec![Key(KeyCode::KeyB)]),
+            (Action::Copy, vec![Key(KeyCode::KeyC)]),
+            (Action::Paste, vec![Key(KeyCode::KeyV)]),
+            (Action::Rotate, vec![Key(KeyCode::KeyR)]),
+            (Action::Mirror, vec![Key(KeyCode::KeyM)]),
+        ]);
+        let digits = [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+            KeyCode::Digit5,
+            KeyCode::Digit6,
+            KeyCode::Digit7,
+            KeyCode::Digit8,
+            KeyCode::Digit9,
+        ];
+        for (slot, digit) in digits.into_iter().enumerate().take(HOTBAR_SIZE) {
+            bindings.insert(Action::HotbarSlot(slot), vec![Key(digit)]);
+        }
+        Self { bindings }
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], |bindings| bindings)
+    }
+    // Actions triggered by the binding
+    pub fn actions(&self, binding: Binding) -> Vec<Action> {
+        Action::all()
+            .into_iter()
+            .filter(|action| self.get(*action).contains(&binding))
+            .collect()
+    }
+    // Binds without checking for conflicts, used when loading the settings file
+    pub fn set(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+    // Adds a binding to the action, or replaces all of its bindings. Fails if another action already uses it.
+    pub fn bind(
+        &mut self,
+        action: Action,
+        binding: Binding,
+        replace: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(other) = self
+            .actions(binding)
+            .into_iter()
+            .find(|other| *other != action)
+        {
+            return Err(format!("{} is already bound to {}", binding.name(), other.label()).into());
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        if replace {
+            bindings.clear();
+        }
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        Ok(())
+    }
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.insert(action, vec![]);
+    }
+    // Comma separated names, e.g. "KeyJ, ScrollUp"
+    pub fn describe(&self, action: Action) -> String {
+        self.get(action)
+            .iter()
+            .map(Binding::name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+    // Parses a value of the settings file, unknown names are skipped
+    pub fn parse(value: &str) -> Vec<Binding> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .filter_map(|name| {
+                let binding = Binding::from_name(name);
+                if binding.is_none() {
+                    eprintln!("Unknown binding {name}");
+                }
+                binding
+            })
+            .collect()
+    }
+}
+
+// Bindings currently held down
+#[derive(Default)]
+pub struct InputState {
+    held: HashSet<Binding>,
+}
+
+impl InputState {
+    // Returns whether the binding wasn't already held, key repeats aren't new presses
+    pub fn press(&mut self, binding: Binding) -> bool {
+        self.held.insert(binding)
+    }
+    pub fn release(&mut self, binding: Binding) {
+        self.held.remove(&binding);
+    }
+    pub fn clear(&mut self) {
+        self.held.clear();
+    }
+    pub fn is_active(&self, action: Action, bindings: &Bindings) -> bool {
+        bindings
+            .get(action)
+            .iter()
+            .any(|binding| self.held.contains(binding))
+    }
+    // Direction the player moves in, from the held actions and the gamepad's left stick (x to the right, y forward).
+    // Clamped so that walking diagonally or mixing both isn't faster.
+    pub fn movement(&self, bindings: &Bindings, stick: Vec2) -> Vec3 {
+        let axis = |positive, negative| {
+            self.is_active(positive, bindings) as i32 as f32
+                - self.is_active(negative, bindings) as i32 as f32
+        };
+        let horizontal = (Vec2::new(
+            axis(Action::MoveRight, Action::MoveLeft),
+            axis(Action::MoveForward, Action::MoveBackward),
+        ) + stick)
+            .clamp_length_max(1.0);
+        Vec3::new(
+            horizontal.x,
+            axis(Action::FlyUp, Action::FlyDown),
+            horizontal.y,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_names_round_trip() {
+        for binding in [
+            Binding::Key(KeyCode::KeyW),
+            Binding::Key(KeyCode::BracketLeft),
+            Binding::Mouse(MouseButton::Middle),
+            Binding::Mouse(MouseButton::Other(7)),
+            Binding::ScrollDown,
+            Binding::Gamepad(GamepadButton::RightTrigger),
+        ] {
+            assert_eq!(Binding::from_name(&binding.name()), Some(binding));
+        }
+        assert_eq!(Binding::from_name("NotAKey"), None);
+        assert_eq!(
+            Bindings::parse("KeyJ, ScrollUp, Nope"),
+            vec![Binding::Key(KeyCode::KeyJ), Binding::ScrollUp]
+        );
+    }
+
+    #[test]
+    fn default_bindings_have_no_conflicts() {
+        let bindings = Bindings::default();
+        for action in Action::all() {
+            assert!(!bindings.get(action).is_empty(), "{:?} is unbound", action);
+            for binding in bindings.get(action) {
+                assert_eq!(bindings.actions(*binding), vec![action]);
+            }
+        }
+    }
+
+    #[test]
+    fn bind_rejects_conflicts() {
+        let mut bindings = Bindings::default();
+        assert!(bindings
+            .bind(Action::Jump, Binding::Key(KeyCode::KeyW), false)
+            .is_err());
+        bindings
+            .bind(Action::Jump, Binding::Mouse(MouseButton::Middle), false)
+            .unwrap();
+        assert_eq!(bindings.get(Action::Jump).len(), 3);
+        bindings
+            .bind(Action::Jump, Binding::Key(KeyCode::KeyX), true)
+            .unwrap();
+        assert_eq!(bindings.get(Action::Jump), [Binding::Key(KeyCode::KeyX)]);
+    }
+
+    #[test]
+    fn held_actions_stay_active_while_any_binding_is_down() {
+        let mut bindings = Bindings::default();
+        bindings
+            .bind(Action::MoveForward, Binding::Key(KeyCode::ArrowUp), false)
+            .unwrap();
+        let mut input = InputState::default();
+        assert!(input.press(Binding::Key(KeyCode::KeyW)));
+        assert!(!input.press(Binding::Key(KeyCode::KeyW)));
+        input.press(Binding::Key(KeyCode::ArrowUp));
+        input.release(Binding::Key(KeyCode::KeyW));
+        assert!(input.is_active(Action::MoveForward, &bindings));
+        input.release(Binding::Key(KeyCode::ArrowUp));
+        assert!(!input.is_active(Action::MoveForward, &bindings));
+    }
+
+    #[test]
+    fn stick_moves_the_player() {
+        let bindings = Bindings::default();
+        let mut input = InputState::default();
+        assert_eq!(input.movement(&bindings, Vec2::ZERO), Vec3::ZERO);
+        // Half tilted to the right walks at half speed
+        assert_eq!(
+            input.movement(&bindings, Vec2::new(0.5, 0.0)),
+            Vec3::new(0.5, 0.0, 0.0)
+        );
+        input.press(Binding::Key(KeyCode::KeyW));
+        let movement = input.movement(&bindings, Vec2::new(1.0, 0.0));
+        assert!((movement.length() - 1.0).abs() < 1e-6);
+        assert!(movement.x > 0.0 && movement.z > 0.0);
+    }
+}
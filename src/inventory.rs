@@ -0,0 +1,243 @@
+use std::any::Any;
+use std::error::Error;
+
+use crate::blocks::block_type::BlockType;
+use crate::persistence::{self, Loadable, Saveable};
+
+pub const HOTBAR_SIZE: usize = 9;
+// Hotbar slots come first
+pub const INVENTORY_SIZE: usize = 36;
+pub const MAX_STACK_SIZE: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ItemStack {
+    pub block_type: BlockType,
+    pub count: u32,
+}
+
+pub struct Inventory {
+    pub slots: [Option<ItemStack>; INVENTORY_SIZE],
+    // Index of the selected hotbar slot
+    pub selected_slot: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            slots: [None; INVENTORY_SIZE],
+            selected_slot: 0,
+        }
+    }
+}
+
+impl Inventory {
+    // What new players start with, a stack of every block they can build with
+    pub fn starter() -> Self {
+        let mut inventory = Inventory::default();
+        for block_type in (0..=BlockType::MAX_ID).map(BlockType::from_id) {
+            if block_type.is_solid() {
+                inventory.add(block_type, MAX_STACK_SIZE);
+            }
+        }
+        inventory
+    }
+    pub fn hotbar(&self) -> &[Option<ItemStack>] {
+        &self.slots[..HOTBAR_SIZE]
+    }
+    pub fn selected(&self) -> Option<ItemStack> {
+        self.slots[self.selected_slot]
+    }
+    pub fn selected_block_type(&self) -> Option<BlockType> {
+        self.selected().map(|stack| stack.block_type)
+    }
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SIZE {
+            self.selected_slot = slot;
+        }
+    }
+    // Moves the selection by offset slots, wrapping around the hotbar
+    pub fn scroll(&mut self, offset: i32) {
+        self.selected_slot =
+            (self.selected_slot as i32 + offset).rem_euclid(HOTBAR_SIZE as i32) as usize;
+    }
+    pub fn count(&self, block_type: BlockType) -> u32 {
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|stack| stack.block_type == block_type)
+            .map(|stack| stack.count)
+            .sum()
+    }
+    // Adds the items to the existing stacks first and then to the empty slots.
+    // Returns how many items didn't fit.
+    pub fn add(&mut self, block_type: BlockType, mut count: u32) -> u32 {
+        for stack in self.slots.iter_mut().flatten() {
+            if count == 0 {
+                break;
+            }
+            if stack.block_type == block_type && stack.count < MAX_STACK_SIZE {
+                let added = count.min(MAX_STACK_SIZE - stack.count);
+                stack.count += added;
+                count -= added;
+            }
+        }
+        for slot in self.slots.iter_mut().filter(|slot| slot.is_none()) {
+            if count == 0 {
+                break;
+            }
+            let added = count.min(MAX_STACK_SIZE);
+            *slot = Some(ItemStack {
+                block_type,
+                count: added,
+            });
+            count -= added;
+        }
+        count
+    }
+    // Removes one item of the block type from the first stack holding it, false if there's none
+    pub fn take(&mut self, block_type: BlockType) -> bool {
+        let Some(slot) = self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.is_some_and(|stack| stack.block_type == block_type))
+        else {
+            return false;
+        };
+        let stack = slot.as_mut().unwrap();
+        stack.count -= 1;
+        if stack.count == 0 {
+            *slot = None;
+        }
+        true
+    }
+    // Removes one item from the selected slot, returning its block type
+    pub fn take_selected(&mut self) -> Option<BlockType> {
+        let slot = &mut self.slots[self.selected_slot];
+        let stack = slot.as_mut()?;
+        let block_type = stack.block_type;
+        stack.count -= 1;
+        if stack.count == 0 {
+            *slot = None;
+        }
+        Some(block_type)
+    }
+    // Format: selected_slot;id:count,id:count,... with empty slots left blank
+    pub fn serialize(&self) -> String {
+        let slots = self
+            .slots
+            .iter()
+            .map(|slot| match slot {
+                Some(stack) => format!("{}:{}", stack.block_type.to_id(), stack.count),
+                None => String::new(),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("{};{}", self.selected_slot, slots)
+    }
+    pub fn deserialize(data: &str) -> Result<Inventory, Box<dyn Error>> {
+        let (selected_slot, slots) = data.trim().split_once(';').ok_or("Missing selected slot")?;
+        let mut inventory = Inventory::default();
+        inventory.select(selected_slot.parse()?);
+
+        for (slot, stack) in inventory.slots.iter_mut().zip(slots.split(',')) {
+            if stack.is_empty() {
+                continue;
+            }
+            let (id, count) = stack.split_once(':').ok_or("Invalid item stack")?;
+            let id = id.parse::<u32>()?;
+            if id > BlockType::MAX_ID {
+                return Err(format!("Invalid block id {id}").into());
+            }
+            let count = count.parse::<u32>()?.min(MAX_STACK_SIZE);
+            if count > 0 {
+                *slot = Some(ItemStack {
+                    block_type: BlockType::from_id(id),
+                    count,
+                });
+            }
+        }
+        Ok(inventory)
+    }
+}
+
+impl Saveable<Inventory> for Inventory {
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        persistence::create_world_dir()?;
+        std::fs::write(
+            persistence::world_path("inventory"),
+            self.serialize().as_bytes(),
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Loadable<Inventory> for Inventory {
+    fn load(_: Box<dyn Any>) -> Result<Inventory, Box<dyn Error>> {
+        let data = String::from_utf8(std::fs::read(persistence::world_path("inventory"))?)?;
+        Inventory::deserialize(&data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_fills_stacks_before_empty_slots() {
+        let mut inventory = Inventory::default();
+        assert_eq!(inventory.add(BlockType::Dirt, 60), 0);
+        assert_eq!(inventory.add(BlockType::Stone, 1), 0);
+        assert_eq!(inventory.add(BlockType::Dirt, 10), 0);
+
+        assert_eq!(inventory.slots[0].unwrap().count, MAX_STACK_SIZE);
+        assert_eq!(inventory.slots[1].unwrap().block_type, BlockType::Stone);
+        assert_eq!(inventory.slots[2].unwrap().count, 6);
+        assert_eq!(inventory.count(BlockType::Dirt), 70);
+    }
+
+    #[test]
+    fn new_players_can_build_with_every_solid_block() {
+        let inventory = Inventory::starter();
+        assert_eq!(inventory.count(BlockType::Stone), MAX_STACK_SIZE);
+        assert_eq!(inventory.count(BlockType::Water), 0);
+        assert!(inventory.selected_block_type().is_some());
+    }
+
+    #[test]
+    fn add_returns_what_does_not_fit() {
+        let mut inventory = Inventory::default();
+        let capacity = MAX_STACK_SIZE * INVENTORY_SIZE as u32;
+        assert_eq!(inventory.add(BlockType::Sand, capacity + 5), 5);
+    }
+
+    #[test]
+    fn take_selected_empties_the_slot() {
+        let mut inventory = Inventory::default();
+        inventory.add(BlockType::Wood, 1);
+        assert_eq!(inventory.take_selected(), Some(BlockType::Wood));
+        assert_eq!(inventory.take_selected(), None);
+        assert!(inventory.selected().is_none());
+    }
+
+    #[test]
+    fn scroll_wraps_around_the_hotbar() {
+        let mut inventory = Inventory::default();
+        inventory.scroll(-1);
+        assert_eq!(inventory.selected_slot, HOTBAR_SIZE - 1);
+        inventory.scroll(2);
+        assert_eq!(inventory.selected_slot, 1);
+    }
+
+    #[test]
+    fn serialization_round_trip() {
+        let mut inventory = Inventory::default();
+        inventory.add(BlockType::Grass, 100);
+        inventory.add(BlockType::Leaf, 3);
+        inventory.select(4);
+
+        let loaded = Inventory::deserialize(&inventory.serialize()).unwrap();
+        assert_eq!(loaded.slots, inventory.slots);
+        assert_eq!(loaded.selected_slot, 4);
+    }
+}
@@ -0,0 +1,253 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{self, AtomicBool, AtomicUsize};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+
+// Jobs with a lower value run first, the ones loading chunks use their distance to the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Priority(pub u32);
+
+impl Priority {
+    // Jobs the caller is waiting for
+    pub const IMMEDIATE: Priority = Priority(0);
+    // Jobs nobody waits for, like the paths of the mobs
+    pub const BACKGROUND: Priority = Priority(u32::MAX);
+
+    // Nearest chunks first, right after the immediate jobs
+    pub fn chunk(center: (i32, i32), key: (i32, i32)) -> Self {
+        let distance = (key.0 - center.0)
+            .unsigned_abs()
+            .max((key.1 - center.1).unsigned_abs());
+        Priority(distance.saturating_add(1))
+    }
+}
+
+// Counters shown by the debug overlay
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JobStats {
+    // Jobs waiting for a worker, the cancelled ones included until a worker drops them
+    pub queued: usize,
+    pub running: usize,
+    pub completed: usize,
+    pub cancelled: usize,
+}
+
+pub enum JobResult<T> {
+    Pending,
+    Done(T),
+    // The job was cancelled or panicked
+    Lost,
+}
+
+// Handle to the result of a job
+pub struct JobHandle<T> {
+    result: mpsc::Receiver<T>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<T> JobHandle<T> {
+    // The job won't run if no worker has started it yet
+    pub fn cancel(&self) {
+        self.cancelled.store(true, atomic::Ordering::Relaxed);
+    }
+    pub fn try_result(&self) -> JobResult<T> {
+        match self.result.try_recv() {
+            Ok(result) => JobResult::Done(result),
+            Err(mpsc::TryRecvError::Empty) => JobResult::Pending,
+            Err(mpsc::TryRecvError::Disconnected) => JobResult::Lost,
+        }
+    }
+    // Waits for the job to finish, None if it was cancelled or panicked
+    pub fn join(self) -> Option<T> {
+        self.result.recv().ok()
+    }
+}
+
+struct QueuedJob {
+    priority: Priority,
+    // Jobs of the same priority run in the order they were spawned
+    sequence: u64,
+    cancelled: Arc<AtomicBool>,
+    run: Box<dyn FnOnce() + Send + 'static>,
+}
+
+// Reversed so that the binary heap pops the lowest priority value, then the oldest job
+impl Ord for QueuedJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.priority, other.sequence).cmp(&(self.priority, self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedJob {}
+
+#[derive(Default)]
+struct Queue {
+    jobs: BinaryHeap<QueuedJob>,
+    next_sequence: u64,
+    // Set when the system is dropped, the workers stop once the queue is empty
+    closed: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    available: Condvar,
+    running: AtomicUsize,
+    completed: AtomicUsize,
+    cancelled: AtomicUsize,
+}
+
+// Worker threads running the jobs by priority
+pub struct JobSystem {
+    shared: Arc<Shared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl JobSystem {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0);
+        let shared = Arc::new(Shared::default());
+        let workers = (0..size)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || work(&shared))
+            })
+            .collect();
+        Self { shared, workers }
+    }
+    pub fn spawn<T, F>(&self, priority: Priority, f: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, result) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut queue = self.shared.queue.lock().unwrap();
+        let sequence = queue.next_sequence;
+        queue.next_sequence += 1;
+        queue.jobs.push(QueuedJob {
+            priority,
+            sequence,
+            cancelled: Arc::clone(&cancelled),
+            run: Box::new(move || {
+                // Nobody may be waiting for the result anymore
+                let _ = sender.send(f());
+            }),
+        });
+        std::mem::drop(queue);
+        self.shared.available.notify_one();
+        JobHandle { result, cancelled }
+    }
+    pub fn stats(&self) -> JobStats {
+        JobStats {
+            queued: self.shared.queue.lock().unwrap().jobs.len(),
+            running: self.shared.running.load(atomic::Ordering::Relaxed),
+            completed: self.shared.completed.load(atomic::Ordering::Relaxed),
+            cancelled: self.shared.cancelled.load(atomic::Ordering::Relaxed),
+        }
+    }
+}
+
+impl Drop for JobSystem {
+    // Waits for the queued jobs, so that the chunks being saved are written
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.available.notify_all();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(job) = queue.jobs.pop() {
+                    break job;
+                }
+                if queue.closed {
+                    return;
+                }
+                queue = shared.available.wait(queue).unwrap();
+            }
+        };
+        if job.cancelled.load(atomic::Ordering::Relaxed) {
+            shared.cancelled.fetch_add(1, atomic::Ordering::Relaxed);
+            continue;
+        }
+        shared.running.fetch_add(1, atomic::Ordering::Relaxed);
+        // A panicking job loses its result but keeps the worker alive
+        let _ = panic::catch_unwind(AssertUnwindSafe(job.run));
+        shared.running.fetch_sub(1, atomic::Ordering::Relaxed);
+        shared.completed.fetch_add(1, atomic::Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keeps the only worker busy until the returned sender is dropped
+    fn block_worker(jobs: &JobSystem) -> mpsc::Sender<()> {
+        let (gate, wait) = mpsc::channel::<()>();
+        let (started, ready) = mpsc::channel();
+        jobs.spawn(Priority::IMMEDIATE, move || {
+            started.send(()).unwrap();
+            let _ = wait.recv();
+        });
+        ready.recv().unwrap();
+        gate
+    }
+
+    #[test]
+    fn jobs_run_by_priority() {
+        let jobs = JobSystem::new(1);
+        let gate = block_worker(&jobs);
+        let order = Arc::new(Mutex::new(vec![]));
+        let handles: Vec<_> = [3, 1, 2, 1]
+            .into_iter()
+            .enumerate()
+            .map(|(index, priority)| {
+                let order = Arc::clone(&order);
+                jobs.spawn(Priority(priority), move || {
+                    order.lock().unwrap().push(index);
+                    index * 10
+                })
+            })
+            .collect();
+        assert_eq!(jobs.stats().queued, 4);
+        std::mem::drop(gate);
+        let results: Vec<_> = handles.into_iter().map(JobHandle::join).collect();
+        assert_eq!(results, vec![Some(0), Some(10), Some(20), Some(30)]);
+        assert_eq!(*order.lock().unwrap(), vec![1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn cancelled_jobs_never_run() {
+        let jobs = JobSystem::new(1);
+        let gate = block_worker(&jobs);
+        let handle = jobs.spawn(Priority::BACKGROUND, || 1);
+        handle.cancel();
+        assert!(matches!(handle.try_result(), JobResult::Pending));
+        std::mem::drop(gate);
+        assert_eq!(handle.join(), None);
+        assert_eq!(jobs.stats().cancelled, 1);
+        assert_eq!(jobs.spawn(Priority::IMMEDIATE, || 2).join(), Some(2));
+    }
+}
@@ -2,18 +2,12 @@
     all(target_os = "windows", not(debug_assertions)),
     windows_subsystem = "windows"
 )]
+use gamepad::Gamepads;
 use state::State;
-use std::io::Cursor;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use winit::dpi::LogicalSize;
-use winit::window::CursorGrabMode;
-use winit::{
-    event::*,
-    event_loop::EventLoop,
-    keyboard::{Key, NamedKey},
-    window::Window,
-};
+use winit::{event::*, event_loop::EventLoop, window::Window};
 
 const DEFAULT_WINDOW_WIDTH: u32 = 1200;
 const DEFAULT_WINDOW_HEIGHT: u32 = 800;
@@ -24,40 +18,44 @@ extern crate lazy_static;
 pub mod blocks;
 pub mod chunk;
 pub mod collision;
+pub mod commands;
+pub mod console;
+pub mod debug;
 pub mod effects;
-pub mod macros;
+pub mod entity;
+pub mod font;
+pub mod gamepad;
+pub mod headless;
+pub mod history;
+pub mod hud;
+pub mod input;
+pub mod inventory;
+pub mod jobs;
 pub mod material;
+pub mod menu;
+pub mod mesh_pool;
+pub mod mining;
+pub mod net;
 pub mod persistence;
 pub mod pipeline;
 pub mod pipelines;
 pub mod player;
+pub mod region;
+pub mod settings;
 pub mod state;
 pub mod structures;
 pub mod utils;
+pub mod vitals;
 pub mod world;
 
 async fn run(event_loop: EventLoop<()>, window: Window) {
     let start = Instant::now();
     let mut total_time = start.elapsed();
-    let mut delta_time = start.elapsed();
 
-    let mut frames = 0;
-    let mut fps_counter = Instant::now();
-
-    let mut size = window.inner_size();
-    size.width = size.width.max(1);
-    size.height = size.height.max(1);
-
-    window
-        .set_cursor_grab(CursorGrabMode::Confined)
-        .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
-        .unwrap();
-    window.set_cursor_visible(false);
     let window = Arc::new(Mutex::new(window));
     let mut state = State::new(window.clone()).await;
+    let mut gamepads = Gamepads::new();
 
-    let mut prev_mouse_pos = glam::vec2(0.0, 0.0);
-    let mut cursor_in = false;
     let mut first_render = true;
 
     event_loop
@@ -72,21 +70,21 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                         state.resize(new_size);
                         window.lock().unwrap().request_redraw();
                     }
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        event:
-                            KeyEvent {
-                                logical_key: Key::Named(NamedKey::Escape),
-                                ..
-                            },
-                        ..
-                    } => {
-                        state.save_state();
+                    WindowEvent::CloseRequested => {
+                        state.close_world();
                         state.dispose();
                         target.exit();
                     }
-
+                    WindowEvent::KeyboardInput { event, .. } if state.menu.is_open() => {
+                        state.handle_menu_keypress(event)
+                    }
+                    WindowEvent::KeyboardInput { event, .. } if state.console.open => {
+                        state.handle_console_keypress(event)
+                    }
                     WindowEvent::KeyboardInput { event, .. } => state.handle_keypress(event),
+                    WindowEvent::CursorMoved { position, .. } => {
+                        state.handle_cursor_moved(glam::vec2(position.x as f32, position.y as f32))
+                    }
                     WindowEvent::MouseInput {
                         state: ElementState::Pressed,
                         button,
@@ -94,33 +92,22 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     } => {
                         state.on_click(button);
                     }
-
-                    WindowEvent::CursorMoved { position, .. } => {
-                        if !cursor_in {
-                            prev_mouse_pos.x = position.x as f32;
-                            prev_mouse_pos.y = position.y as f32;
-                            cursor_in = true;
-                        }
-
-                        prev_mouse_pos.x = position.x as f32;
-                        prev_mouse_pos.y = position.y as f32;
-
-                        // state.handle_mouse(&delta);
+                    WindowEvent::MouseInput {
+                        state: ElementState::Released,
+                        button,
+                        ..
+                    } => {
+                        state.on_release(button);
                     }
-                    WindowEvent::CursorLeft { .. } => cursor_in = false,
+
                     WindowEvent::RedrawRequested => {
-                        frames += 1;
+                        let delta_time = start.elapsed() - total_time;
+                        total_time = start.elapsed();
 
-                        #[cfg(debug_assertions)]
-                        if fps_counter.elapsed().as_secs() >= 3 {
-                            fps_counter = Instant::now();
-                            println!("\x1b[32mFPS - {}\x1b[0m", frames / 3);
-                            frames = 0;
+                        for gamepad_event in gamepads.poll() {
+                            state.handle_gamepad_event(gamepad_event);
                         }
 
-                        delta_time = start.elapsed() - total_time;
-                        total_time = start.elapsed();
-
                         if first_render {
                             // Don't do calcs based on delta time on first render
                             state.update(0.0);
@@ -129,6 +116,11 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                             state.update(delta_time.as_secs_f32());
                         }
                         state.draw();
+                        if state.exit_requested {
+                            state.dispose();
+                            target.exit();
+                            return;
+                        }
                         window.lock().unwrap().request_redraw();
                     }
 
@@ -152,6 +144,24 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 }
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.iter().any(|arg| arg == "--headless") {
+        let result = headless::HeadlessOptions::parse(&args).and_then(headless::run);
+        if let Err(err) = result {
+            eprintln!("{err}\n{}", headless::USAGE);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == "--server") {
+        let result = net::server::ServerOptions::parse(&args).and_then(net::server::run);
+        if let Err(err) = result {
+            eprintln!("{err}\n{}", net::server::USAGE);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let event_loop = EventLoop::new().unwrap();
     let builder = winit::window::WindowBuilder::new();
 
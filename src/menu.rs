@@ -0,0 +1,440 @@
+use crate::input::Action;
+use crate::net::protocol::MAX_NAME_LENGTH;
+use crate::settings::{Setting, Settings};
+
+// Worlds shown at once on the main menu, the rest are reached by scrolling
+pub const MAX_LISTED_WORLDS: usize = 5;
+// Same for the actions on the controls screen
+pub const MAX_LISTED_ACTIONS: usize = 8;
+// Button widths, in unscaled HUD pixels
+const WIDE_BUTTON: f32 = 200.0;
+const WORLD_BUTTON: f32 = 150.0;
+const DELETE_BUTTON: f32 = 46.0;
+const ARROW_BUTTON: f32 = 16.0;
+const SETTING_BUTTON: f32 = 160.0;
+const BINDING_BUTTON: f32 = 220.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Screen {
+    // World selection, shown at startup
+    MainMenu,
+    CreateWorld,
+    ConfirmDelete(String),
+    Playing,
+    Paused,
+    Settings,
+    Controls,
+    JoinServer,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MenuAction {
+    OpenWorld(String),
+    AskDelete(String),
+    DeleteWorld(String),
+    ShowCreateWorld,
+    CreateWorld,
+    ShowJoinServer,
+    JoinServer,
+    // Scrolls the world list or the controls
+    ScrollList(i32),
+    Resume,
+    ShowSettings,
+    AdjustSetting(Setting, i32),
+    ShowControls,
+    // Waits for the next key, button or scroll to bind to the action, replacing its bindings if true
+    CaptureBinding(Action, bool),
+    ClearBinding(Action),
+    ResetBindings,
+    Back,
+    SaveAndQuit,
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Button {
+    pub label: String,
+    pub action: MenuAction,
+    pub width: f32,
+}
+
+impl Button {
+    fn new(label: &str, action: MenuAction, width: f32) -> Self {
+        Self {
+            label: label.to_string(),
+            action,
+            width,
+        }
+    }
+}
+
+pub struct Menu {
+    pub screen: Screen,
+    // Highlighted button, moved with the arrow keys or by hovering it
+    pub selected: usize,
+    pub worlds: Vec<String>,
+    // Index of the first listed world or action
+    pub scroll: usize,
+    // Name typed on the create world screen
+    pub world_name: String,
+    // Typed on the join server screen, into the selected field
+    pub player_name: String,
+    pub server_address: String,
+    // Shown under the title, e.g. why a world couldn't be created
+    pub message: Option<String>,
+    // Action waiting for a new binding on the controls screen
+    pub capturing: Option<(Action, bool)>,
+    // Playing on a server, which saves the world itself
+    pub connected: bool,
+    // Where the settings screen goes back to
+    settings_parent: Screen,
+}
+
+impl Menu {
+    pub fn new(worlds: Vec<String>) -> Self {
+        Self {
+            screen: Screen::MainMenu,
+            selected: 0,
+            worlds,
+            scroll: 0,
+            world_name: String::new(),
+            player_name: String::new(),
+            server_address: String::new(),
+            message: None,
+            capturing: None,
+            connected: false,
+            settings_parent: Screen::Paused,
+        }
+    }
+    pub fn is_open(&self) -> bool {
+        self.screen != Screen::Playing
+    }
+    pub fn show(&mut self, screen: Screen) {
+        if screen == Screen::Settings && !matches!(self.screen, Screen::Settings | Screen::Controls)
+        {
+            self.settings_parent = self.screen.clone();
+        }
+        if screen == Screen::CreateWorld {
+            self.world_name.clear();
+        }
+        if screen != self.screen {
+            self.scroll = 0;
+        }
+        self.screen = screen;
+        self.selected = 0;
+        self.message = None;
+        self.capturing = None;
+    }
+    pub fn title(&self) -> String {
+        match &self.screen {
+            Screen::MainMenu => "RustyCraft".to_string(),
+            Screen::CreateWorld => "Create new world".to_string(),
+            Screen::ConfirmDelete(name) => format!("Delete world \"{name}\"?"),
+            Screen::Playing => String::new(),
+            Screen::Paused => "Game paused".to_string(),
+            Screen::Settings => "Settings".to_string(),
+            Screen::Controls => "Controls".to_string(),
+            Screen::JoinServer => "Join server".to_string(),
+        }
+    }
+    // Buttons of the current screen, laid out in rows
+    pub fn rows(&self, settings: &Settings) -> Vec<Vec<Button>> {
+        match &self.screen {
+            Screen::MainMenu => {
+                let mut rows = self
+                    .worlds
+                    .iter()
+                    .skip(self.scroll)
+                    .take(MAX_LISTED_WORLDS)
+                    .map(|world| {
+                        vec![
+                            Button::new(world, MenuAction::OpenWorld(world.clone()), WORLD_BUTTON),
+                            Button::new(
+                                "Delete",
+                                MenuAction::AskDelete(world.clone()),
+                                DELETE_BUTTON,
+                            ),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                rows.push(vec![Button::new(
+                    "Create new world",
+                    MenuAction::ShowCreateWorld,
+                    WIDE_BUTTON,
+                )]);
+                rows.push(vec![Button::new(
+                    "Join server",
+                    MenuAction::ShowJoinServer,
+                    WIDE_BUTTON,
+                )]);
+                rows.push(vec![Button::new(
+                    "Settings",
+                    MenuAction::ShowSettings,
+                    WIDE_BUTTON,
+                )]);
+                rows.push(vec![Button::new(
+                    "Quit game",
+                    MenuAction::Quit,
+                    WIDE_BUTTON,
+                )]);
+                rows
+            }
+            Screen::CreateWorld => vec![
+                vec![Button::new(
+                    &format!("Name: {}_", self.world_name),
+                    MenuAction::CreateWorld,
+                    WIDE_BUTTON,
+                )],
+                vec![Button::new("Create", MenuAction::CreateWorld, WIDE_BUTTON)],
+                vec![Button::new("Cancel", MenuAction::Back, WIDE_BUTTON)],
+            ],
+            Screen::JoinServer => {
+                // The cursor shows which field is typed into
+                let cursor = |field| if self.selected == field { "_" } else { "" };
+                vec![
+                    vec![Button::new(
+                        &format!("Name: {}{}", self.player_name, cursor(0)),
+                        MenuAction::JoinServer,
+                        WIDE_BUTTON,
+                    )],
+                    vec![Button::new(
+                        &format!("Address: {}{}", self.server_address, cursor(1)),
+                        MenuAction::JoinServer,
+                        WIDE_BUTTON,
+                    )],
+                    vec![Button::new("Join", MenuAction::JoinServer, WIDE_BUTTON)],
+                    vec![Button::new("Cancel", MenuAction::Back, WIDE_BUTTON)],
+                ]
+            }
+            Screen::ConfirmDelete(name) => vec![
+                vec![Button::new(
+                    "Delete",
+                    MenuAction::DeleteWorld(name.clone()),
+                    WIDE_BUTTON,
+                )],
+                vec![Button::new("Cancel", MenuAction::Back, WIDE_BUTTON)],
+            ],
+            Screen::Playing => vec![],
+            Screen::Paused => vec![
+                vec![Button::new("Resume", MenuAction::Resume, WIDE_BUTTON)],
+                vec![Button::new(
+                    "Settings",
+                    MenuAction::ShowSettings,
+                    WIDE_BUTTON,
+                )],
+                vec![Button::new(
+                    if self.connected {
+                        "Disconnect"
+                    } else {
+                        "Save and quit to title"
+                    },
+                    MenuAction::SaveAndQuit,
+                    WIDE_BUTTON,
+                )],
+            ],
+            Screen::Settings => {
+                let mut rows = Setting::ALL
+                    .iter()
+                    .map(|setting| {
+                        let label = format!("{}: {}", setting.label(), settings.value(*setting));
+                        vec![
+                            Button::new("<", MenuAction::AdjustSetting(*setting, -1), ARROW_BUTTON),
+                            Button::new(
+                                &label,
+                                MenuAction::AdjustSetting(*setting, 1),
+                                SETTING_BUTTON,
+                            ),
+                            Button::new(">", MenuAction::AdjustSetting(*setting, 1), ARROW_BUTTON),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                rows.push(vec![Button::new(
+                    "Controls",
+                    MenuAction::ShowControls,
+                    WIDE_BUTTON,
+                )]);
+                rows.push(vec![Button::new("Back", MenuAction::Back, WIDE_BUTTON)]);
+                rows
+            }
+            Screen::Controls => {
+                let mut rows = Action::all()
+                    .into_iter()
+                    .skip(self.scroll)
+                    .take(MAX_LISTED_ACTIONS)
+                    .map(|action| {
+                        let bindings = match self.capturing {
+                            Some((capturing, _)) if capturing == action => "...".to_string(),
+                            _ => settings.bindings.describe(action),
+                        };
+                        let label = format!("{}: {}", action.label(), bindings);
+                        vec![
+                            Button::new(
+                                &label,
+                                MenuAction::CaptureBinding(action, true),
+                                BINDING_BUTTON,
+                            ),
+                            Button::new(
+                                "+",
+                                MenuAction::CaptureBinding(action, false),
+                                ARROW_BUTTON,
+                            ),
+                            Button::new("x", MenuAction::ClearBinding(action), ARROW_BUTTON),
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                rows.push(vec![Button::new(
+                    "Reset to defaults",
+                    MenuAction::ResetBindings,
+                    WIDE_BUTTON,
+                )]);
+                rows.push(vec![Button::new("Back", MenuAction::Back, WIDE_BUTTON)]);
+                rows
+            }
+        }
+    }
+    pub fn move_selection(&mut self, offset: i32, button_count: usize) {
+        if button_count > 0 {
+            self.selected =
+                (self.selected as i32 + offset).rem_euclid(button_count as i32) as usize;
+        }
+    }
+    // What Escape does on the current screen
+    pub fn back_action(&self) -> Option<MenuAction> {
+        match self.screen {
+            Screen::MainMenu | Screen::Playing => None,
+            Screen::Paused => Some(MenuAction::Resume),
+            _ => Some(MenuAction::Back),
+        }
+    }
+    // Handles the actions that only change the menu, the others are returned for the game to run
+    pub fn apply(&mut self, action: MenuAction) -> Option<MenuAction> {
+        match action {
+            MenuAction::AskDelete(name) => self.show(Screen::ConfirmDelete(name)),
+            MenuAction::ShowCreateWorld => self.show(Screen::CreateWorld),
+            MenuAction::ShowJoinServer => self.show(Screen::JoinServer),
+            MenuAction::ShowSettings => self.show(Screen::Settings),
+            MenuAction::ShowControls => self.show(Screen::Controls),
+            MenuAction::ScrollList(offset) => {
+                let max_scroll = match self.screen {
+                    Screen::MainMenu => self.worlds.len().saturating_sub(MAX_LISTED_WORLDS),
+                    Screen::Controls => Action::all().len().saturating_sub(MAX_LISTED_ACTIONS),
+                    _ => 0,
+                };
+                self.scroll = (self.scroll as i32 + offset).clamp(0, max_scroll as i32) as usize;
+            }
+            MenuAction::CaptureBinding(action, replace) => {
+                self.capturing = Some((action, replace));
+                self.message = Some(format!(
+                    "Press a key or button for {}, Escape cancels",
+                    action.label()
+                ));
+            }
+            MenuAction::Back => match self.screen {
+                Screen::Settings => self.show(self.settings_parent.clone()),
+                Screen::Controls => self.show(Screen::Settings),
+                _ => self.show(Screen::MainMenu),
+            },
+            action => return Some(action),
+        }
+        None
+    }
+    pub fn type_text(&mut self, text: &str) {
+        match (&self.screen, self.selected) {
+            (Screen::CreateWorld, _) => self.world_name.extend(
+                text.chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '-' | '_')),
+            ),
+            (Screen::JoinServer, 0) => {
+                let allowed = text
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'));
+                self.player_name.extend(allowed);
+                self.player_name.truncate(MAX_NAME_LENGTH);
+            }
+            (Screen::JoinServer, 1) => self
+                .server_address
+                .extend(text.chars().filter(|c| c.is_ascii_graphic())),
+            _ => {}
+        }
+    }
+    pub fn backspace(&mut self) {
+        match (&self.screen, self.selected) {
+            (Screen::CreateWorld, _) => {
+                self.world_name.pop();
+            }
+            (Screen::JoinServer, 0) => {
+                self.player_name.pop();
+            }
+            (Screen::JoinServer, 1) => {
+                self.server_address.pop();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn main_menu_lists_worlds_with_scrolling() {
+        let worlds = (0..8).map(|i| format!("world {i}")).collect();
+        let mut menu = Menu::new(worlds);
+        let rows = menu.rows(&Settings::default());
+        // The worlds, create, join, settings and quit
+        assert_eq!(rows.len(), MAX_LISTED_WORLDS + 4);
+        assert_eq!(
+            rows[0][0].action,
+            MenuAction::OpenWorld("world 0".to_string())
+        );
+
+        assert_eq!(menu.apply(MenuAction::ScrollList(10)), None);
+        assert_eq!(menu.scroll, 3);
+        assert_eq!(menu.rows(&Settings::default())[0][0].label, "world 3");
+    }
+
+    #[test]
+    fn settings_go_back_to_where_they_were_opened() {
+        let mut menu = Menu::new(vec![]);
+        menu.show(Screen::Paused);
+        menu.apply(MenuAction::ShowSettings);
+        assert_eq!(menu.screen, Screen::Settings);
+        assert_eq!(menu.back_action(), Some(MenuAction::Back));
+        menu.apply(MenuAction::ShowControls);
+        menu.apply(MenuAction::Back);
+        assert_eq!(menu.screen, Screen::Settings);
+        menu.apply(MenuAction::Back);
+        assert_eq!(menu.screen, Screen::Paused);
+        assert_eq!(menu.back_action(), Some(MenuAction::Resume));
+    }
+
+    #[test]
+    fn game_actions_are_returned() {
+        let mut menu = Menu::new(vec!["a".to_string()]);
+        menu.apply(MenuAction::AskDelete("a".to_string()));
+        assert_eq!(menu.screen, Screen::ConfirmDelete("a".to_string()));
+        assert_eq!(
+            menu.apply(MenuAction::DeleteWorld("a".to_string())),
+            Some(MenuAction::DeleteWorld("a".to_string()))
+        );
+        menu.apply(MenuAction::Back);
+        assert_eq!(menu.screen, Screen::MainMenu);
+    }
+
+    #[test]
+    fn join_server_types_into_the_selected_field() {
+        let mut menu = Menu::new(vec![]);
+        menu.apply(MenuAction::ShowJoinServer);
+        menu.type_text("Al ex!");
+        menu.move_selection(1, 4);
+        menu.type_text("localhost: 4000");
+        menu.backspace();
+        assert_eq!(menu.player_name, "Alex");
+        assert_eq!(menu.server_address, "localhost:400");
+        assert_eq!(
+            menu.rows(&Settings::default())[1][0].label,
+            "Address: localhost:400_"
+        );
+    }
+}
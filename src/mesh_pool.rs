@@ -0,0 +1,692 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex, RwLock};
+
+use wgpu::util::{DeviceExt, DrawIndexedIndirect};
+
+use crate::blocks::block::BlockVertexData;
+use crate::chunk::{Chunk, ChunkMesh};
+use crate::player::Camera;
+
+// Vertices and indices of each shared buffer, a chunk too big for them gets buffers of its own
+const PAGE_VERTICES: u32 = 1 << 19;
+const PAGE_INDICES: u32 = PAGE_VERTICES / 4 * 6;
+const VERTEX_SIZE: u64 = std::mem::size_of::<BlockVertexData>() as u64;
+const INDEX_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+const OFFSET_SIZE: u64 = std::mem::size_of::<[i32; 2]>() as u64;
+const COMMAND_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirect>() as u64;
+// Chunk offsets and draw commands the buffers hold at first, they double when full
+const INITIAL_SLOTS: u64 = 1024;
+
+// First-fit allocator of the ranges of a buffer, in elements
+#[derive(Debug)]
+pub struct RangeAllocator {
+    // Sorted, and never touching each other
+    free: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+    pub fn new(size: u32) -> Self {
+        Self {
+            free: std::iter::once(0..size).collect(),
+        }
+    }
+    pub fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+        if len == 0 {
+            return Some(0..0);
+        }
+        let index = self.free.iter().position(|free| free.len() as u32 >= len)?;
+        let free = &mut self.free[index];
+        let allocated = free.start..free.start + len;
+        free.start += len;
+        if free.start == free.end {
+            self.free.remove(index);
+        }
+        Some(allocated)
+    }
+    // Merges the range with the free ones next to it
+    pub fn free(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+        let index = self.free.partition_point(|free| free.start < range.start);
+        let joins_previous = index > 0 && self.free[index - 1].end == range.start;
+        let joins_next = index < self.free.len() && self.free[index].start == range.end;
+        match (joins_previous, joins_next) {
+            (true, true) => {
+                self.free[index - 1].end = self.free[index].end;
+                self.free.remove(index);
+            }
+            (true, false) => self.free[index - 1].end = range.end,
+            (false, true) => self.free[index].start = range.start,
+            (false, false) => self.free.insert(index, range),
+        }
+    }
+}
+
+// How the chunks are drawn, depending on what the device supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawMode {
+    // One call per page
+    MultiDrawIndirect,
+    // One indirect call per chunk
+    Indirect,
+    // Direct calls, for the devices that can't start indirect calls at another instance than the first
+    Direct,
+}
+
+impl DrawMode {
+    // Requested when the adapter has them, the chunks are still drawn without
+    pub const FEATURES: wgpu::Features =
+        wgpu::Features::MULTI_DRAW_INDIRECT.union(wgpu::Features::INDIRECT_FIRST_INSTANCE);
+
+    pub fn for_features(features: wgpu::Features) -> Self {
+        if !features.contains(wgpu::Features::INDIRECT_FIRST_INSTANCE) {
+            DrawMode::Direct
+        } else if features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+            DrawMode::MultiDrawIndirect
+        } else {
+            DrawMode::Indirect
+        }
+    }
+}
+
+// One of the shared buffers the chunk meshes are sub-allocated from
+#[derive(Debug)]
+pub struct MeshPage {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+}
+
+// Where a mesh is in the pages
+#[derive(Debug, Clone, Default)]
+struct MeshRange {
+    page: usize,
+    vertices: Range<u32>,
+    indices: Range<u32>,
+}
+
+impl MeshRange {
+    // The instance index is the chunk's slot, the shaders read its offset with it
+    fn command(&self, slot: u32) -> DrawIndexedIndirect {
+        DrawIndexedIndirect {
+            vertex_count: self.indices.len() as u32,
+            instance_count: 1,
+            base_index: self.indices.start,
+            vertex_offset: self.vertices.start as i32,
+            base_instance: slot,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ChunkAllocation {
+    slot: u32,
+    solid: MeshRange,
+    water: MeshRange,
+}
+
+// Mesh of a loaded chunk as the debug overlay sees it
+#[derive(Debug)]
+pub struct ChunkDraw {
+    pub x: i32,
+    pub y: i32,
+    // Whether the chunk is in front of the camera
+    pub visible: bool,
+    pub indices: u32,
+    pub water_indices: u32,
+}
+
+// Draw commands of the visible chunks of a page, as ranges of the indirect buffer
+#[derive(Debug)]
+struct PageDraws {
+    page: usize,
+    solid: Range<u32>,
+    water: Range<u32>,
+}
+
+// Chunk meshes drawn in a frame, the buffers stay alive while the frame holds the snapshot
+#[derive(Debug, Default)]
+pub struct MeshSnapshot {
+    pub chunks: Vec<ChunkDraw>,
+    pages: Vec<PageDraws>,
+    commands: Vec<DrawIndexedIndirect>,
+    // The buffers are set by ChunkMeshes::publish, a snapshot without them draws nothing
+    buffers: Vec<Arc<MeshPage>>,
+    bind_group: Option<Arc<wgpu::BindGroup>>,
+    indirect_buffer: Option<Arc<wgpu::Buffer>>,
+    mode: Option<DrawMode>,
+}
+
+impl MeshSnapshot {
+    // Draws the solid or the water meshes of the visible chunks, the pipeline and the other bind groups are set
+    // by the caller
+    pub fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, water: bool) {
+        let (Some(bind_group), Some(indirect_buffer), Some(mode)) =
+            (&self.bind_group, &self.indirect_buffer, self.mode)
+        else {
+            return;
+        };
+        rpass.set_bind_group(1, bind_group, &[]);
+        for draws in self.pages.iter() {
+            let commands = if water {
+                draws.water.clone()
+            } else {
+                draws.solid.clone()
+            };
+            if commands.is_empty() {
+                continue;
+            }
+            let page = &self.buffers[draws.page];
+            rpass.set_vertex_buffer(0, page.vertex_buffer.slice(..));
+            rpass.set_index_buffer(page.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            match mode {
+                DrawMode::MultiDrawIndirect => rpass.multi_draw_indexed_indirect(
+                    indirect_buffer,
+                    commands.start as u64 * COMMAND_SIZE,
+                    commands.len() as u32,
+                ),
+                DrawMode::Indirect => {
+                    for command in commands {
+                        rpass.draw_indexed_indirect(indirect_buffer, command as u64 * COMMAND_SIZE);
+                    }
+                }
+                DrawMode::Direct => {
+                    for command in &self.commands[commands.start as usize..commands.end as usize] {
+                        rpass.draw_indexed(
+                            command.base_index..command.base_index + command.vertex_count,
+                            command.vertex_offset,
+                            command.base_instance..command.base_instance + 1,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    // Calls made by the two draws of a frame, for the debug overlay
+    pub fn draw_calls(&self) -> usize {
+        match self.mode {
+            Some(DrawMode::MultiDrawIndirect) => self
+                .pages
+                .iter()
+                .map(|draws| {
+                    usize::from(!draws.solid.is_empty()) + usize::from(!draws.water.is_empty())
+                })
+                .sum(),
+            Some(_) => self.commands.len(),
+            None => 0,
+        }
+    }
+}
+
+// Ranges of the pages and slots taken by the chunk meshes, apart from the buffers they're in
+#[derive(Debug, Default)]
+struct MeshAllocator {
+    // Vertices and indices left in each page
+    pages: Vec<(RangeAllocator, RangeAllocator)>,
+    chunks: HashMap<(i32, i32), ChunkAllocation>,
+    // Offset of the chunk of each slot, mirrored by the storage buffer
+    offsets: Vec<[i32; 2]>,
+    free_slots: Vec<u32>,
+    // Freed while the last published frame may still draw them, they're reused after the next publish
+    released: Vec<MeshRange>,
+    released_slots: Vec<u32>,
+}
+
+impl MeshAllocator {
+    fn add_page(&mut self, vertices: u32, indices: u32) {
+        self.pages
+            .push((RangeAllocator::new(vertices), RangeAllocator::new(indices)));
+    }
+    // Ranges in the first page with room for the mesh, None if no page has. Empty meshes take no room
+    fn allocate(&mut self, vertex_count: u32, index_count: u32) -> Option<MeshRange> {
+        if index_count == 0 {
+            return Some(MeshRange::default());
+        }
+        let mut pages = self.pages.iter_mut().enumerate();
+        pages.find_map(|(page, (vertex_allocator, index_allocator))| {
+            let vertices = vertex_allocator.allocate(vertex_count)?;
+            match index_allocator.allocate(index_count) {
+                Some(indices) => Some(MeshRange {
+                    page,
+                    vertices,
+                    indices,
+                }),
+                None => {
+                    vertex_allocator.free(vertices);
+                    None
+                }
+            }
+        })
+    }
+    // Replaces the meshes of the chunk, returns its slot and whether the chunk just got it
+    fn insert(&mut self, x: i32, y: i32, solid: MeshRange, water: MeshRange) -> (u32, bool) {
+        let (slot, is_new) = match self.chunks.get(&(x, y)) {
+            Some(previous) => (previous.slot, false),
+            None => (self.take_slot([x, y]), true),
+        };
+        let allocation = ChunkAllocation { slot, solid, water };
+        if let Some(previous) = self.chunks.insert((x, y), allocation) {
+            self.released.extend([previous.solid, previous.water]);
+        }
+        (slot, is_new)
+    }
+    fn take_slot(&mut self, offset: [i32; 2]) -> u32 {
+        match self.free_slots.pop() {
+            Some(slot) => {
+                self.offsets[slot as usize] = offset;
+                slot
+            }
+            None => {
+                self.offsets.push(offset);
+                self.offsets.len() as u32 - 1
+            }
+        }
+    }
+    fn remove(&mut self, x: i32, y: i32) {
+        if let Some(previous) = self.chunks.remove(&(x, y)) {
+            self.released.extend([previous.solid, previous.water]);
+            self.released_slots.push(previous.slot);
+        }
+    }
+    fn clear(&mut self) {
+        let keys: Vec<_> = self.chunks.keys().copied().collect();
+        for (x, y) in keys {
+            self.remove(x, y);
+        }
+    }
+    // Frees what the frames drawn until now may have used
+    fn reclaim(&mut self) {
+        for range in std::mem::take(&mut self.released) {
+            let (vertex_allocator, index_allocator) = &mut self.pages[range.page];
+            vertex_allocator.free(range.vertices);
+            index_allocator.free(range.indices);
+        }
+        self.free_slots.append(&mut self.released_slots);
+    }
+    // Snapshot of the meshes written so far without its buffers, only the visible chunks get draw commands
+    fn publish<F>(&mut self, is_visible: F) -> MeshSnapshot
+    where
+        F: Fn(i32, i32) -> bool,
+    {
+        self.reclaim();
+
+        let mut chunks = vec![];
+        let mut solid: Vec<Vec<DrawIndexedIndirect>> = vec![vec![]; self.pages.len()];
+        let mut water: Vec<Vec<DrawIndexedIndirect>> = vec![vec![]; self.pages.len()];
+        for (&(x, y), allocation) in self.chunks.iter() {
+            let visible = is_visible(x, y);
+            chunks.push(ChunkDraw {
+                x,
+                y,
+                visible,
+                indices: allocation.solid.indices.len() as u32,
+                water_indices: allocation.water.indices.len() as u32,
+            });
+            if !visible {
+                continue;
+            }
+            for (draws, range) in [
+                (&mut solid, &allocation.solid),
+                (&mut water, &allocation.water),
+            ] {
+                if !range.indices.is_empty() {
+                    draws[range.page].push(range.command(allocation.slot));
+                }
+            }
+        }
+
+        let mut commands = vec![];
+        let mut pages = vec![];
+        for (page, (solid, water)) in solid.into_iter().zip(water).enumerate() {
+            let start = commands.len() as u32;
+            commands.extend(solid);
+            let middle = commands.len() as u32;
+            commands.extend(water);
+            pages.push(PageDraws {
+                page,
+                solid: start..middle,
+                water: middle..commands.len() as u32,
+            });
+        }
+        MeshSnapshot {
+            chunks,
+            pages,
+            commands,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MeshPool {
+    pages: Vec<Arc<MeshPage>>,
+    allocator: MeshAllocator,
+    offsets_buffer: wgpu::Buffer,
+    bind_group: Arc<wgpu::BindGroup>,
+    indirect_buffer: Arc<wgpu::Buffer>,
+}
+
+impl MeshPool {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout) -> Self {
+        let offsets_buffer = create_offsets_buffer(device, &[], INITIAL_SLOTS);
+        Self {
+            pages: vec![],
+            allocator: MeshAllocator::default(),
+            bind_group: Arc::new(create_offsets_bind_group(device, layout, &offsets_buffer)),
+            offsets_buffer,
+            indirect_buffer: Arc::new(create_indirect_buffer(device, INITIAL_SLOTS)),
+        }
+    }
+    fn add_page(&mut self, device: &wgpu::Device, vertices: u32, indices: u32) {
+        let page = self.pages.len();
+        let buffer = |size: u64, usage: wgpu::BufferUsages, label: String| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&label),
+                size,
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        self.pages.push(Arc::new(MeshPage {
+            vertex_buffer: buffer(
+                vertices as u64 * VERTEX_SIZE,
+                wgpu::BufferUsages::VERTEX,
+                format!("chunk-vertex-page-{page}"),
+            ),
+            index_buffer: buffer(
+                indices as u64 * INDEX_SIZE,
+                wgpu::BufferUsages::INDEX,
+                format!("chunk-index-page-{page}"),
+            ),
+        }));
+        self.allocator.add_page(vertices, indices);
+    }
+    // Copies the mesh to the first page with room for it, a new page is added if none has
+    fn allocate(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[BlockVertexData],
+        indices: &[u32],
+    ) -> MeshRange {
+        let (vertex_count, index_count) = (vertices.len() as u32, indices.len() as u32);
+        let range = match self.allocator.allocate(vertex_count, index_count) {
+            Some(range) => range,
+            None => {
+                self.add_page(
+                    device,
+                    vertex_count.max(PAGE_VERTICES),
+                    index_count.max(PAGE_INDICES),
+                );
+                self.allocator.allocate(vertex_count, index_count).unwrap()
+            }
+        };
+        if indices.is_empty() {
+            return range;
+        }
+        let buffers = &self.pages[range.page];
+        queue.write_buffer(
+            &buffers.vertex_buffer,
+            range.vertices.start as u64 * VERTEX_SIZE,
+            bytemuck::cast_slice(vertices),
+        );
+        queue.write_buffer(
+            &buffers.index_buffer,
+            range.indices.start as u64 * INDEX_SIZE,
+            bytemuck::cast_slice(indices),
+        );
+        range
+    }
+    // Copies the chunk offset of the slot to the storage buffer, which grows when it's full
+    fn write_offset(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        slot: u32,
+    ) {
+        let offsets = &self.allocator.offsets;
+        if offsets.len() as u64 * OFFSET_SIZE > self.offsets_buffer.size() {
+            let capacity = self.offsets_buffer.size() / OFFSET_SIZE * 2;
+            self.offsets_buffer = create_offsets_buffer(device, offsets, capacity);
+            self.bind_group = Arc::new(create_offsets_bind_group(
+                device,
+                layout,
+                &self.offsets_buffer,
+            ));
+        } else {
+            queue.write_buffer(
+                &self.offsets_buffer,
+                slot as u64 * OFFSET_SIZE,
+                bytemuck::cast_slice(&offsets[slot as usize..=slot as usize]),
+            );
+        }
+    }
+}
+
+// Meshes of the loaded chunks, double-buffered. The mesh jobs write them to the pages while the frames draw the
+// published snapshot, so drawing never locks the chunk map or a chunk while they're generated or edited.
+#[derive(Debug)]
+pub struct ChunkMeshes {
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    layout: Arc<wgpu::BindGroupLayout>,
+    mode: DrawMode,
+    pool: Mutex<MeshPool>,
+    front: RwLock<Arc<MeshSnapshot>>,
+}
+
+impl ChunkMeshes {
+    pub fn new(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        layout: Arc<wgpu::BindGroupLayout>,
+    ) -> Self {
+        Self {
+            mode: DrawMode::for_features(device.features()),
+            pool: Mutex::new(MeshPool::new(&device, &layout)),
+            front: RwLock::new(Arc::new(MeshSnapshot::default())),
+            device,
+            queue,
+            layout,
+        }
+    }
+    pub fn insert(&self, x: i32, y: i32, mesh: &ChunkMesh) {
+        let (device, queue) = (&self.device, &self.queue);
+        let mut pool = self.pool.lock().unwrap();
+        let solid = pool.allocate(device, queue, &mesh.vertices, &mesh.indices);
+        let water = pool.allocate(device, queue, &mesh.water_vertices, &mesh.water_indices);
+        let (slot, is_new) = pool.allocator.insert(x, y, solid, water);
+        if is_new {
+            pool.write_offset(device, queue, &self.layout, slot);
+        }
+    }
+    pub fn remove(&self, x: i32, y: i32) {
+        self.pool.lock().unwrap().allocator.remove(x, y);
+    }
+    // Removes the meshes of every chunk and draws none from now on, for when their world is closed
+    pub fn clear(&self) {
+        let mut pool = self.pool.lock().unwrap();
+        pool.allocator.clear();
+        *self.front.write().unwrap() = Arc::new(MeshSnapshot::default());
+    }
+    // Hands the meshes written so far to the next frames, culling the chunks out of the camera's view
+    pub fn publish(&self, camera: &Camera) {
+        let mut pool = self.pool.lock().unwrap();
+        let mut snapshot = pool
+            .allocator
+            .publish(|x, y| Chunk::is_visible(x, y, camera));
+
+        let commands = &snapshot.commands;
+        if self.mode != DrawMode::Direct && !commands.is_empty() {
+            if commands.len() as u64 * COMMAND_SIZE > pool.indirect_buffer.size() {
+                let capacity = (commands.len() as u64).next_power_of_two();
+                pool.indirect_buffer = Arc::new(create_indirect_buffer(&self.device, capacity));
+            }
+            let bytes: Vec<u8> = commands
+                .iter()
+                .flat_map(|command| command.as_bytes().iter().copied())
+                .collect();
+            self.queue.write_buffer(&pool.indirect_buffer, 0, &bytes);
+        }
+
+        snapshot.buffers = pool.pages.clone();
+        snapshot.bind_group = Some(Arc::clone(&pool.bind_group));
+        snapshot.indirect_buffer = Some(Arc::clone(&pool.indirect_buffer));
+        snapshot.mode = Some(self.mode);
+        *self.front.write().unwrap() = Arc::new(snapshot);
+    }
+    pub fn snapshot(&self) -> Arc<MeshSnapshot> {
+        Arc::clone(&self.front.read().unwrap())
+    }
+}
+
+fn create_offsets_buffer(
+    device: &wgpu::Device,
+    offsets: &[[i32; 2]],
+    capacity: u64,
+) -> wgpu::Buffer {
+    let mut contents = vec![[0; 2]; capacity as usize];
+    contents[..offsets.len()].copy_from_slice(offsets);
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("chunk-offsets"),
+        contents: bytemuck::cast_slice(&contents),
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+    })
+}
+
+fn create_offsets_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    offsets_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        label: Some("chunk-offsets-bg"),
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: offsets_buffer.as_entire_binding(),
+        }],
+    })
+}
+
+fn create_indirect_buffer(device: &wgpu::Device, capacity: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("chunk-draw-commands"),
+        size: capacity * COMMAND_SIZE,
+        usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freed_ranges_are_merged_and_reused() {
+        let mut allocator = RangeAllocator::new(100);
+        let a = allocator.allocate(30).unwrap();
+        let b = allocator.allocate(30).unwrap();
+        let c = allocator.allocate(30).unwrap();
+        assert_eq!((a.clone(), b.clone(), c.clone()), (0..30, 30..60, 60..90));
+        assert_eq!(allocator.allocate(20), None);
+
+        allocator.free(a);
+        allocator.free(c);
+        // First fit, in the hole left by the first range
+        assert_eq!(allocator.allocate(10), Some(0..10));
+        allocator.free(b);
+        // 10..100 is free again as a single range
+        assert_eq!(allocator.allocate(90), Some(10..100));
+        assert_eq!(allocator.allocate(0), Some(0..0));
+    }
+
+    #[test]
+    fn draw_mode_follows_the_features() {
+        use wgpu::Features;
+        assert_eq!(
+            DrawMode::for_features(DrawMode::FEATURES),
+            DrawMode::MultiDrawIndirect
+        );
+        assert_eq!(
+            DrawMode::for_features(Features::INDIRECT_FIRST_INSTANCE),
+            DrawMode::Indirect
+        );
+        assert_eq!(
+            DrawMode::for_features(Features::MULTI_DRAW_INDIRECT),
+            DrawMode::Direct
+        );
+    }
+
+    // Allocator with a single page, and the chunk at 0, 0 holding a solid mesh in it
+    fn allocator_with_a_chunk(vertices: u32, indices: u32) -> MeshAllocator {
+        let mut allocator = MeshAllocator::default();
+        allocator.add_page(100, 150);
+        let solid = allocator.allocate(vertices, indices).unwrap();
+        allocator.insert(0, 0, solid, MeshRange::default());
+        allocator
+    }
+
+    #[test]
+    fn published_meshes_are_drawn_until_the_next_publish() {
+        let mut allocator = allocator_with_a_chunk(40, 60);
+        let published = allocator.publish(|_, _| true);
+
+        // The chunk is meshed again while the frames draw the published snapshot
+        let remeshed = allocator.allocate(40, 60).unwrap();
+        assert_eq!(
+            (remeshed.vertices.clone(), remeshed.indices.clone()),
+            (40..80, 60..120)
+        );
+        assert_eq!(
+            allocator.insert(0, 0, remeshed, MeshRange::default()),
+            (0, false)
+        );
+        assert_eq!(published.commands.len(), 1);
+        assert_eq!(published.commands[0].base_index, 0);
+        assert_eq!(published.commands[0].vertex_offset, 0);
+
+        let next = allocator.publish(|_, _| true);
+        assert_eq!(next.commands.len(), 1);
+        assert_eq!(next.commands[0].base_index, 60);
+        // The first mesh is only freed once the next snapshot replaces the one that drew it
+        assert_eq!(allocator.allocate(40, 60).unwrap().vertices, 0..40);
+    }
+
+    #[test]
+    fn cleared_meshes_are_freed_at_the_next_publish() {
+        let mut allocator = allocator_with_a_chunk(60, 90);
+        allocator.clear();
+        assert!(allocator.allocate(60, 90).is_none());
+        assert!(allocator.publish(|_, _| true).chunks.is_empty());
+        assert!(allocator.allocate(60, 90).is_some());
+    }
+
+    #[test]
+    fn removed_chunks_are_freed_at_the_next_publish() {
+        let mut allocator = allocator_with_a_chunk(60, 90);
+        allocator.publish(|_, _| true);
+
+        allocator.remove(0, 0);
+        assert!(allocator.allocate(60, 90).is_none());
+        let empty = MeshRange::default();
+        assert_eq!(
+            allocator.insert(1, 0, empty.clone(), empty.clone()),
+            (1, true)
+        );
+
+        let published = allocator.publish(|x, _| x != 1);
+        assert_eq!(published.chunks.len(), 1);
+        assert!(!published.chunks[0].visible);
+        assert!(published.commands.is_empty());
+        let range = allocator.allocate(60, 90).unwrap();
+        assert_eq!(
+            (range.page, range.vertices, range.indices),
+            (0, 0..60, 0..90)
+        );
+        // The slot of the removed chunk goes to the next new one
+        assert_eq!(allocator.insert(2, 0, empty.clone(), empty), (0, true));
+    }
+}
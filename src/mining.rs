@@ -0,0 +1,61 @@
+use glam::Vec3;
+
+use crate::blocks::block_type::BlockType;
+
+// Number of crack overlays shown while a block is being mined
+pub const CRACK_STAGES: u32 = 10;
+
+// Block that the player is mining in survival mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MiningProgress {
+    // Absolute position of the block
+    pub position: Vec3,
+    pub elapsed: f32,
+    pub duration: f32,
+}
+
+impl MiningProgress {
+    pub fn new(position: Vec3, block_type: BlockType) -> Self {
+        Self {
+            position,
+            elapsed: 0.0,
+            duration: block_type.mining_time(),
+        }
+    }
+    // Returns true once the block is mined
+    pub fn advance(&mut self, delta_time: f32) -> bool {
+        self.elapsed += delta_time;
+        self.is_done()
+    }
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+    // Crack overlay to show, from 0 to CRACK_STAGES - 1
+    pub fn stage(&self) -> u32 {
+        let progress = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        ((progress * CRACK_STAGES as f32) as u32).min(CRACK_STAGES - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harder_blocks_take_longer() {
+        let stone = MiningProgress::new(Vec3::ZERO, BlockType::Stone);
+        let dirt = MiningProgress::new(Vec3::ZERO, BlockType::Dirt);
+        assert_eq!(stone.duration, BlockType::Stone.get_config().hardness);
+        assert!(stone.duration > dirt.duration);
+    }
+
+    #[test]
+    fn stages_follow_progress() {
+        let mut progress = MiningProgress::new(Vec3::ZERO, BlockType::Dirt);
+        assert_eq!(progress.stage(), 0);
+        assert!(!progress.advance(progress.duration / 2.0));
+        assert_eq!(progress.stage(), CRACK_STAGES / 2);
+        assert!(progress.advance(progress.duration));
+        assert_eq!(progress.stage(), CRACK_STAGES - 1);
+    }
+}
@@ -0,0 +1,406 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::f32::consts::{PI, TAU};
+use std::io;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use glam::{IVec3, Vec3};
+
+use super::protocol::{
+    is_newer_sequence, ClientMessage, Datagram, ServerMessage, PROTOCOL_VERSION,
+};
+use super::stream::MessageStream;
+use super::DEFAULT_PORT;
+use crate::blocks::block_type::BlockType;
+use crate::player::GameMode;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// Other players are drawn this far in the past, so that there are positions to interpolate between
+const INTERPOLATION_DELAY: f32 = 0.1;
+// How far the head turns before the body follows
+const MAX_HEAD_TURN: f32 = 0.9;
+// How fast the body turns towards the head while walking
+const BODY_TURN_SPEED: f32 = 6.0;
+// Blocks per second at which the limbs swing the most
+const FULL_SWING_SPEED: f32 = 4.3;
+// How fast the swing follows changes of speed
+const SWING_RESPONSE: f32 = 10.0;
+
+// What the server sent when the client joined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Welcome {
+    pub spawn: Vec3,
+    pub time: f32,
+    pub game_mode: GameMode,
+}
+
+// Another player of the server, positions are eye positions in collision space
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemotePlayer {
+    pub name: String,
+    // Latest position received
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    // Where the player is drawn
+    pub pose: PlayerPose,
+    sequence: Option<u32>,
+    snapshots: VecDeque<Snapshot>,
+    clock: f32,
+}
+
+// Interpolated state of a remote player, used to draw its model
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PlayerPose {
+    pub position: Vec3,
+    // Direction of the head, the body lags behind it
+    pub yaw: f32,
+    pub pitch: f32,
+    pub body_yaw: f32,
+    // Blocks walked so far, drives the phase of the limbs
+    pub walked: f32,
+    // From 0 when standing still to 1 when walking at full speed
+    pub swing: f32,
+}
+
+// Position received at the time of the player's clock
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Snapshot {
+    time: f32,
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl RemotePlayer {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            position: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            pose: PlayerPose::default(),
+            sequence: None,
+            snapshots: VecDeque::new(),
+            clock: 0.0,
+        }
+    }
+    // Only players that sent a position yet are drawn
+    pub fn has_position(&self) -> bool {
+        self.sequence.is_some()
+    }
+    fn receive(&mut self, position: Vec3, yaw: f32, pitch: f32) {
+        if self.snapshots.is_empty() {
+            self.pose = PlayerPose {
+                position,
+                yaw,
+                pitch,
+                body_yaw: yaw,
+                ..Default::default()
+            };
+        }
+        (self.position, self.yaw, self.pitch) = (position, yaw, pitch);
+        self.snapshots.push_back(Snapshot {
+            time: self.clock,
+            position,
+            yaw,
+            pitch,
+        });
+    }
+    // Moves the pose towards the positions received
+    pub fn advance(&mut self, delta_time: f32) {
+        self.clock += delta_time;
+        let time = self.clock - INTERPOLATION_DELAY;
+        while self.snapshots.len() > 1 && self.snapshots[1].time <= time {
+            self.snapshots.pop_front();
+        }
+        let Some(from) = self.snapshots.front().copied() else {
+            return;
+        };
+        let to = self.snapshots.get(1).copied().unwrap_or(from);
+        let t = if to.time > from.time {
+            ((time - from.time) / (to.time - from.time)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        let previous = self.pose;
+        let position = from.position.lerp(to.position, t);
+        let yaw = lerp_angle(from.yaw, to.yaw, t);
+        let moved = position - previous.position;
+        let walked = glam::vec2(moved.x, moved.z).length();
+        let speed = if delta_time > 0.0 {
+            walked / delta_time
+        } else {
+            0.0
+        };
+        let target_swing = (speed / FULL_SWING_SPEED).min(1.0);
+        let response = (SWING_RESPONSE * delta_time).min(1.0);
+
+        // The body turns with the head once it looks too far to the side, and faces forward while walking
+        let mut body_yaw = previous.body_yaw;
+        let turn = angle_difference(body_yaw, yaw);
+        if turn.abs() > MAX_HEAD_TURN {
+            body_yaw += turn - MAX_HEAD_TURN.copysign(turn);
+        }
+        body_yaw += angle_difference(body_yaw, yaw)
+            * (BODY_TURN_SPEED * delta_time * target_swing).min(1.0);
+
+        self.pose = PlayerPose {
+            position,
+            yaw,
+            pitch: from.pitch + (to.pitch - from.pitch) * t,
+            body_yaw,
+            walked: previous.walked + walked,
+            swing: previous.swing + (target_swing - previous.swing) * response,
+        };
+    }
+}
+
+// Signed turn from one angle to another, the short way around
+fn angle_difference(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+fn lerp_angle(from: f32, to: f32, t: f32) -> f32 {
+    from + angle_difference(from, to) * t
+}
+
+// Connection of a client to a server
+pub struct Connection {
+    stream: MessageStream,
+    udp: UdpSocket,
+    pub player_id: u32,
+    session: u32,
+    sequence: u32,
+    // Chunks requested and not received yet
+    pub pending_chunks: HashSet<(i32, i32)>,
+    // Blocks placed that the server didn't answer yet, and the ones it refused
+    pending_placements: HashMap<IVec3, BlockType>,
+    refused_placements: Vec<BlockType>,
+    // Same for the blocks broken, and the ones it let break
+    pending_breaks: HashMap<IVec3, BlockType>,
+    broken_blocks: Vec<BlockType>,
+    // Received during the handshake
+    received: Vec<ServerMessage>,
+}
+
+impl Connection {
+    // Joins the server at "host" or "host:port", blocking until it accepts or rejects the player
+    pub fn connect(
+        address: &str,
+        name: &str,
+        render_distance: u32,
+    ) -> Result<(Self, Welcome), Box<dyn Error>> {
+        let address = resolve(address)?;
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        let mut stream = MessageStream::new(stream)?;
+        stream.send(
+            &ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: name.to_string(),
+                render_distance,
+            }
+            .encode(),
+        );
+
+        let start = Instant::now();
+        let mut messages = vec![];
+        while messages.is_empty() {
+            if start.elapsed() > CONNECT_TIMEOUT {
+                return Err("The server didn't answer".into());
+            }
+            std::thread::sleep(Duration::from_millis(10));
+            stream.flush()?;
+            for frame in stream.receive()? {
+                messages.push(ServerMessage::decode(&frame)?);
+            }
+        }
+        // The messages sent along with the welcome, like the players already there, are kept for the first poll
+        let (player_id, session, welcome) = match messages.remove(0) {
+            ServerMessage::Welcome {
+                player_id,
+                session,
+                spawn,
+                time,
+                game_mode,
+            } => (
+                player_id,
+                session,
+                Welcome {
+                    spawn,
+                    time,
+                    game_mode,
+                },
+            ),
+            ServerMessage::Rejected(reason) => return Err(reason.into()),
+            message => return Err(format!("Unexpected message before joining: {message:?}").into()),
+        };
+
+        let local = if address.is_ipv4() {
+            SocketAddr::from(([0, 0, 0, 0], 0))
+        } else {
+            SocketAddr::from(([0; 16], 0))
+        };
+        let udp = UdpSocket::bind(local)?;
+        udp.connect(address)?;
+        udp.set_nonblocking(true)?;
+
+        let connection = Self {
+            stream,
+            udp,
+            player_id,
+            session,
+            sequence: 0,
+            pending_chunks: HashSet::new(),
+            pending_placements: HashMap::new(),
+            refused_placements: vec![],
+            pending_breaks: HashMap::new(),
+            broken_blocks: vec![],
+            received: messages,
+        };
+        Ok((connection, welcome))
+    }
+    pub fn send(&mut self, message: &ClientMessage) {
+        self.stream.send(&message.encode());
+        // A failed write shows up as an error of the next poll
+        let _ = self.stream.flush();
+    }
+    // Asks the server to place the block, its item is given back by `take_refused_placements` if it refuses
+    pub fn place_block(&mut self, position: IVec3, block_type: BlockType) {
+        self.pending_placements.insert(position, block_type);
+        self.send(&ClientMessage::SetBlock {
+            position,
+            block_type: Some(block_type),
+        });
+    }
+    pub fn take_refused_placements(&mut self) -> Vec<BlockType> {
+        std::mem::take(&mut self.refused_placements)
+    }
+    // Asks the server to break the block, `take_broken_blocks` returns it once the server did
+    pub fn break_block(&mut self, position: IVec3, block_type: BlockType) {
+        self.pending_breaks.insert(position, block_type);
+        self.send(&ClientMessage::SetBlock {
+            position,
+            block_type: None,
+        });
+    }
+    pub fn take_broken_blocks(&mut self) -> Vec<BlockType> {
+        std::mem::take(&mut self.broken_blocks)
+    }
+    pub fn request_chunk(&mut self, x: i32, y: i32) {
+        if self.pending_chunks.insert((x, y)) {
+            self.send(&ClientMessage::RequestChunk(x, y));
+        }
+    }
+    pub fn send_position(&mut self, position: Vec3, yaw: f32, pitch: f32) {
+        self.sequence = self.sequence.wrapping_add(1);
+        let datagram = Datagram::ClientPosition {
+            player_id: self.player_id,
+            session: self.session,
+            sequence: self.sequence,
+            position,
+            yaw,
+            pitch,
+        };
+        // Lost datagrams are fine, a newer position follows
+        let _ = self.udp.send(&datagram.encode());
+    }
+    // Writes the queued messages and returns the ones received, fails once the connection is lost
+    pub fn poll(&mut self) -> Result<Vec<ServerMessage>, Box<dyn Error>> {
+        self.stream.flush()?;
+        let mut messages = std::mem::take(&mut self.received);
+        for frame in self.stream.receive()? {
+            let message = ServerMessage::decode(&frame)?;
+            match message {
+                ServerMessage::ChunkData { x, y, .. } => {
+                    self.pending_chunks.remove(&(x, y));
+                }
+                // The server answers every edit with the block it ended up with
+                ServerMessage::BlockChanged {
+                    position,
+                    block_type,
+                } => {
+                    if let Some(placed) = self.pending_placements.remove(&position) {
+                        if block_type != Some(placed) {
+                            self.refused_placements.push(placed);
+                        }
+                    }
+                    if let Some(broken) = self.pending_breaks.remove(&position) {
+                        if block_type != Some(broken) {
+                            self.broken_blocks.push(broken);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+    // Updates the other players from the position datagrams received
+    pub fn poll_positions(&mut self, players: &mut HashMap<u32, RemotePlayer>) {
+        let mut buffer = [0; 512];
+        loop {
+            let size = match self.udp.recv(&mut buffer) {
+                Ok(size) => size,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => continue,
+            };
+            let Ok(Datagram::PlayerPosition {
+                player_id,
+                sequence,
+                position,
+                yaw,
+                pitch,
+            }) = Datagram::decode(&buffer[..size])
+            else {
+                continue;
+            };
+            let Some(player) = players.get_mut(&player_id) else {
+                continue;
+            };
+            if !is_newer_sequence(sequence, player.sequence) {
+                continue;
+            }
+            player.sequence = Some(sequence);
+            player.receive(position, yaw, pitch);
+        }
+    }
+}
+
+// Uses the default port when the address has none
+fn resolve(address: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    let address = address.trim();
+    let resolved = match address.to_socket_addrs() {
+        Ok(mut addresses) => addresses.next(),
+        Err(_) => (address, DEFAULT_PORT).to_socket_addrs()?.next(),
+    };
+    resolved.ok_or_else(|| format!("Cannot resolve {address}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn poses_are_interpolated_between_the_positions_received() {
+        let mut player = RemotePlayer::new("Alice".into());
+        player.receive(Vec3::ZERO, 3.0, 0.0);
+        player.advance(0.05);
+        player.receive(Vec3::new(2.0, 0.0, 0.0), -3.0, 0.5);
+        assert_eq!(player.pose.position, Vec3::ZERO);
+
+        // Halfway between the two positions, the yaw turns across the wrap instead of all the way around
+        player.advance(0.075);
+        assert!((player.pose.position.x - 1.0).abs() < 1e-4);
+        assert!((player.pose.pitch - 0.25).abs() < 1e-4);
+        assert!(angle_difference(player.pose.yaw, PI).abs() < 1e-4);
+        assert!(player.pose.swing > 0.0);
+
+        player.advance(1.0);
+        assert_eq!(player.pose.position, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(player.snapshots.len(), 1);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod client;
+pub mod protocol;
+pub mod server;
+pub mod stream;
+
+pub const DEFAULT_PORT: u16 = 25565;
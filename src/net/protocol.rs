@@ -0,0 +1,580 @@
+use glam::{IVec3, Vec3};
+
+use crate::blocks::block_type::BlockType;
+use crate::chunk::BlockEntry;
+use crate::player::GameMode;
+use crate::world::CHUNK_SIZE;
+
+// Bumped whenever a message changes, clients of another version are rejected
+pub const PROTOCOL_VERSION: u32 = 2;
+pub const MAX_NAME_LENGTH: usize = 16;
+pub const MAX_CHAT_LENGTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtocolError {
+    UnexpectedEnd,
+    UnknownMessage(u8),
+    InvalidBlock(u8),
+    InvalidBlockPosition([u8; 3]),
+    InvalidGameMode(u8),
+    InvalidString,
+    TrailingBytes(usize),
+}
+
+impl std::fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolError::UnexpectedEnd => write!(f, "the message ended early"),
+            ProtocolError::UnknownMessage(kind) => write!(f, "unknown message type {kind}"),
+            ProtocolError::InvalidBlock(id) => write!(f, "invalid block id {id}"),
+            ProtocolError::InvalidBlockPosition(position) => {
+                write!(f, "block position {position:?} is outside the chunk")
+            }
+            ProtocolError::InvalidGameMode(id) => write!(f, "invalid game mode {id}"),
+            ProtocolError::InvalidString => write!(f, "invalid utf-8 string"),
+            ProtocolError::TrailingBytes(count) => {
+                write!(f, "{count} unread bytes after the message")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+// Sent by clients over TCP
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    // First message of a connection
+    Hello {
+        version: u32,
+        name: String,
+        render_distance: u32,
+    },
+    RequestChunk(i32, i32),
+    // Asks the server to place a block, or to remove it when None
+    SetBlock {
+        position: IVec3,
+        block_type: Option<BlockType>,
+    },
+    Chat(String),
+    // The player started mining the block, in survival the server only lets it break once it's mined
+    StartMining(IVec3),
+}
+
+// Sent by the server over TCP
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    // Answers a valid Hello, the session authenticates the client's datagrams. The spawn is in collision space.
+    Welcome {
+        player_id: u32,
+        session: u32,
+        spawn: Vec3,
+        time: f32,
+        // Chosen by the server, players can't change it
+        game_mode: GameMode,
+    },
+    // The connection is closed right after
+    Rejected(String),
+    ChunkData {
+        x: i32,
+        y: i32,
+        blocks: Vec<BlockEntry>,
+    },
+    BlockChanged {
+        position: IVec3,
+        block_type: Option<BlockType>,
+    },
+    Chat(String),
+    PlayerJoined {
+        player_id: u32,
+        name: String,
+    },
+    PlayerLeft {
+        player_id: u32,
+    },
+    Time(f32),
+}
+
+// Sent over UDP, positions are eye positions in collision space. Older sequences than the last received are dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Datagram {
+    ClientPosition {
+        player_id: u32,
+        session: u32,
+        sequence: u32,
+        position: Vec3,
+        yaw: f32,
+        pitch: f32,
+    },
+    PlayerPosition {
+        player_id: u32,
+        sequence: u32,
+        position: Vec3,
+        yaw: f32,
+        pitch: f32,
+    },
+}
+
+// Whether the sequence number comes after the last one, wrapping around
+pub fn is_newer_sequence(sequence: u32, last: Option<u32>) -> bool {
+    last.is_none_or(|last| (sequence.wrapping_sub(last) as i32) > 0)
+}
+
+// Little endian encoding of the message fields
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    fn i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    fn vec3(&mut self, value: Vec3) {
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.z);
+    }
+    fn ivec3(&mut self, value: IVec3) {
+        self.i32(value.x);
+        self.i32(value.y);
+        self.i32(value.z);
+    }
+    fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+    fn block_type(&mut self, value: BlockType) {
+        self.u8(value.to_id() as u8);
+    }
+    // 0 is air, the block ids are shifted by one
+    fn optional_block_type(&mut self, value: Option<BlockType>) {
+        self.u8(value.map_or(0, |block_type| block_type.to_id() as u8 + 1));
+    }
+    fn game_mode(&mut self, value: GameMode) {
+        self.u8(match value {
+            GameMode::Survival => 0,
+            GameMode::Creative => 1,
+        });
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ProtocolError> {
+        if self.bytes.len() < count {
+            return Err(ProtocolError::UnexpectedEnd);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ProtocolError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+    fn u8(&mut self) -> Result<u8, ProtocolError> {
+        Ok(self.take(1)?[0])
+    }
+    fn u32(&mut self) -> Result<u32, ProtocolError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+    fn i32(&mut self) -> Result<i32, ProtocolError> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+    fn f32(&mut self) -> Result<f32, ProtocolError> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+    fn vec3(&mut self) -> Result<Vec3, ProtocolError> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+    fn ivec3(&mut self) -> Result<IVec3, ProtocolError> {
+        Ok(IVec3::new(self.i32()?, self.i32()?, self.i32()?))
+    }
+    fn string(&mut self) -> Result<String, ProtocolError> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| ProtocolError::InvalidString)
+    }
+    fn block_type(&mut self) -> Result<BlockType, ProtocolError> {
+        let id = self.u8()?;
+        if id as u32 > BlockType::MAX_ID {
+            return Err(ProtocolError::InvalidBlock(id));
+        }
+        Ok(BlockType::from_id(id as u32))
+    }
+    fn optional_block_type(&mut self) -> Result<Option<BlockType>, ProtocolError> {
+        match self.u8()? {
+            0 => Ok(None),
+            id if id as u32 - 1 <= BlockType::MAX_ID => Ok(Some(BlockType::from_id(id as u32 - 1))),
+            id => Err(ProtocolError::InvalidBlock(id)),
+        }
+    }
+    fn game_mode(&mut self) -> Result<GameMode, ProtocolError> {
+        match self.u8()? {
+            0 => Ok(GameMode::Survival),
+            1 => Ok(GameMode::Creative),
+            id => Err(ProtocolError::InvalidGameMode(id)),
+        }
+    }
+    // Fails if the message has bytes left over
+    fn finish<T>(self, value: T) -> Result<T, ProtocolError> {
+        if !self.bytes.is_empty() {
+            return Err(ProtocolError::TrailingBytes(self.bytes.len()));
+        }
+        Ok(value)
+    }
+}
+
+impl ClientMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        match self {
+            ClientMessage::Hello {
+                version,
+                name,
+                render_distance,
+            } => {
+                w.u8(0);
+                w.u32(*version);
+                w.string(name);
+                w.u32(*render_distance);
+            }
+            ClientMessage::RequestChunk(x, y) => {
+                w.u8(1);
+                w.i32(*x);
+                w.i32(*y);
+            }
+            ClientMessage::SetBlock {
+                position,
+                block_type,
+            } => {
+                w.u8(2);
+                w.ivec3(*position);
+                w.optional_block_type(*block_type);
+            }
+            ClientMessage::Chat(text) => {
+                w.u8(3);
+                w.string(text);
+            }
+            ClientMessage::StartMining(position) => {
+                w.u8(4);
+                w.ivec3(*position);
+            }
+        }
+        w.bytes
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let mut r = Reader { bytes };
+        let message = match r.u8()? {
+            0 => ClientMessage::Hello {
+                version: r.u32()?,
+                name: r.string()?,
+                render_distance: r.u32()?,
+            },
+            1 => ClientMessage::RequestChunk(r.i32()?, r.i32()?),
+            2 => ClientMessage::SetBlock {
+                position: r.ivec3()?,
+                block_type: r.optional_block_type()?,
+            },
+            3 => ClientMessage::Chat(r.string()?),
+            4 => ClientMessage::StartMining(r.ivec3()?),
+            kind => return Err(ProtocolError::UnknownMessage(kind)),
+        };
+        r.finish(message)
+    }
+}
+
+impl ServerMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        match self {
+            ServerMessage::Welcome {
+                player_id,
+                session,
+                spawn,
+                time,
+                game_mode,
+            } => {
+                w.u8(0);
+                w.u32(*player_id);
+                w.u32(*session);
+                w.vec3(*spawn);
+                w.f32(*time);
+                w.game_mode(*game_mode);
+            }
+            ServerMessage::Rejected(reason) => {
+                w.u8(1);
+                w.string(reason);
+            }
+            ServerMessage::ChunkData { x, y, blocks } => {
+                w.u8(2);
+                w.i32(*x);
+                w.i32(*y);
+                w.u32(blocks.len() as u32);
+                for ([bx, by, bz], block_type) in blocks {
+                    w.u8(*bx);
+                    w.u8(*by);
+                    w.u8(*bz);
+                    w.block_type(*block_type);
+                }
+            }
+            ServerMessage::BlockChanged {
+                position,
+                block_type,
+            } => {
+                w.u8(3);
+                w.ivec3(*position);
+                w.optional_block_type(*block_type);
+            }
+            ServerMessage::Chat(text) => {
+                w.u8(4);
+                w.string(text);
+            }
+            ServerMessage::PlayerJoined { player_id, name } => {
+                w.u8(5);
+                w.u32(*player_id);
+                w.string(name);
+            }
+            ServerMessage::PlayerLeft { player_id } => {
+                w.u8(6);
+                w.u32(*player_id);
+            }
+            ServerMessage::Time(time) => {
+                w.u8(7);
+                w.f32(*time);
+            }
+        }
+        w.bytes
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let mut r = Reader { bytes };
+        let message = match r.u8()? {
+            0 => ServerMessage::Welcome {
+                player_id: r.u32()?,
+                session: r.u32()?,
+                spawn: r.vec3()?,
+                time: r.f32()?,
+                game_mode: r.game_mode()?,
+            },
+            1 => ServerMessage::Rejected(r.string()?),
+            2 => {
+                let x = r.i32()?;
+                let y = r.i32()?;
+                let count = r.u32()? as usize;
+                // Every block takes 4 bytes, checked first so that a bogus count can't allocate much
+                if r.bytes.len() < count * 4 {
+                    return Err(ProtocolError::UnexpectedEnd);
+                }
+                let mut blocks = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let position: [u8; 3] = r.array()?;
+                    // Used as indices of the chunk's columns
+                    if position[0] as u32 >= CHUNK_SIZE || position[2] as u32 >= CHUNK_SIZE {
+                        return Err(ProtocolError::InvalidBlockPosition(position));
+                    }
+                    blocks.push((position, r.block_type()?));
+                }
+                ServerMessage::ChunkData { x, y, blocks }
+            }
+            3 => ServerMessage::BlockChanged {
+                position: r.ivec3()?,
+                block_type: r.optional_block_type()?,
+            },
+            4 => ServerMessage::Chat(r.string()?),
+            5 => ServerMessage::PlayerJoined {
+                player_id: r.u32()?,
+                name: r.string()?,
+            },
+            6 => ServerMessage::PlayerLeft {
+                player_id: r.u32()?,
+            },
+            7 => ServerMessage::Time(r.f32()?),
+            kind => return Err(ProtocolError::UnknownMessage(kind)),
+        };
+        r.finish(message)
+    }
+}
+
+impl Datagram {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        match self {
+            Datagram::ClientPosition {
+                player_id,
+                session,
+                sequence,
+                position,
+                yaw,
+                pitch,
+            } => {
+                w.u8(0);
+                w.u32(*player_id);
+                w.u32(*session);
+                w.u32(*sequence);
+                w.vec3(*position);
+                w.f32(*yaw);
+                w.f32(*pitch);
+            }
+            Datagram::PlayerPosition {
+                player_id,
+                sequence,
+                position,
+                yaw,
+                pitch,
+            } => {
+                w.u8(1);
+                w.u32(*player_id);
+                w.u32(*sequence);
+                w.vec3(*position);
+                w.f32(*yaw);
+                w.f32(*pitch);
+            }
+        }
+        w.bytes
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Self, ProtocolError> {
+        let mut r = Reader { bytes };
+        let datagram = match r.u8()? {
+            0 => Datagram::ClientPosition {
+                player_id: r.u32()?,
+                session: r.u32()?,
+                sequence: r.u32()?,
+                position: r.vec3()?,
+                yaw: r.f32()?,
+                pitch: r.f32()?,
+            },
+            1 => Datagram::PlayerPosition {
+                player_id: r.u32()?,
+                sequence: r.u32()?,
+                position: r.vec3()?,
+                yaw: r.f32()?,
+                pitch: r.f32()?,
+            },
+            kind => return Err(ProtocolError::UnknownMessage(kind)),
+        };
+        r.finish(datagram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_round_trip() {
+        let client_messages = [
+            ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: "Steve".to_string(),
+                render_distance: 8,
+            },
+            ClientMessage::RequestChunk(-3, 7),
+            ClientMessage::SetBlock {
+                position: IVec3::new(-1, 20, 300),
+                block_type: Some(BlockType::Sand),
+            },
+            ClientMessage::SetBlock {
+                position: IVec3::ZERO,
+                block_type: None,
+            },
+            ClientMessage::Chat("héllo".to_string()),
+            ClientMessage::StartMining(IVec3::new(5, 6, -7)),
+        ];
+        for message in client_messages {
+            assert_eq!(ClientMessage::decode(&message.encode()), Ok(message));
+        }
+
+        let server_messages = [
+            ServerMessage::Welcome {
+                player_id: 4,
+                session: 0xdead_beef,
+                spawn: Vec3::new(1.5, 60.0, -2.0),
+                time: 13.25,
+                game_mode: GameMode::Creative,
+            },
+            ServerMessage::ChunkData {
+                x: 2,
+                y: -5,
+                blocks: vec![
+                    ([0, 0, 0], BlockType::Stone),
+                    ([15, 254, 15], BlockType::Leaf),
+                ],
+            },
+            ServerMessage::BlockChanged {
+                position: IVec3::new(5, 6, 7),
+                block_type: Some(BlockType::Grass),
+            },
+            ServerMessage::PlayerJoined {
+                player_id: 2,
+                name: "Alex".to_string(),
+            },
+            ServerMessage::PlayerLeft { player_id: 2 },
+            ServerMessage::Time(6.0),
+        ];
+        for message in server_messages {
+            assert_eq!(ServerMessage::decode(&message.encode()), Ok(message));
+        }
+
+        let datagram = Datagram::PlayerPosition {
+            player_id: 1,
+            sequence: 99,
+            position: Vec3::new(0.5, 40.0, 0.5),
+            yaw: 1.0,
+            pitch: -0.5,
+        };
+        assert_eq!(Datagram::decode(&datagram.encode()), Ok(datagram));
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        let mut bytes = ClientMessage::RequestChunk(1, 2).encode();
+        assert_eq!(
+            ClientMessage::decode(&bytes[..5]),
+            Err(ProtocolError::UnexpectedEnd)
+        );
+        bytes.push(0);
+        assert_eq!(
+            ClientMessage::decode(&bytes),
+            Err(ProtocolError::TrailingBytes(1))
+        );
+        assert_eq!(
+            ServerMessage::decode(&[3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 200]),
+            Err(ProtocolError::InvalidBlock(200))
+        );
+        assert_eq!(
+            ServerMessage::decode(&[42]),
+            Err(ProtocolError::UnknownMessage(42))
+        );
+        // Blocks outside the chunk's columns
+        assert_eq!(
+            ServerMessage::decode(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 16, 0, 5, 0]),
+            Err(ProtocolError::InvalidBlockPosition([16, 0, 5]))
+        );
+        // A huge block count doesn't allocate before failing
+        assert_eq!(
+            ServerMessage::decode(&[2, 0, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255, 255]),
+            Err(ProtocolError::UnexpectedEnd)
+        );
+    }
+
+    #[test]
+    fn sequences_wrap_around() {
+        assert!(is_newer_sequence(0, None));
+        assert!(is_newer_sequence(5, Some(4)));
+        assert!(!is_newer_sequence(4, Some(4)));
+        assert!(!is_newer_sequence(3, Some(4)));
+        assert!(is_newer_sequence(1, Some(u32::MAX)));
+    }
+}
@@ -0,0 +1,852 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io;
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use glam::{IVec3, Vec3};
+
+use super::protocol::{
+    is_newer_sequence, ClientMessage, Datagram, ServerMessage, MAX_CHAT_LENGTH, MAX_NAME_LENGTH,
+    PROTOCOL_VERSION,
+};
+use super::stream::MessageStream;
+use super::DEFAULT_PORT;
+use crate::blocks::block::Block;
+use crate::blocks::block_type::BlockType;
+use crate::entity::mob::NearbyPlayer;
+use crate::headless::TICKS_PER_SECOND;
+use crate::inventory::Inventory;
+use crate::persistence;
+use crate::player::{
+    player_collision, GameMode, BLOCK_COLLISION_OFFSET, DEFAULT_SPAWN_POINT, PLAYER_REACH,
+};
+use crate::settings::RENDER_DISTANCE_RANGE;
+use crate::utils::{ChunkFromPosition, RelativeFromAbsolute};
+use crate::world::World;
+
+// Allows edits a bit further than the reach as the server only knows where the player was a moment ago
+const REACH_TOLERANCE: f32 = 2.0;
+// Seconds a survival player may break a block early, as the messages don't arrive evenly
+const MINING_TOLERANCE: f32 = 0.25;
+// Chunks generated or loaded per tick at most, the rest of the requests wait for the next ticks
+const MAX_CHUNKS_PER_TICK: usize = 32;
+const TIME_SYNC_TICKS: u64 = 20 * 10;
+const UNLOAD_TICKS: u64 = 20 * 5;
+const AUTOSAVE_TICKS: u64 = 20 * 60;
+
+pub const USAGE: &str =
+    "Usage: rustycraft --server --world <name> [--port <port>] [--game-mode <survival|creative>]";
+
+// Command line options of the dedicated server
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerOptions {
+    pub world: String,
+    pub port: u16,
+    pub game_mode: GameMode,
+}
+
+impl ServerOptions {
+    // Parses the arguments following the program name, "--server" included
+    pub fn parse(args: &[String]) -> Result<Self, Box<dyn Error>> {
+        let mut world = None;
+        let mut port = DEFAULT_PORT;
+        let mut game_mode = GameMode::Survival;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("Missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--server" => {}
+                "--world" => world = Some(value()?.clone()),
+                "--port" => port = value()?.parse()?,
+                "--game-mode" => {
+                    let name = value()?;
+                    game_mode =
+                        GameMode::from_name(name).ok_or(format!("Unknown game mode {name}"))?;
+                }
+                arg => return Err(format!("Unknown argument {arg}").into()),
+            }
+        }
+        let world = world.ok_or("Missing --world")?;
+        if !persistence::is_valid_world_name(&world) {
+            return Err(format!("Invalid world name {world}").into());
+        }
+        Ok(Self {
+            world,
+            port,
+            game_mode,
+        })
+    }
+}
+
+// A connection, it only becomes a player once its Hello is accepted
+struct Client {
+    stream: MessageStream,
+    // None until the handshake is done
+    name: Option<String>,
+    session: u32,
+    render_distance: u32,
+    // Where the position datagrams come from, the other players' positions are sent there
+    udp_address: Option<SocketAddr>,
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    sequence: Option<u32>,
+    // A new position arrived since the last broadcast
+    moved: bool,
+    requested_chunks: Vec<(i32, i32)>,
+    // Mirrors the player's inventory, survival players only place the blocks they have
+    inventory: Inventory,
+    // Block being mined and the server's clock when it started
+    mining: Option<(IVec3, f32)>,
+    // Closed once the queued messages are written
+    closing: bool,
+    closed: bool,
+}
+
+impl Client {
+    fn send(&mut self, message: &ServerMessage) {
+        self.stream.send(&message.encode());
+    }
+    // Sends the reason then closes the connection
+    fn reject(&mut self, reason: &str) {
+        self.send(&ServerMessage::Rejected(reason.to_string()));
+        self.closing = true;
+    }
+    fn is_in_range(&self, chunk: (i32, i32)) -> bool {
+        let (x, y) = self.position.get_chunk_from_position_absolute();
+        let range = self.render_distance as i32 + 1;
+        (chunk.0 - x).abs() <= range && (chunk.1 - y).abs() <= range
+    }
+}
+
+// Authoritative owner of the world, players only ask it for changes
+pub struct Server {
+    pub world: World,
+    listener: TcpListener,
+    udp: UdpSocket,
+    clients: HashMap<u32, Client>,
+    next_player_id: u32,
+    ticks: u64,
+    // Seconds since the server started
+    clock: f32,
+    // Of every player, they can't change it
+    pub game_mode: GameMode,
+}
+
+impl Server {
+    // Listens for TCP connections and position datagrams on the same address
+    pub fn bind(world: World, address: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        let udp = UdpSocket::bind(listener.local_addr()?)?;
+        udp.set_nonblocking(true)?;
+        Ok(Self {
+            world,
+            listener,
+            udp,
+            clients: HashMap::new(),
+            next_player_id: 1,
+            ticks: 0,
+            clock: 0.0,
+            game_mode: GameMode::Survival,
+        })
+    }
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+    pub fn player_count(&self) -> usize {
+        self.players().count()
+    }
+    fn players(&self) -> impl Iterator<Item = (&u32, &Client)> {
+        self.clients
+            .iter()
+            .filter(|(_, client)| client.name.is_some() && !client.closing)
+    }
+    // Sends the message to every player
+    fn broadcast(&mut self, message: &ServerMessage) {
+        let frame = message.encode();
+        for client in self.clients.values_mut() {
+            if client.name.is_some() && !client.closing {
+                client.stream.send(&frame);
+            }
+        }
+    }
+    pub fn tick(&mut self, delta_time: f32) {
+        self.accept_clients();
+        self.receive_messages();
+        self.receive_positions();
+        self.send_chunks();
+
+        // Held blocks and sprinting aren't sent to the server, so the mobs only wander around the players
+        let players: Vec<NearbyPlayer> = self
+            .players()
+            .map(|(_, client)| NearbyPlayer::from_eye(client.position, None, false))
+            .collect();
+        self.world.tick(delta_time, &players);
+        self.ticks += 1;
+        self.clock += delta_time;
+        if self.ticks.is_multiple_of(TIME_SYNC_TICKS) {
+            self.broadcast(&ServerMessage::Time(self.world.time));
+        }
+        if self.ticks.is_multiple_of(UNLOAD_TICKS) {
+            self.unload_unused_chunks();
+        }
+        if self.ticks.is_multiple_of(AUTOSAVE_TICKS) {
+            self.world.save_state();
+        }
+
+        self.send_positions();
+        self.flush_clients();
+    }
+    fn accept_clients(&mut self) {
+        loop {
+            let stream = match self.listener.accept() {
+                Ok((stream, _)) => stream,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    eprintln!("Failed to accept a connection: {err}");
+                    break;
+                }
+            };
+            let Ok(stream) = MessageStream::new(stream) else {
+                continue;
+            };
+            let player_id = self.next_player_id;
+            self.next_player_id += 1;
+            self.clients.insert(
+                player_id,
+                Client {
+                    stream,
+                    name: None,
+                    session: rand::random(),
+                    render_distance: RENDER_DISTANCE_RANGE.0,
+                    udp_address: None,
+                    position: DEFAULT_SPAWN_POINT + BLOCK_COLLISION_OFFSET,
+                    yaw: 0.0,
+                    pitch: 0.0,
+                    sequence: None,
+                    moved: false,
+                    requested_chunks: vec![],
+                    inventory: Inventory::starter(),
+                    mining: None,
+                    closing: false,
+                    closed: false,
+                },
+            );
+        }
+    }
+    fn receive_messages(&mut self) {
+        let mut messages = vec![];
+        for (player_id, client) in self.clients.iter_mut() {
+            if client.closing {
+                continue;
+            }
+            let frames = match client.stream.receive() {
+                Ok(frames) => frames,
+                Err(_) => {
+                    client.closed = true;
+                    continue;
+                }
+            };
+            for frame in frames {
+                match ClientMessage::decode(&frame) {
+                    Ok(message) => messages.push((*player_id, message)),
+                    Err(err) => {
+                        client.reject(&format!("Invalid message: {err}"));
+                        break;
+                    }
+                }
+            }
+        }
+        for (player_id, message) in messages {
+            self.handle_message(player_id, message);
+        }
+    }
+    fn handle_message(&mut self, player_id: u32, message: ClientMessage) {
+        let Some(client) = self.clients.get_mut(&player_id) else {
+            return;
+        };
+        if client.closing {
+            return;
+        }
+        let Some(name) = client.name.clone() else {
+            match message {
+                ClientMessage::Hello {
+                    version,
+                    name,
+                    render_distance,
+                } => self.join(player_id, version, name, render_distance),
+                _ => client.reject("Expected a hello message"),
+            }
+            return;
+        };
+        match message {
+            ClientMessage::Hello { .. } => client.reject("Already joined"),
+            ClientMessage::RequestChunk(x, y) => {
+                if client.is_in_range((x, y)) && !client.requested_chunks.contains(&(x, y)) {
+                    client.requested_chunks.push((x, y));
+                }
+            }
+            ClientMessage::SetBlock {
+                position,
+                block_type,
+            } => self.set_block(player_id, position, block_type),
+            ClientMessage::StartMining(position) => client.mining = Some((position, self.clock)),
+            ClientMessage::Chat(text) => {
+                let text = text
+                    .trim()
+                    .chars()
+                    .take(MAX_CHAT_LENGTH)
+                    .collect::<String>();
+                if !text.is_empty() {
+                    println!("<{name}> {text}");
+                    self.broadcast(&ServerMessage::Chat(format!("<{name}> {text}")));
+                }
+            }
+        }
+    }
+    fn join(&mut self, player_id: u32, version: u32, name: String, render_distance: u32) {
+        let name = name.trim().to_string();
+        let name_taken = self
+            .players()
+            .any(|(_, client)| client.name.as_deref() == Some(name.as_str()));
+        let client = self.clients.get_mut(&player_id).unwrap();
+        if version != PROTOCOL_VERSION {
+            client.reject(&format!(
+                "The server uses protocol version {PROTOCOL_VERSION}, not {version}"
+            ));
+            return;
+        }
+        if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+            client.reject(&format!(
+                "Names must have between 1 and {MAX_NAME_LENGTH} characters"
+            ));
+            return;
+        }
+        if name_taken {
+            client.reject(&format!("{name} is already playing"));
+            return;
+        }
+
+        let (min, max) = RENDER_DISTANCE_RANGE;
+        client.render_distance = render_distance.clamp(min, max);
+        client.send(&ServerMessage::Welcome {
+            player_id,
+            session: client.session,
+            spawn: DEFAULT_SPAWN_POINT + BLOCK_COLLISION_OFFSET,
+            time: self.world.time,
+            game_mode: self.game_mode,
+        });
+        let others = self
+            .players()
+            .map(|(id, other)| ServerMessage::PlayerJoined {
+                player_id: *id,
+                name: other.name.clone().unwrap(),
+            })
+            .collect::<Vec<_>>();
+        let client = self.clients.get_mut(&player_id).unwrap();
+        for message in others.iter() {
+            client.send(message);
+        }
+
+        self.broadcast(&ServerMessage::PlayerJoined {
+            player_id,
+            name: name.clone(),
+        });
+        self.clients.get_mut(&player_id).unwrap().name = Some(name.clone());
+        println!("{name} joined the game");
+        self.broadcast(&ServerMessage::Chat(format!("{name} joined the game")));
+    }
+    // Whether a survival player has the block to place, or mined the block long enough to break it
+    fn survival_allows(
+        &self,
+        client: &Client,
+        position: IVec3,
+        block_type: Option<BlockType>,
+    ) -> bool {
+        match block_type {
+            Some(block_type) => client.inventory.count(block_type) > 0,
+            None => {
+                let Some(mined) = self.world.get_block_type_absolute(&position.as_vec3()) else {
+                    return false;
+                };
+                client.mining.is_some_and(|(mining, start)| {
+                    mining == position
+                        && self.clock - start >= mined.mining_time() - MINING_TOLERANCE
+                })
+            }
+        }
+    }
+    // Applies the edit if the player could have made it, the requester is told the actual block either way
+    fn set_block(&mut self, player_id: u32, position: IVec3, block_type: Option<BlockType>) {
+        let block_position = position.as_vec3();
+        let client = &self.clients[&player_id];
+        let in_reach = client.position.distance(block_position + Vec3::splat(0.5))
+            <= PLAYER_REACH + REACH_TOLERANCE;
+        let survival = self.game_mode == GameMode::Survival;
+        let allowed = in_reach && (!survival || self.survival_allows(client, position, block_type));
+        let mined = self.world.get_block_type_absolute(&block_position);
+
+        let applied = allowed
+            && World::is_inside_height(&block_position)
+            && match block_type {
+                Some(block_type) => {
+                    let obstacles = self
+                        .players()
+                        .map(|(_, client)| player_collision(client.position))
+                        .collect::<Vec<_>>();
+                    let block = Block::new(
+                        block_position.relative_from_absolute(),
+                        block_position.get_chunk_from_position_absolute(),
+                        block_type,
+                    );
+                    self.world
+                        .place_block(Arc::new(RwLock::new(block)), &obstacles)
+                        .is_ok()
+                }
+                None => match self.world.get_blocks_absolute(&block_position) {
+                    Some(block) => self.world.remove_block(block).is_ok(),
+                    None => false,
+                },
+            };
+
+        if applied && survival {
+            let client = self.clients.get_mut(&player_id).unwrap();
+            match (block_type, mined) {
+                (Some(placed), _) => {
+                    client.inventory.take(placed);
+                }
+                // The player picks up the dropped item
+                (None, Some(mined)) => {
+                    client.mining = None;
+                    client.inventory.add(mined, 1);
+                }
+                (None, None) => {}
+            }
+        }
+        // Removing a block can fill it with water, so the result is sent rather than the request
+        let message = ServerMessage::BlockChanged {
+            position,
+            block_type: self.world.get_block_type_absolute(&block_position),
+        };
+        if applied {
+            self.broadcast(&message);
+        } else if let Some(client) = self.clients.get_mut(&player_id) {
+            client.send(&message);
+        }
+    }
+    fn receive_positions(&mut self) {
+        let mut buffer = [0; 512];
+        loop {
+            let (size, address) = match self.udp.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                // Errors of a datagram, like an ICMP unreachable from a client that left, don't stop the others
+                Err(_) => continue,
+            };
+            let Ok(Datagram::ClientPosition {
+                player_id,
+                session,
+                sequence,
+                position,
+                yaw,
+                pitch,
+            }) = Datagram::decode(&buffer[..size])
+            else {
+                continue;
+            };
+            let Some(client) = self.clients.get_mut(&player_id) else {
+                continue;
+            };
+            if client.name.is_none()
+                || client.session != session
+                || !is_newer_sequence(sequence, client.sequence)
+                || !position.is_finite()
+            {
+                continue;
+            }
+            client.udp_address = Some(address);
+            client.sequence = Some(sequence);
+            client.position = position;
+            client.yaw = yaw;
+            client.pitch = pitch;
+            client.moved = true;
+        }
+    }
+    fn send_positions(&mut self) {
+        let positions = self
+            .players()
+            .filter(|(_, client)| client.moved)
+            .map(|(player_id, client)| {
+                let datagram = Datagram::PlayerPosition {
+                    player_id: *player_id,
+                    sequence: client.sequence.unwrap(),
+                    position: client.position,
+                    yaw: client.yaw,
+                    pitch: client.pitch,
+                };
+                (*player_id, datagram.encode())
+            })
+            .collect::<Vec<_>>();
+        for (player_id, client) in self.players() {
+            let Some(address) = client.udp_address else {
+                continue;
+            };
+            for (_, datagram) in positions.iter().filter(|(id, _)| id != player_id) {
+                // Lost datagrams are fine, a newer position follows
+                let _ = self.udp.send_to(datagram, address);
+            }
+        }
+        for client in self.clients.values_mut() {
+            client.moved = false;
+        }
+    }
+    fn send_chunks(&mut self) {
+        let mut requests = vec![];
+        for (player_id, client) in self.clients.iter_mut() {
+            let count = client
+                .requested_chunks
+                .len()
+                .min(MAX_CHUNKS_PER_TICK - requests.len());
+            requests.extend(
+                client
+                    .requested_chunks
+                    .drain(..count)
+                    .map(|key| (*player_id, key)),
+            );
+        }
+        if requests.is_empty() {
+            return;
+        }
+        self.world
+            .load_chunks(requests.iter().map(|(_, key)| *key).collect());
+
+        let chunks = self.world.chunks.read().unwrap();
+        for (player_id, (x, y)) in requests {
+            let (Some(chunk), Some(client)) =
+                (chunks.get(&(x, y)), self.clients.get_mut(&player_id))
+            else {
+                continue;
+            };
+            let blocks = chunk.read().unwrap().block_entries();
+            client.send(&ServerMessage::ChunkData { x, y, blocks });
+        }
+    }
+    // Unloads the chunks out of every player's range, saving the modified ones
+    fn unload_unused_chunks(&mut self) {
+        let unused = self
+            .world
+            .chunks
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|key| !self.players().any(|(_, client)| client.is_in_range(**key)))
+            .copied()
+            .collect::<Vec<_>>();
+        self.world.unload_chunks(&unused);
+    }
+    fn flush_clients(&mut self) {
+        for client in self.clients.values_mut() {
+            match client.stream.flush() {
+                Err(err) => {
+                    let name = client.name.as_deref().unwrap_or("a client");
+                    eprintln!("Dropping {name}: {err}");
+                    client.closed = true;
+                }
+                Ok(()) if client.closing && !client.stream.has_pending_writes() => {
+                    client.closed = true;
+                }
+                Ok(()) => {}
+            }
+        }
+        let closed = self
+            .clients
+            .iter()
+            .filter(|(_, client)| client.closed)
+            .map(|(player_id, _)| *player_id)
+            .collect::<Vec<_>>();
+        for player_id in closed {
+            let client = self.clients.remove(&player_id).unwrap();
+            if let Some(name) = client.name {
+                println!("{name} left the game");
+                self.broadcast(&ServerMessage::PlayerLeft { player_id });
+                self.broadcast(&ServerMessage::Chat(format!("{name} left the game")));
+            }
+        }
+    }
+    pub fn dispose(&mut self) {
+        self.world.save_state();
+        self.world.dispose();
+    }
+}
+
+// Hosts the world until the process is killed, modified chunks are saved every minute
+pub fn run(options: ServerOptions) -> Result<(), Box<dyn Error>> {
+    persistence::set_world(&options.world);
+    persistence::create_world_dir()?;
+
+    let address = SocketAddr::from(([0, 0, 0, 0], options.port));
+    let mut server = Server::bind(World::headless(), address)?;
+    server.game_mode = options.game_mode;
+    println!(
+        "Serving world \"{}\" on {}",
+        options.world,
+        server.local_addr()?
+    );
+
+    let tick_duration = Duration::from_secs(1) / TICKS_PER_SECOND;
+    loop {
+        let tick_start = Instant::now();
+        server.tick(tick_duration.as_secs_f32());
+        if let Some(remaining) = tick_duration.checked_sub(tick_start.elapsed()) {
+            std::thread::sleep(remaining);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::client::{Connection, RemotePlayer};
+    use std::thread;
+
+    fn connect(server: &mut Server, name: &str) -> Connection {
+        let address = server.local_addr().unwrap().to_string();
+        let name = name.to_string();
+        let handle = thread::spawn(move || {
+            Connection::connect(&address, &name, 2).map_err(|err| err.to_string())
+        });
+        while !handle.is_finished() {
+            server.tick(0.05);
+            thread::sleep(Duration::from_millis(1));
+        }
+        handle.join().unwrap().unwrap().0
+    }
+
+    // Ticks the server until the client receives a matching message, the others are skipped
+    fn wait_for<F>(server: &mut Server, connection: &mut Connection, predicate: F) -> ServerMessage
+    where
+        F: Fn(&ServerMessage) -> bool,
+    {
+        for _ in 0..500 {
+            server.tick(0.05);
+            if let Some(message) = connection.poll().unwrap().into_iter().find(&predicate) {
+                return message;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("The message never arrived");
+    }
+
+    #[test]
+    fn parses_the_options() {
+        let args = |args: &str| {
+            args.split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        let options = ServerOptions::parse(&args("--server --world test --port 4000")).unwrap();
+        assert_eq!(options.world, "test");
+        assert_eq!(options.port, 4000);
+        assert_eq!(options.game_mode, GameMode::Survival);
+        assert_eq!(
+            ServerOptions::parse(&args("--server --world test --game-mode creative"))
+                .unwrap()
+                .game_mode,
+            GameMode::Creative
+        );
+        assert_eq!(
+            ServerOptions::parse(&args("--server --world test"))
+                .unwrap()
+                .port,
+            DEFAULT_PORT
+        );
+        assert!(ServerOptions::parse(&args("--server --port 4000")).is_err());
+    }
+
+    #[test]
+    fn players_share_chunks_edits_positions_and_chat() {
+        let _world = persistence::set_test_world("server-test");
+        let address = SocketAddr::from(([127, 0, 0, 1], 0));
+        let mut server = Server::bind(World::headless(), address).unwrap();
+        server.game_mode = GameMode::Creative;
+        let mut alice = connect(&mut server, "Alice");
+        let mut bob = connect(&mut server, "Bob");
+        assert_eq!(server.player_count(), 2);
+        let alice_joined = ServerMessage::PlayerJoined {
+            player_id: alice.player_id,
+            name: "Alice".to_string(),
+        };
+        assert_eq!(
+            wait_for(&mut server, &mut bob, |m| *m == alice_joined),
+            alice_joined
+        );
+        assert!(Connection::connect(&server.local_addr().unwrap().to_string(), "Bob", 2).is_err());
+
+        // Chunk of the spawn point
+        alice.request_chunk(-1, 0);
+        let ServerMessage::ChunkData { blocks, .. } =
+            wait_for(&mut server, &mut alice, |message| {
+                matches!(message, ServerMessage::ChunkData { x: -1, y: 0, .. })
+            })
+        else {
+            unreachable!()
+        };
+        assert!(alice.pending_chunks.is_empty());
+        let top = blocks
+            .iter()
+            .filter(|([x, _, z], _)| (*x, *z) == (12, 4))
+            .map(|([_, y, _], _)| *y as i32)
+            .max()
+            .unwrap();
+
+        // Alice hovers over the column, Bob goes away so that he isn't in the way
+        alice.send_position(Vec3::new(-3.5, top as f32 + 3.0, 4.5), 0.0, 0.0);
+        bob.send_position(Vec3::new(100.0, 100.0, 100.0), 1.0, 0.5);
+        let mut players = HashMap::from([(alice.player_id, RemotePlayer::new("Alice".into()))]);
+        for _ in 0..500 {
+            server.tick(0.05);
+            bob.poll_positions(&mut players);
+            if players[&alice.player_id].has_position() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(players[&alice.player_id].position.y, top as f32 + 3.0);
+
+        let position = IVec3::new(-4, top, 4);
+        alice.send(&ClientMessage::SetBlock {
+            position,
+            block_type: None,
+        });
+        let is_change = |message: &ServerMessage| matches!(message, ServerMessage::BlockChanged { position: p, .. } if *p == position);
+        let change = wait_for(&mut server, &mut bob, is_change);
+        let actual = server.world.get_block_type_absolute(&position.as_vec3());
+        assert_eq!(
+            change,
+            ServerMessage::BlockChanged {
+                position,
+                block_type: actual
+            }
+        );
+        // Water flows in when the removed block is next to some
+        assert!(actual.is_none_or(|block_type| !block_type.is_solid()));
+
+        alice.send(&ClientMessage::SetBlock {
+            position,
+            block_type: Some(BlockType::Stone),
+        });
+        let place = ServerMessage::BlockChanged {
+            position,
+            block_type: Some(BlockType::Stone),
+        };
+        assert_eq!(wait_for(&mut server, &mut bob, is_change), place);
+        assert_eq!(wait_for(&mut server, &mut alice, |m| *m == place), place);
+
+        // Out of reach, only Alice is told that nothing changed
+        let far = IVec3::new(-4, top + 40, 4);
+        alice.send(&ClientMessage::SetBlock {
+            position: far,
+            block_type: Some(BlockType::Stone),
+        });
+        assert_eq!(
+            wait_for(&mut server, &mut alice, |m| matches!(
+                m,
+                ServerMessage::BlockChanged { .. }
+            )),
+            ServerMessage::BlockChanged {
+                position: far,
+                block_type: None
+            }
+        );
+
+        bob.send(&ClientMessage::Chat("  hi  ".to_string()));
+        let chat = ServerMessage::Chat("<Bob> hi".to_string());
+        assert_eq!(wait_for(&mut server, &mut alice, |m| *m == chat), chat);
+
+        let alice_id = alice.player_id;
+        std::mem::drop(alice);
+        let left = ServerMessage::PlayerLeft {
+            player_id: alice_id,
+        };
+        assert_eq!(wait_for(&mut server, &mut bob, |m| *m == left), left);
+        assert_eq!(server.player_count(), 1);
+        server.world.dispose();
+    }
+
+    #[test]
+    fn survival_players_mine_blocks_and_place_the_ones_they_have() {
+        let _world = persistence::set_test_world("survival-server-test");
+        let address = SocketAddr::from(([127, 0, 0, 1], 0));
+        let mut server = Server::bind(World::headless(), address).unwrap();
+        let mut alice = connect(&mut server, "Alice");
+        server.world.load_chunks(vec![(-1, 0)]);
+        let top = (0..255)
+            .rev()
+            .find(|y| {
+                server
+                    .world
+                    .get_block_type_absolute(&Vec3::new(-4.0, *y as f32, 4.0))
+                    .is_some_and(|block_type| block_type.is_solid())
+            })
+            .unwrap();
+        alice.send_position(Vec3::new(-3.5, top as f32 + 3.0, 4.5), 0.0, 0.0);
+        for _ in 0..500 {
+            server.tick(0.05);
+            if server.clients[&alice.player_id].position.y == top as f32 + 3.0 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        // Starts with nothing, to only hold what it mines
+        server.clients.get_mut(&alice.player_id).unwrap().inventory = Inventory::default();
+        let position = IVec3::new(-4, top, 4);
+        let mined = server
+            .world
+            .get_block_type_absolute(&position.as_vec3())
+            .unwrap();
+        let changed = |m: &ServerMessage| matches!(m, ServerMessage::BlockChanged { .. });
+
+        // Nothing to place, the item is given back, and the block wasn't mined
+        alice.place_block(position + IVec3::Y, BlockType::Stone);
+        wait_for(&mut server, &mut alice, changed);
+        assert_eq!(alice.take_refused_placements(), vec![BlockType::Stone]);
+        alice.break_block(position, mined);
+        wait_for(&mut server, &mut alice, changed);
+        assert!(alice.take_broken_blocks().is_empty());
+        assert_eq!(
+            server.world.get_block_type_absolute(&position.as_vec3()),
+            Some(mined)
+        );
+
+        alice.send(&ClientMessage::StartMining(position));
+        let ticks = (mined.mining_time() / 0.05).ceil() as u32 + 1;
+        for _ in 0..ticks {
+            server.tick(0.05);
+        }
+        alice.break_block(position, mined);
+        wait_for(&mut server, &mut alice, changed);
+        assert_eq!(alice.take_broken_blocks(), vec![mined]);
+        assert_ne!(
+            server.world.get_block_type_absolute(&position.as_vec3()),
+            Some(mined)
+        );
+        assert_eq!(server.clients[&alice.player_id].inventory.count(mined), 1);
+
+        // The mined block can be placed back, once
+        alice.place_block(position, mined);
+        let placed = ServerMessage::BlockChanged {
+            position,
+            block_type: Some(mined),
+        };
+        assert_eq!(wait_for(&mut server, &mut alice, |m| *m == placed), placed);
+        assert_eq!(server.clients[&alice.player_id].inventory.count(mined), 0);
+        assert!(alice.take_refused_placements().is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+
+// Larger frames close the connection, the biggest messages are chunks of a few hundred kilobytes
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+// Peers leaving more than this unread are dropped, rather than queuing their messages forever
+pub const MAX_PENDING_WRITES: usize = 32 * 1024 * 1024;
+
+// Nonblocking TCP connection split into frames, each one prefixed by its length as a little endian u32
+pub struct MessageStream {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    // Bytes that couldn't be written yet without blocking
+    outgoing: Vec<u8>,
+    // The peer closed the connection, the frames it sent before are still returned
+    closed: bool,
+}
+
+impl MessageStream {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: vec![],
+            outgoing: vec![],
+            closed: false,
+        })
+    }
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+    // Queues a frame, it is written by the next flush
+    pub fn send(&mut self, frame: &[u8]) {
+        self.outgoing
+            .extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        self.outgoing.extend_from_slice(frame);
+    }
+    // Writes as much of the queued frames as possible without blocking, fails if too many are left unread
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if self.outgoing.len() > MAX_PENDING_WRITES {
+            return Err(io::Error::other(format!(
+                "{} bytes left unread by the peer",
+                self.outgoing.len()
+            )));
+        }
+        Ok(())
+    }
+    pub fn has_pending_writes(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+    // Reads everything available and returns the complete frames, fails once the peer closed the connection
+    pub fn receive(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut buffer = [0; READ_BUFFER_SIZE];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut frames = vec![];
+        let mut start = 0;
+        while let Some(header) = self.incoming.get(start..start + 4) {
+            let length = u32::from_le_bytes(header.try_into().unwrap()) as usize;
+            if length > MAX_FRAME_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("frame of {length} bytes is too large"),
+                ));
+            }
+            let Some(frame) = self.incoming.get(start + 4..start + 4 + length) else {
+                break;
+            };
+            frames.push(frame.to_vec());
+            start += 4 + length;
+        }
+        self.incoming.drain(..start);
+        if frames.is_empty() && self.closed {
+            return Err(io::ErrorKind::ConnectionAborted.into());
+        }
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn peers_that_stop_reading_are_dropped() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let _peer = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut stream = MessageStream::new(listener.accept().unwrap().0).unwrap();
+        let frame = vec![0; 1024 * 1024];
+        let mut result = Ok(());
+        for _ in 0..MAX_PENDING_WRITES / frame.len() * 2 {
+            stream.send(&frame);
+            result = stream.flush();
+            if result.is_err() {
+                break;
+            }
+        }
+        assert!(result.is_err());
+    }
+}
@@ -1,5 +1,21 @@
 use std::any::Any;
 use std::error::Error;
+use std::path::PathBuf;
+use std::sync::RwLock;
+#[cfg(test)]
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+// Every world is saved in its own directory in here
+pub const WORLDS_DIR: &str = "data/worlds";
+const MAX_WORLD_NAME_LENGTH: usize = 32;
+// Saves from before worlds had their own directory were written straight into data
+const LEGACY_SAVE_DIR: &str = "data";
+const LEGACY_WORLD_NAME: &str = "World";
+
+lazy_static! {
+    // Directory of the open world, the saveables write their files in it
+    static ref WORLD_DIR: RwLock<PathBuf> = RwLock::new(PathBuf::from(WORLDS_DIR).join("default"));
+}
 
 pub trait Saveable<T> {
     fn save(&self) -> Result<(), Box<dyn Error>>;
@@ -8,3 +24,101 @@ pub trait Saveable<T> {
 pub trait Loadable<T> {
     fn load(args: Box<dyn Any>) -> Result<T, Box<dyn Error>>;
 }
+
+// Makes the saveables use the world's directory
+pub fn set_world(name: &str) {
+    *WORLD_DIR.write().unwrap() = PathBuf::from(WORLDS_DIR).join(name);
+}
+
+// Makes the saveables use the world's directory for a test, the other tests that call it wait until the
+// guard is dropped, as the directory is shared by the whole process
+#[cfg(test)]
+pub fn set_test_world(name: &str) -> MutexGuard<'static, ()> {
+    static TEST_WORLD: Mutex<()> = Mutex::new(());
+    let guard = TEST_WORLD.lock().unwrap_or_else(PoisonError::into_inner);
+    set_world(name);
+    guard
+}
+
+// Path of a file of the open world
+pub fn world_path(file: &str) -> PathBuf {
+    WORLD_DIR.read().unwrap().join(file)
+}
+
+pub fn create_world_dir() -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(&*WORLD_DIR.read().unwrap())?;
+    Ok(())
+}
+
+// Names of the saved worlds, sorted
+pub fn list_worlds() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(WORLDS_DIR) else {
+        return vec![];
+    };
+    let mut worlds = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect::<Vec<_>>();
+    worlds.sort();
+    worlds
+}
+
+// Moves a save from before world selection into its own world
+pub fn migrate_legacy_save() -> Result<(), Box<dyn Error>> {
+    let Ok(entries) = std::fs::read_dir(LEGACY_SAVE_DIR) else {
+        return Ok(());
+    };
+    let files = entries
+        .flatten()
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            entry.path().is_file()
+                && (name == "player" || name == "inventory" || name.starts_with("chunk"))
+        })
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        return Ok(());
+    }
+    let world_dir = PathBuf::from(WORLDS_DIR).join(LEGACY_WORLD_NAME);
+    if world_dir.exists() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(&world_dir)?;
+    for file in files {
+        std::fs::rename(file.path(), world_dir.join(file.file_name()))?;
+    }
+    Ok(())
+}
+
+pub fn delete_world(name: &str) -> Result<(), Box<dyn Error>> {
+    if !is_valid_world_name(name) {
+        return Err(format!("Invalid world name {name}").into());
+    }
+    std::fs::remove_dir_all(PathBuf::from(WORLDS_DIR).join(name))?;
+    Ok(())
+}
+
+// World names are used as directory names, so only a safe set of characters is allowed
+pub fn is_valid_world_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_WORLD_NAME_LENGTH
+        && name.trim() == name
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_names_are_safe_directory_names() {
+        assert!(is_valid_world_name("My world_2"));
+        assert!(!is_valid_world_name(""));
+        assert!(!is_valid_world_name(" padded "));
+        assert!(!is_valid_world_name("../data"));
+        assert!(!is_valid_world_name(&"a".repeat(MAX_WORLD_NAME_LENGTH + 1)));
+    }
+}
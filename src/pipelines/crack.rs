@@ -0,0 +1,253 @@
+use crate::{
+    blocks::block::{FaceDirections, CUBE_VERTEX},
+    material::Texture,
+    player::Player,
+    state::State,
+};
+
+use super::{pipeline_manager::PipelineManager, Pipeline};
+
+// Slightly bigger than a block so that the overlay doesn't z-fight with it
+const OVERLAY_SCALE: f32 = 1.002;
+const FACE_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [0.0, 0.0], [1.0, 0.0], [1.0, 1.0]];
+
+// Draws the crack overlay on the block being mined
+pub struct CrackPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub stage_buffer: wgpu::Buffer,
+    pub stage_bind_group: wgpu::BindGroup,
+    pub indices: u32,
+}
+
+impl Pipeline for CrackPipeline {
+    fn render(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        _player: &std::sync::RwLockReadGuard<'_, Player>,
+        _meshes: &crate::mesh_pool::MeshSnapshot,
+    ) {
+        if self.indices == 0 {
+            return;
+        }
+        let main_pipeline_ref = state
+            .pipeline_manager
+            .main_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow();
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &main_pipeline_ref.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &main_pipeline_ref.bind_group_0, &[]);
+        rpass.set_bind_group(1, &self.stage_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..self.indices, 0, 0..1);
+    }
+    fn update(
+        &mut self,
+        _pipeline_manager: &PipelineManager,
+        state: &State,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let player = state.player.read().unwrap();
+        let Some(mining) = player.mining else {
+            self.indices = 0;
+            return Ok(());
+        };
+
+        let mut vertices: Vec<[f32; 5]> = Vec::with_capacity(24);
+        let mut indices: Vec<u32> = Vec::with_capacity(36);
+        for face in FaceDirections::all() {
+            let face_indices = face.get_indices();
+            let base = vertices.len() as u32;
+            // Every face is two triangles (a, b, c) and (a, c, d)
+            for (corner, uv) in [0, 1, 2, 5].iter().zip(FACE_UVS) {
+                let vertex = face_indices[*corner] as usize * 3;
+                vertices.push([
+                    CUBE_VERTEX[vertex] * OVERLAY_SCALE + mining.position.x,
+                    CUBE_VERTEX[vertex + 1] * OVERLAY_SCALE + mining.position.y,
+                    CUBE_VERTEX[vertex + 2] * OVERLAY_SCALE + mining.position.z,
+                    uv[0],
+                    uv[1],
+                ]);
+            }
+            indices.extend([0, 1, 2, 0, 2, 3].map(|i| base + i));
+        }
+
+        state
+            .queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        state
+            .queue
+            .write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+        state.queue.write_buffer(
+            &self.stage_buffer,
+            0,
+            bytemuck::cast_slice(&[mining.stage() as f32]),
+        );
+        self.indices = indices.len() as u32;
+        Ok(())
+    }
+    fn init(state: &State, pipeline_manager: &PipelineManager) -> Self {
+        let swapchain_capabilities = state.surface.get_capabilities(&state.adapter);
+        let swapchain_format = swapchain_capabilities.formats[0];
+        let shader_source = include_str!("../shaders/crack.wgsl");
+
+        let shader = state
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+            });
+
+        let vertex_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("crack_vertex_buffer"),
+            size: std::mem::size_of::<[[f32; 5]; 24]>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("crack_index_buffer"),
+            size: std::mem::size_of::<[u32; 36]>() as u64,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let stage_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("crack_stage_buffer"),
+            size: std::mem::size_of::<f32>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let stage_bind_group_layout =
+            state
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("crack_bind_group_layout"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let stage_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("crack_bind_group"),
+            layout: &stage_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: stage_buffer.as_entire_binding(),
+            }],
+        });
+
+        let pipeline_layout =
+            state
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[
+                        &pipeline_manager
+                            .main_pipeline
+                            .as_ref()
+                            .unwrap()
+                            .borrow()
+                            .bind_group_0_layout,
+                        &stage_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+        let render_pipeline =
+            state
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: None,
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[Self::get_vertex_data_layout()],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: swapchain_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        cull_mode: None,
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: Texture::DEPTH_FORMAT,
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::LessEqual,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                });
+
+        Self {
+            pipeline: render_pipeline,
+            vertex_buffer,
+            index_buffer,
+            stage_buffer,
+            stage_bind_group,
+            indices: 0,
+        }
+    }
+}
+
+impl CrackPipeline {
+    fn get_vertex_data_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                // Position
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x3,
+                    offset: 0,
+                    shader_location: 0,
+                },
+                // Uv
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x2,
+                    offset: std::mem::size_of::<[f32; 3]>() as u64,
+                    shader_location: 1,
+                },
+            ],
+        }
+    }
+}
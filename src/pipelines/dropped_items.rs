@@ -0,0 +1,197 @@
+use glam::{Quat, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    blocks::{
+        block::{BlockVertexData, FaceDirections, TexturedBlock, CUBE_VERTEX},
+        block_type::BlockType,
+    },
+    entity::{item::ITEM_SIZE, Entity, EntityKind},
+    player::{Player, BLOCK_COLLISION_OFFSET},
+    state::State,
+};
+
+use super::{pipeline_manager::PipelineManager, Pipeline};
+
+const SPIN_SPEED: f32 = 1.5;
+const BOB_SPEED: f32 = 2.5;
+const BOB_HEIGHT: f32 = 0.05;
+const VERTICES_PER_ITEM: usize = 24;
+const INDICES_PER_ITEM: usize = 36;
+
+// Draws the dropped items as small spinning blocks, reusing the main pipeline
+pub struct DroppedItemsPipeline {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    // Items are in absolute positions, so they are drawn as if they were in the chunk (0, 0)
+    pub origin_bind_group: wgpu::BindGroup,
+    // Number of items that fit in the buffers
+    pub capacity: usize,
+    pub indices: u32,
+}
+
+impl Pipeline for DroppedItemsPipeline {
+    fn render(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        player: &std::sync::RwLockReadGuard<'_, Player>,
+        _meshes: &crate::mesh_pool::MeshSnapshot,
+    ) {
+        if self.indices == 0 {
+            return;
+        }
+        let main_pipeline_ref = state
+            .pipeline_manager
+            .main_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow();
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &main_pipeline_ref.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&main_pipeline_ref.pipeline);
+        rpass.set_bind_group(0, &main_pipeline_ref.bind_group_0, &[]);
+        rpass.set_bind_group(1, &self.origin_bind_group, &[]);
+        rpass.set_bind_group(2, &player.camera.position_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..self.indices, 0, 0..1);
+    }
+    fn update(
+        &mut self,
+        _pipeline_manager: &PipelineManager,
+        state: &State,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let items = state
+            .world
+            .entities
+            .iter()
+            .filter_map(|entity| match entity.kind {
+                EntityKind::Item(block_type) => Some((entity, block_type)),
+                EntityKind::Mob(_) => None,
+            })
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            self.indices = 0;
+            return Ok(());
+        }
+
+        let mut vertices = Vec::with_capacity(items.len() * VERTICES_PER_ITEM);
+        let mut indices = Vec::with_capacity(items.len() * INDICES_PER_ITEM);
+        for (item, block_type) in &items {
+            let base = vertices.len() as u32;
+            vertices.extend(Self::create_item_vertices(item, *block_type));
+            indices.extend(Self::create_item_indices(base));
+        }
+
+        if items.len() > self.capacity {
+            self.capacity = items.len().next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) =
+                Self::create_buffers(&state.device, self.capacity);
+        }
+        state
+            .queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        state
+            .queue
+            .write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+        self.indices = indices.len() as u32;
+        Ok(())
+    }
+    fn init(state: &State, _pipeline_manager: &PipelineManager) -> Self {
+        let origin_buffer = state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                contents: bytemuck::cast_slice(&[0_i32, 0_i32]),
+                label: Some("dropped-items-origin"),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let origin_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &state.world.renderer().chunk_data_layout,
+            label: Some("dropped-items-bg"),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: origin_buffer.as_entire_binding(),
+            }],
+        });
+
+        let capacity = 16;
+        let (vertex_buffer, index_buffer) = Self::create_buffers(&state.device, capacity);
+
+        Self {
+            vertex_buffer,
+            index_buffer,
+            origin_bind_group,
+            capacity,
+            indices: 0,
+        }
+    }
+}
+
+impl DroppedItemsPipeline {
+    fn create_buffers(device: &wgpu::Device, capacity: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("dropped_items_vertex_buffer"),
+            size: (std::mem::size_of::<BlockVertexData>() * VERTICES_PER_ITEM * capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("dropped_items_index_buffer"),
+            size: (std::mem::size_of::<u32>() * INDICES_PER_ITEM * capacity) as u64,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (vertex_buffer, index_buffer)
+    }
+    fn create_item_vertices(item: &Entity, block_type: BlockType) -> Vec<BlockVertexData> {
+        let rotation = Quat::from_rotation_y(item.age * SPIN_SPEED);
+        let bob = (item.age * BOB_SPEED).sin() * BOB_HEIGHT;
+        // Blocks are rendered centered on their position
+        let center = item.center() - BLOCK_COLLISION_OFFSET + Vec3::new(0.0, bob, 0.0);
+
+        let mut vertices = Vec::with_capacity(VERTICES_PER_ITEM);
+        for face in FaceDirections::all() {
+            let face_indices = face.get_indices();
+            let tex_coords = block_type.get_texcoords(face);
+            let normal = rotation * face.get_normal_vector();
+            // Every face is two triangles (a, b, c) and (a, c, d)
+            for (corner, tex_coords) in [0, 1, 2, 5].iter().zip(tex_coords) {
+                let vertex = face_indices[*corner] as usize * 3;
+                let offset = Vec3::from_slice(&CUBE_VERTEX[vertex..vertex + 3]);
+                vertices.push(BlockVertexData {
+                    position: (center + rotation * offset * ITEM_SIZE).into(),
+                    normal: normal.into(),
+                    tex_coords,
+                    ao: 0.0,
+                });
+            }
+        }
+        vertices
+    }
+    fn create_item_indices(base: u32) -> Vec<u32> {
+        (0..6)
+            .flat_map(|face| [0, 1, 2, 0, 2, 3].map(|i| base + face * 4 + i))
+            .collect()
+    }
+}
@@ -15,7 +15,7 @@ impl Pipeline for HighlightSelectedPipeline {
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         _player: &std::sync::RwLockReadGuard<'_, Player>,
-        _chunks: &Vec<std::sync::RwLockReadGuard<'_, crate::chunk::Chunk>>,
+        _meshes: &crate::mesh_pool::MeshSnapshot,
     ) {
         let main_pipeline_ref = state
             .pipeline_manager
@@ -64,7 +64,7 @@ impl Pipeline for HighlightSelectedPipeline {
                 .iter()
                 .find(|f| **f == player.facing_face.unwrap())
                 .unwrap()
-                .create_face_data(block_ptr.clone(), &vec![]);
+                .create_face_data(block_ptr.clone(), &[]);
 
             let block = block_ptr.read().unwrap();
             let block_positions = face_data
@@ -10,6 +10,7 @@ use wgpu::util::DeviceExt;
 pub struct MainPipeline {
     pub projection_buffer: wgpu::Buffer,
     pub view_buffer: wgpu::Buffer,
+    pub chunks_per_row_buffer: wgpu::Buffer,
     pub pipeline: wgpu::RenderPipeline,
     pub bind_group_0: wgpu::BindGroup,
     pub bind_group_0_layout: wgpu::BindGroupLayout,
@@ -19,12 +20,13 @@ pub struct MainPipeline {
 impl Pipeline for MainPipeline {
     fn render(
         &self,
-        _state: &State,
+        state: &State,
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         player: &std::sync::RwLockReadGuard<'_, Player>,
-        chunks: &Vec<std::sync::RwLockReadGuard<'_, crate::chunk::Chunk>>,
+        meshes: &crate::mesh_pool::MeshSnapshot,
     ) {
+        let daylight = state.world.daylight() as f64;
         let mut main_rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
@@ -32,9 +34,9 @@ impl Pipeline for MainPipeline {
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.03,
-                        g: 0.64,
-                        b: 0.97,
+                        r: 0.03 * daylight,
+                        g: 0.64 * daylight,
+                        b: 0.97 * daylight,
                         a: 1.0,
                     }),
                     store: wgpu::StoreOp::Store,
@@ -56,28 +58,7 @@ impl Pipeline for MainPipeline {
 
         main_rpass.set_bind_group(2, &player.camera.position_bind_group, &[]);
 
-        for chunk in chunks.iter() {
-            if chunk.visible {
-                main_rpass.set_bind_group(1, &chunk.chunk_bind_group, &[]);
-                main_rpass.set_vertex_buffer(
-                    0,
-                    chunk
-                        .chunk_vertex_buffer
-                        .as_ref()
-                        .expect("Vertex buffer not initiated")
-                        .slice(..),
-                );
-                main_rpass.set_index_buffer(
-                    chunk
-                        .chunk_index_buffer
-                        .as_ref()
-                        .expect("Index buffer not initiated")
-                        .slice(..),
-                    wgpu::IndexFormat::Uint32,
-                );
-                main_rpass.draw_indexed(0..chunk.indices, 0, 0..1);
-            }
-        }
+        meshes.draw(&mut main_rpass, false);
     }
 
     fn update(
@@ -121,13 +102,13 @@ impl Pipeline for MainPipeline {
             });
 
         // Constant bindgroup for chunks per row
-        let world_chunk_per_row_buffer =
+        let chunks_per_row_buffer =
             state
                 .device
                 .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    contents: bytemuck::cast_slice(&[crate::world::CHUNKS_PER_ROW]),
+                    contents: bytemuck::cast_slice(&[state.world.chunks_per_row]),
                     label: Some("world_chunk_per_row"),
-                    usage: wgpu::BufferUsages::UNIFORM,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
                 });
 
         let image_bytes = include_bytes!("../../assets/tex_atlas.png");
@@ -207,7 +188,7 @@ impl Pipeline for MainPipeline {
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: world_chunk_per_row_buffer.as_entire_binding(),
+                    resource: chunks_per_row_buffer.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 3,
@@ -231,7 +212,7 @@ impl Pipeline for MainPipeline {
                     label: None,
                     bind_group_layouts: &[
                         &bind_group_0_layout,
-                        &state.world.chunk_data_layout,
+                        &state.world.renderer().chunk_data_layout,
                         &state
                             .player
                             .read()
@@ -278,6 +259,7 @@ impl Pipeline for MainPipeline {
             bind_group_0_layout,
             view_buffer,
             projection_buffer,
+            chunks_per_row_buffer,
             depth_texture,
             bind_group_0,
             pipeline: render_pipeline,
@@ -1,7 +1,7 @@
 use std::{error::Error, sync::RwLockReadGuard};
 
 use self::pipeline_manager::PipelineManager;
-use crate::{chunk::Chunk, player::Player, state::State};
+use crate::{mesh_pool::MeshSnapshot, player::Player, state::State};
 
 pub trait Pipeline {
     fn init(state: &State, pipeline_manager: &PipelineManager) -> Self;
@@ -20,11 +20,16 @@ pub trait Pipeline {
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         player: &RwLockReadGuard<'_, Player>,
-        chunks: &Vec<RwLockReadGuard<'_, Chunk>>,
+        meshes: &MeshSnapshot,
     );
 }
+mod crack;
+mod dropped_items;
 mod highlight_selected;
 mod main;
+mod models;
 pub mod pipeline_manager;
+mod selection;
 mod translucent;
 mod ui;
+mod underwater;
@@ -0,0 +1,480 @@
+use std::f32::consts::FRAC_PI_2;
+
+use glam::{vec2, vec3, Quat, Vec2, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    blocks::block::{BlockVertexData, FaceDirections, CUBE_VERTEX},
+    entity::{mob::MobKind, Entity, EntityKind},
+    material::Texture,
+    net::client::PlayerPose,
+    player::{Player, BLOCK_COLLISION_OFFSET, PLAYER_EYE_HEIGHT, PLAYER_HEIGHT},
+    state::State,
+};
+
+use super::{pipeline_manager::PipelineManager, Pipeline};
+
+// Radians per block walked
+const SWING_FREQUENCY: f32 = 2.2;
+const MAX_SWING_ANGLE: f32 = 0.9;
+
+// How a part of a model moves
+#[derive(Clone, Copy, PartialEq)]
+enum Motion {
+    Fixed,
+    // Follows the yaw and pitch of the head
+    Head,
+    // Swings back and forth while walking, the sign tells which way it starts
+    Swing(f32),
+}
+
+// Box of a model in texture pixels, from the feet with the front towards -z and the right side towards -x
+struct ModelPart {
+    motion: Motion,
+    from: Vec3,
+    size: Vec3,
+    // Point the part rotates around
+    pivot: Vec3,
+    // Top left corner of the box's unwrapped faces in the texture
+    uv: [f32; 2],
+}
+
+struct Model {
+    parts: &'static [ModelPart],
+    texture_size: Vec2,
+    // Size of a texture pixel in blocks
+    pixel_size: f32,
+}
+
+impl Model {
+    fn vertices_per_model(&self) -> usize {
+        24 * self.parts.len()
+    }
+    fn indices_per_model(&self) -> usize {
+        36 * self.parts.len()
+    }
+}
+
+// Laid out like the classic 64x32 skins, 32 pixels tall
+const PLAYER_MODEL: Model = Model {
+    texture_size: vec2(64.0, 32.0),
+    pixel_size: PLAYER_HEIGHT / 32.0,
+    parts: &[
+        ModelPart {
+            motion: Motion::Head,
+            from: vec3(-4.0, 24.0, -4.0),
+            size: vec3(8.0, 8.0, 8.0),
+            pivot: vec3(0.0, 24.0, 0.0),
+            uv: [0.0, 0.0],
+        },
+        ModelPart {
+            motion: Motion::Fixed,
+            from: vec3(-4.0, 12.0, -2.0),
+            size: vec3(8.0, 12.0, 4.0),
+            pivot: vec3(0.0, 12.0, 0.0),
+            uv: [16.0, 16.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(1.0),
+            from: vec3(-8.0, 12.0, -2.0),
+            size: vec3(4.0, 12.0, 4.0),
+            pivot: vec3(-6.0, 22.0, 0.0),
+            uv: [40.0, 16.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(-1.0),
+            from: vec3(4.0, 12.0, -2.0),
+            size: vec3(4.0, 12.0, 4.0),
+            pivot: vec3(6.0, 22.0, 0.0),
+            uv: [40.0, 16.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(-1.0),
+            from: vec3(-4.0, 0.0, -2.0),
+            size: vec3(4.0, 12.0, 4.0),
+            pivot: vec3(-2.0, 12.0, 0.0),
+            uv: [0.0, 16.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(1.0),
+            from: vec3(0.0, 0.0, -2.0),
+            size: vec3(4.0, 12.0, 4.0),
+            pivot: vec3(2.0, 12.0, 0.0),
+            uv: [0.0, 16.0],
+        },
+    ],
+};
+
+const PIG_MODEL: Model = Model {
+    texture_size: vec2(64.0, 64.0),
+    pixel_size: 1.0 / 16.0,
+    parts: &[
+        ModelPart {
+            motion: Motion::Head,
+            from: vec3(-4.0, 8.0, -14.0),
+            size: vec3(8.0, 8.0, 8.0),
+            pivot: vec3(0.0, 12.0, -8.0),
+            uv: [0.0, 0.0],
+        },
+        ModelPart {
+            motion: Motion::Head,
+            from: vec3(-2.0, 9.0, -15.0),
+            size: vec3(4.0, 3.0, 1.0),
+            pivot: vec3(0.0, 12.0, -8.0),
+            uv: [32.0, 0.0],
+        },
+        ModelPart {
+            motion: Motion::Fixed,
+            from: vec3(-5.0, 6.0, -8.0),
+            size: vec3(10.0, 8.0, 16.0),
+            pivot: vec3(0.0, 6.0, 0.0),
+            uv: [0.0, 32.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(1.0),
+            from: vec3(-5.0, 0.0, -7.0),
+            size: vec3(4.0, 6.0, 4.0),
+            pivot: vec3(-3.0, 6.0, -5.0),
+            uv: [0.0, 16.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(-1.0),
+            from: vec3(1.0, 0.0, -7.0),
+            size: vec3(4.0, 6.0, 4.0),
+            pivot: vec3(3.0, 6.0, -5.0),
+            uv: [0.0, 16.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(-1.0),
+            from: vec3(-5.0, 0.0, 3.0),
+            size: vec3(4.0, 6.0, 4.0),
+            pivot: vec3(-3.0, 6.0, 5.0),
+            uv: [0.0, 16.0],
+        },
+        ModelPart {
+            motion: Motion::Swing(1.0),
+            from: vec3(1.0, 0.0, 3.0),
+            size: vec3(4.0, 6.0, 4.0),
+            pivot: vec3(3.0, 6.0, 5.0),
+            uv: [0.0, 16.0],
+        },
+    ],
+};
+
+// Where and how a model is drawn, yaws are like the camera's
+struct ModelPose {
+    // Center of the bottom of the model, in the space the camera renders
+    feet: Vec3,
+    body_yaw: f32,
+    head_yaw: f32,
+    head_pitch: f32,
+    // Distance walked, the phase of the swing
+    walked: f32,
+    // From 0 when standing still to 1 when the limbs swing the most
+    swing: f32,
+}
+
+impl ModelPose {
+    fn from_player(pose: &PlayerPose) -> Self {
+        Self {
+            feet: pose.position - BLOCK_COLLISION_OFFSET - vec3(0.0, PLAYER_EYE_HEIGHT, 0.0),
+            body_yaw: pose.body_yaw,
+            head_yaw: pose.yaw,
+            head_pitch: pose.pitch,
+            walked: pose.walked,
+            swing: pose.swing,
+        }
+    }
+    fn from_mob(entity: &Entity, kind: MobKind) -> Self {
+        let speed = vec2(entity.velocity.x, entity.velocity.z).length();
+        Self {
+            feet: entity.position - BLOCK_COLLISION_OFFSET,
+            body_yaw: entity.yaw,
+            head_yaw: entity.yaw,
+            head_pitch: 0.0,
+            walked: entity.walked,
+            swing: (speed / kind.walk_speed()).min(1.0),
+        }
+    }
+}
+
+// Models sharing a texture, drawn with a single draw call
+pub struct ModelBatch {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    // Bind group 0 of the main pipeline with the model's texture instead of the texture atlas
+    pub bind_group: wgpu::BindGroup,
+    // Number of models that fit in the buffers
+    pub capacity: usize,
+    pub indices: u32,
+}
+
+// Draws the other players of the server and the mobs with box limbed models, reusing the main pipeline
+pub struct ModelsPipeline {
+    pub player_batch: ModelBatch,
+    pub pig_batch: ModelBatch,
+    // Models are in absolute positions, so they are drawn as if they were in the chunk (0, 0)
+    pub origin_bind_group: wgpu::BindGroup,
+}
+
+impl Pipeline for ModelsPipeline {
+    fn render(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        player: &std::sync::RwLockReadGuard<'_, Player>,
+        _meshes: &crate::mesh_pool::MeshSnapshot,
+    ) {
+        let batches = [&self.player_batch, &self.pig_batch];
+        if batches.iter().all(|batch| batch.indices == 0) {
+            return;
+        }
+        let main_pipeline_ref = state
+            .pipeline_manager
+            .main_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow();
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &main_pipeline_ref.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&main_pipeline_ref.pipeline);
+        rpass.set_bind_group(1, &self.origin_bind_group, &[]);
+        rpass.set_bind_group(2, &player.camera.position_bind_group, &[]);
+        for batch in batches.into_iter().filter(|batch| batch.indices > 0) {
+            rpass.set_bind_group(0, &batch.bind_group, &[]);
+            rpass.set_vertex_buffer(0, batch.vertex_buffer.slice(..));
+            rpass.set_index_buffer(batch.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            rpass.draw_indexed(0..batch.indices, 0, 0..1);
+        }
+    }
+    fn update(
+        &mut self,
+        _pipeline_manager: &PipelineManager,
+        state: &State,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let players = state
+            .remote_players
+            .values()
+            .filter(|player| player.has_position())
+            .map(|player| ModelPose::from_player(&player.pose))
+            .collect::<Vec<_>>();
+        self.player_batch.write(state, &PLAYER_MODEL, &players);
+
+        let pigs = state
+            .world
+            .entities
+            .iter()
+            .filter_map(|entity| match &entity.kind {
+                EntityKind::Mob(mob) if mob.kind == MobKind::Pig => {
+                    Some(ModelPose::from_mob(entity, mob.kind))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        self.pig_batch.write(state, &PIG_MODEL, &pigs);
+        Ok(())
+    }
+    fn init(state: &State, pipeline_manager: &PipelineManager) -> Self {
+        let skin = include_bytes!("../../assets/skin.png");
+        let pig = include_bytes!("../../assets/pig.png");
+        let origin_buffer = state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                contents: bytemuck::cast_slice(&[0_i32, 0_i32]),
+                label: Some("models-origin"),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let origin_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &state.world.renderer().chunk_data_layout,
+            label: Some("models-bg"),
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: origin_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            player_batch: ModelBatch::new(state, pipeline_manager, &PLAYER_MODEL, skin, "skin"),
+            pig_batch: ModelBatch::new(state, pipeline_manager, &PIG_MODEL, pig, "pig"),
+            origin_bind_group,
+        }
+    }
+}
+
+impl ModelBatch {
+    fn new(
+        state: &State,
+        pipeline_manager: &PipelineManager,
+        model: &Model,
+        texture_bytes: &[u8],
+        name: &str,
+    ) -> Self {
+        let main_pipeline_ref = pipeline_manager.main_pipeline.as_ref().unwrap().borrow();
+        let texture =
+            Texture::from_bytes(texture_bytes, name.to_string(), &state.device, &state.queue)
+                .unwrap();
+        let bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &main_pipeline_ref.bind_group_0_layout,
+            label: Some(&format!("models-{name}-bg")),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: main_pipeline_ref.projection_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: main_pipeline_ref.view_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: main_pipeline_ref.chunks_per_row_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        let capacity = 4;
+        let (vertex_buffer, index_buffer) = Self::create_buffers(&state.device, model, capacity);
+        Self {
+            vertex_buffer,
+            index_buffer,
+            bind_group,
+            capacity,
+            indices: 0,
+        }
+    }
+    fn create_buffers(
+        device: &wgpu::Device,
+        model: &Model,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("models_vertex_buffer"),
+            size: (std::mem::size_of::<BlockVertexData>() * model.vertices_per_model() * capacity)
+                as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("models_index_buffer"),
+            size: (std::mem::size_of::<u32>() * model.indices_per_model() * capacity) as u64,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (vertex_buffer, index_buffer)
+    }
+    fn write(&mut self, state: &State, model: &Model, poses: &[ModelPose]) {
+        if poses.is_empty() {
+            self.indices = 0;
+            return;
+        }
+        let mut vertices = Vec::with_capacity(poses.len() * model.vertices_per_model());
+        let mut indices = Vec::with_capacity(poses.len() * model.indices_per_model());
+        for pose in poses {
+            let base = vertices.len() as u32;
+            vertices.extend(create_model_vertices(model, pose));
+            indices.extend(
+                (0..6 * model.parts.len() as u32)
+                    .flat_map(|face| [0, 1, 2, 0, 2, 3].map(|i| base + face * 4 + i)),
+            );
+        }
+
+        if poses.len() > self.capacity {
+            self.capacity = poses.len().next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) =
+                Self::create_buffers(&state.device, model, self.capacity);
+        }
+        state
+            .queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        state
+            .queue
+            .write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&indices));
+        self.indices = indices.len() as u32;
+    }
+}
+
+fn create_model_vertices(model: &Model, pose: &ModelPose) -> Vec<BlockVertexData> {
+    // Models face -z, turned so that they face the direction the yaw points to
+    let body = Quat::from_rotation_y(-FRAC_PI_2 - pose.body_yaw);
+    let head =
+        Quat::from_rotation_y(-FRAC_PI_2 - pose.head_yaw) * Quat::from_rotation_x(pose.head_pitch);
+    let swing = (pose.walked * SWING_FREQUENCY).sin() * MAX_SWING_ANGLE * pose.swing;
+
+    let mut vertices = Vec::with_capacity(model.vertices_per_model());
+    for part in model.parts {
+        let rotation = match part.motion {
+            Motion::Fixed => body,
+            Motion::Head => head,
+            Motion::Swing(direction) => body * Quat::from_rotation_x(swing * direction),
+        };
+        let pivot = pose.feet + body * part.pivot * model.pixel_size;
+        for face in FaceDirections::all() {
+            let face_indices = face.get_indices();
+            let normal = rotation * face.get_normal_vector();
+            let tex_coords = texture_coords(model, part, face);
+            for (corner, tex_coords) in [0, 1, 2, 5].iter().zip(tex_coords) {
+                let vertex = face_indices[*corner] as usize * 3;
+                let offset = Vec3::from_slice(&CUBE_VERTEX[vertex..vertex + 3]) + 0.5;
+                let local = part.from + offset * part.size - part.pivot;
+                vertices.push(BlockVertexData {
+                    position: (pivot + rotation * local * model.pixel_size).into(),
+                    normal: normal.into(),
+                    tex_coords,
+                    ao: 0.0,
+                });
+            }
+        }
+    }
+    vertices
+}
+
+// Faces are unwrapped around the box like in the classic skins
+fn texture_coords(model: &Model, part: &ModelPart, face: FaceDirections) -> [[f32; 2]; 4] {
+    let [u, v] = part.uv;
+    let Vec3 { x: w, y: h, z: d } = part.size;
+    let (x, y, width, height) = match face {
+        FaceDirections::Top => (u + d, v, w, d),
+        FaceDirections::Bottom => (u + d + w, v, w, d),
+        FaceDirections::Left => (u, v + d, d, h),
+        FaceDirections::Front => (u + d, v + d, w, h),
+        FaceDirections::Right => (u + d + w, v + d, d, h),
+        FaceDirections::Back => (u + 2.0 * d + w, v + d, w, h),
+    };
+    let min = vec2(x, y) / model.texture_size;
+    let max = vec2(x + width, y + height) / model.texture_size;
+    // Bottom left, top left, top right, bottom right as seen from outside the box
+    [
+        [min.x, max.y],
+        [min.x, min.y],
+        [max.x, min.y],
+        [max.x, max.y],
+    ]
+}
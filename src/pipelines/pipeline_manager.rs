@@ -5,8 +5,10 @@ use wgpu::{CommandEncoder, TextureView};
 use crate::state::State;
 
 use super::{
-    highlight_selected::HighlightSelectedPipeline, main::MainPipeline,
-    translucent::TranslucentPipeline, ui::UIPipeline, Pipeline,
+    crack::CrackPipeline, dropped_items::DroppedItemsPipeline,
+    highlight_selected::HighlightSelectedPipeline, main::MainPipeline, models::ModelsPipeline,
+    selection::SelectionPipeline, translucent::TranslucentPipeline, ui::UIPipeline,
+    underwater::UnderwaterPipeline, Pipeline,
 };
 
 pub struct PipelineManager {
@@ -14,6 +16,11 @@ pub struct PipelineManager {
     pub translucent_pipeline: Option<RefCell<TranslucentPipeline>>,
     pub highlight_selected_pipeline: Option<RefCell<HighlightSelectedPipeline>>,
     pub ui_pipeline: Option<RefCell<UIPipeline>>,
+    pub underwater_pipeline: Option<RefCell<UnderwaterPipeline>>,
+    pub selection_pipeline: Option<RefCell<SelectionPipeline>>,
+    pub crack_pipeline: Option<RefCell<CrackPipeline>>,
+    pub dropped_items_pipeline: Option<RefCell<DroppedItemsPipeline>>,
+    pub models_pipeline: Option<RefCell<ModelsPipeline>>,
 }
 
 impl PipelineManager {
@@ -31,6 +38,11 @@ impl PipelineManager {
             main_pipeline: None,
             translucent_pipeline: None,
             ui_pipeline: None,
+            underwater_pipeline: None,
+            selection_pipeline: None,
+            crack_pipeline: None,
+            dropped_items_pipeline: None,
+            models_pipeline: None,
         };
         pipeline.main_pipeline = Some(RefCell::new(MainPipeline::init(state, &pipeline)));
         pipeline.translucent_pipeline =
@@ -39,6 +51,13 @@ impl PipelineManager {
             state, &pipeline,
         )));
         pipeline.ui_pipeline = Some(RefCell::new(UIPipeline::init(state, &pipeline)));
+        pipeline.underwater_pipeline =
+            Some(RefCell::new(UnderwaterPipeline::init(state, &pipeline)));
+        pipeline.selection_pipeline = Some(RefCell::new(SelectionPipeline::init(state, &pipeline)));
+        pipeline.crack_pipeline = Some(RefCell::new(CrackPipeline::init(state, &pipeline)));
+        pipeline.dropped_items_pipeline =
+            Some(RefCell::new(DroppedItemsPipeline::init(state, &pipeline)));
+        pipeline.models_pipeline = Some(RefCell::new(ModelsPipeline::init(state, &pipeline)));
         pipeline
     }
 
@@ -63,6 +82,31 @@ impl PipelineManager {
             .unwrap()
             .borrow_mut()
             .update(self, state)?;
+        self.underwater_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .update(self, state)?;
+        self.selection_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .update(self, state)?;
+        self.crack_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .update(self, state)?;
+        self.dropped_items_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .update(self, state)?;
+        self.models_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .update(self, state)?;
 
         Ok(())
     }
@@ -0,0 +1,179 @@
+use crate::{material::Texture, player::Player, state::State};
+
+use super::{
+    highlight_selected::HighlightSelectedPipeline, pipeline_manager::PipelineManager, Pipeline,
+};
+
+// Edges of a box as pairs of corner indices, corners are indexed by their xyz bits
+const BOX_EDGES: [u32; 24] = [
+    0, 1, 2, 3, 4, 5, 6, 7, // x
+    0, 2, 1, 3, 4, 6, 5, 7, // y
+    0, 4, 1, 5, 2, 6, 3, 7, // z
+];
+
+// Draws the outline of the player's WorldEdit selection
+pub struct SelectionPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub indices: u32,
+}
+impl Pipeline for SelectionPipeline {
+    fn render(
+        &self,
+        state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        _player: &std::sync::RwLockReadGuard<'_, Player>,
+        _meshes: &crate::mesh_pool::MeshSnapshot,
+    ) {
+        if self.indices == 0 {
+            return;
+        }
+        let main_pipeline_ref = state
+            .pipeline_manager
+            .main_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow();
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &main_pipeline_ref.depth_texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &main_pipeline_ref.bind_group_0, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..self.indices, 0, 0..1);
+    }
+    fn update(
+        &mut self,
+        _pipeline_manager: &PipelineManager,
+        state: &State,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let player = state.player.read().unwrap();
+        if let Some(region) = player.selection.region() {
+            // Blocks are rendered centered on their position
+            let min = region.min - 0.5;
+            let max = region.max + 0.5;
+            let corners = (0..8)
+                .map(|i| {
+                    [
+                        if i & 1 == 0 { min.x } else { max.x },
+                        if i & 2 == 0 { min.y } else { max.y },
+                        if i & 4 == 0 { min.z } else { max.z },
+                    ]
+                })
+                .collect::<Vec<_>>();
+
+            state
+                .queue
+                .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&corners));
+            self.indices = BOX_EDGES.len() as u32;
+        } else {
+            self.indices = 0;
+        }
+        Ok(())
+    }
+    fn init(state: &State, pipeline_manager: &PipelineManager) -> Self {
+        let swapchain_capabilities = state.surface.get_capabilities(&state.adapter);
+        let swapchain_format = swapchain_capabilities.formats[0];
+        let shader_source = include_str!("../shaders/selection.wgsl");
+
+        let shader = state
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+            });
+
+        let vertex_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of::<[[f32; 3]; 8]>() as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = state.device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size: std::mem::size_of_val(&BOX_EDGES) as u64,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        state
+            .queue
+            .write_buffer(&index_buffer, 0, bytemuck::cast_slice(&BOX_EDGES));
+
+        let pipeline_layout =
+            state
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[&pipeline_manager
+                        .main_pipeline
+                        .as_ref()
+                        .unwrap()
+                        .borrow()
+                        .bind_group_0_layout],
+                    push_constant_ranges: &[],
+                });
+        let render_pipeline =
+            state
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: None,
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[HighlightSelectedPipeline::get_vertex_data_layout()],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: swapchain_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::LineList,
+                        ..Default::default()
+                    },
+                    // The outline stays visible through blocks
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: Texture::DEPTH_FORMAT,
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Always,
+                        stencil: wgpu::StencilState::default(),
+                        bias: wgpu::DepthBiasState::default(),
+                    }),
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                });
+
+        Self {
+            indices: 0,
+            pipeline: render_pipeline,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+}
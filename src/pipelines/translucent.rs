@@ -3,8 +3,8 @@ use std::sync::RwLockReadGuard;
 use super::pipeline_manager::PipelineManager;
 use super::Pipeline;
 use crate::blocks::block::Block;
-use crate::chunk::Chunk;
 use crate::material::Texture;
+use crate::mesh_pool::MeshSnapshot;
 use crate::player::Player;
 use crate::state::State;
 
@@ -52,7 +52,7 @@ impl Pipeline for TranslucentPipeline {
                             .unwrap()
                             .borrow()
                             .bind_group_0_layout,
-                        &state.world.chunk_data_layout,
+                        &state.world.renderer().chunk_data_layout,
                         &state
                             .player
                             .read()
@@ -109,7 +109,7 @@ impl Pipeline for TranslucentPipeline {
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         player: &RwLockReadGuard<'_, Player>,
-        chunks: &Vec<RwLockReadGuard<'_, Chunk>>,
+        meshes: &MeshSnapshot,
     ) {
         let main_pipeline_ref = state
             .pipeline_manager
@@ -131,7 +131,8 @@ impl Pipeline for TranslucentPipeline {
                 view: &main_pipeline_ref.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
                     load: wgpu::LoadOp::Load,
-                    store: wgpu::StoreOp::Discard,
+                    // Kept for the underwater post effect
+                    store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
             }),
@@ -142,28 +143,7 @@ impl Pipeline for TranslucentPipeline {
         water_rpass.set_bind_group(0, &main_pipeline_ref.bind_group_0, &[]);
         water_rpass.set_bind_group(2, &player.camera.position_bind_group, &[]);
 
-        for chunk in chunks.iter() {
-            if chunk.visible {
-                water_rpass.set_bind_group(1, &chunk.chunk_bind_group, &[]);
-                water_rpass.set_vertex_buffer(
-                    0,
-                    chunk
-                        .chunk_water_vertex_buffer
-                        .as_ref()
-                        .expect("Vertex buffer not initiated")
-                        .slice(..),
-                );
-                water_rpass.set_index_buffer(
-                    chunk
-                        .chunk_water_index_buffer
-                        .as_ref()
-                        .expect("Index buffer not initiated")
-                        .slice(..),
-                    wgpu::IndexFormat::Uint32,
-                );
-                water_rpass.draw_indexed(0..chunk.water_indices, 0, 0..1);
-            }
-        }
+        meshes.draw(&mut water_rpass, true);
     }
 }
 
@@ -1,16 +1,28 @@
-use crate::blocks::block::{FaceDirections, TexturedBlock};
+use crate::debug::DebugInfo;
+use crate::hud::{self, HudBatch, HudLayout, HudVertex};
 use crate::material::Texture;
-use crate::player::Player;
+use crate::player::{Player, BLOCK_COLLISION_OFFSET};
 use crate::state::State;
-use wgpu::util::DeviceExt;
-use wgpu::BufferUsages;
 
 use super::pipeline_manager::PipelineManager;
 use super::Pipeline;
 
+// Quads that fit in the buffers when they're created
+const INITIAL_QUAD_CAPACITY: usize = 256;
+// Nameplates float this far above the eyes of the other players
+const NAMEPLATE_HEIGHT: f32 = 0.55;
+// Farther players don't get a nameplate
+const NAMEPLATE_DISTANCE: f32 = 48.0;
+
+// Draws the HUD built by `hud::build_hud` in a single batched pass
 pub struct UIPipeline {
     pub pipeline: wgpu::RenderPipeline,
-    pub screenspace_buffer: wgpu::Buffer,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub quad_capacity: usize,
+    pub indices: u32,
+    pub layout: HudLayout,
+    pub font_bind_group: wgpu::BindGroup,
 }
 
 impl Pipeline for UIPipeline {
@@ -20,7 +32,7 @@ impl Pipeline for UIPipeline {
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
         _player: &std::sync::RwLockReadGuard<'_, Player>,
-        _chunks: &Vec<std::sync::RwLockReadGuard<'_, crate::chunk::Chunk>>,
+        _meshes: &crate::mesh_pool::MeshSnapshot,
     ) {
         let main_pipeline_ref = state
             .pipeline_manager
@@ -51,8 +63,10 @@ impl Pipeline for UIPipeline {
         });
         rpass.set_pipeline(&self.pipeline);
         rpass.set_bind_group(0, &main_pipeline_ref.bind_group_0, &[]);
-        rpass.set_vertex_buffer(0, self.screenspace_buffer.slice(..));
-        rpass.draw(0..6, 0..1);
+        rpass.set_bind_group(1, &self.font_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        rpass.draw_indexed(0..self.indices, 0, 0..1);
     }
     fn init(state: &State, pipeline_manager: &PipelineManager) -> Self {
         let swapchain_capabilities = state.surface.get_capabilities(&state.adapter);
@@ -66,21 +80,51 @@ impl Pipeline for UIPipeline {
                 source: wgpu::ShaderSource::Wgsl(shader_source.into()),
             });
 
-        let aspect_ratio = state.surface_config.height as f32 / state.surface_config.width as f32;
-
-        let player = state.player.read().unwrap();
-        let block_type = player.placing_block;
-        let tex_coords = block_type.get_texcoords(FaceDirections::Front);
-        let screen_quad = Self::create_screen_quad(aspect_ratio, tex_coords);
+        let (vertex_buffer, index_buffer) =
+            Self::create_buffers(&state.device, INITIAL_QUAD_CAPACITY);
 
-        let screenspace_buffer =
+        let font_bytes = include_bytes!("../../assets/font.png");
+        let font_texture =
+            Texture::from_bytes(font_bytes, "font".to_string(), &state.device, &state.queue)
+                .unwrap();
+        let font_bind_group_layout =
             state
                 .device
-                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                    contents: bytemuck::cast_slice(&screen_quad),
-                    label: Some("Screenspace rectangle"),
-                    usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("font_bind_group_layout"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
                 });
+        let font_bind_group = state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("font_bind_group"),
+            layout: &font_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&font_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&font_texture.sampler),
+                },
+            ],
+        });
 
         // Pipeline layouts
         let pipeline_layout =
@@ -88,12 +132,15 @@ impl Pipeline for UIPipeline {
                 .device
                 .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                     label: None,
-                    bind_group_layouts: &[&pipeline_manager
-                        .main_pipeline
-                        .as_ref()
-                        .unwrap()
-                        .borrow()
-                        .bind_group_0_layout],
+                    bind_group_layouts: &[
+                        &pipeline_manager
+                            .main_pipeline
+                            .as_ref()
+                            .unwrap()
+                            .borrow()
+                            .bind_group_0_layout,
+                        &font_bind_group_layout,
+                    ],
                     push_constant_ranges: &[],
                 });
 
@@ -134,8 +181,16 @@ impl Pipeline for UIPipeline {
                 });
 
         Self {
-            screenspace_buffer,
             pipeline: render_pipeline,
+            vertex_buffer,
+            index_buffer,
+            quad_capacity: INITIAL_QUAD_CAPACITY,
+            indices: 0,
+            layout: HudLayout::new(
+                state.surface_config.width as f32,
+                state.surface_config.height as f32,
+            ),
+            font_bind_group,
         }
     }
     fn update(
@@ -143,52 +198,87 @@ impl Pipeline for UIPipeline {
         _pipeline_manager: &PipelineManager,
         state: &State,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let aspect_ratio = state.surface_config.height as f32 / state.surface_config.width as f32;
         let player = state.player.read().unwrap();
-        let block_type = player.placing_block;
-        let tex_coords = block_type.get_texcoords(FaceDirections::Front);
-        let screen_quad = Self::create_screen_quad(aspect_ratio, tex_coords);
+        let mut batch = if state.world_name.is_some() {
+            let mut batch = hud::build_hud(self.layout, &player);
+            add_nameplates(&mut batch, state, &player);
+            hud::add_console(&mut batch, &state.console);
+            if state.settings.debug_overlay {
+                let info = DebugInfo::collect(state, &player);
+                hud::add_debug_overlay(&mut batch, &info.lines());
+            }
+            batch
+        } else {
+            HudBatch::new(self.layout)
+        };
+        if state.menu.is_open() {
+            hud::add_menu(&mut batch, &state.menu, &state.settings);
+        }
+
+        let quads = batch.vertices.len() / 4;
+        if quads > self.quad_capacity {
+            self.quad_capacity = quads.next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) =
+                Self::create_buffers(&state.device, self.quad_capacity);
+        }
         state.queue.write_buffer(
-            &self.screenspace_buffer,
+            &self.vertex_buffer,
             0,
-            bytemuck::cast_slice(&screen_quad),
+            bytemuck::cast_slice(&batch.vertices),
         );
+        state
+            .queue
+            .write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&batch.indices));
+        self.indices = batch.indices.len() as u32;
         Ok(())
     }
 }
+// Names of the other players of the server, in the space the camera renders
+fn add_nameplates(batch: &mut HudBatch, state: &State, player: &Player) {
+    let camera = &player.camera;
+    let nameplates = state
+        .remote_players
+        .values()
+        .filter(|remote| remote.has_position())
+        .map(|remote| {
+            let head = remote.pose.position - BLOCK_COLLISION_OFFSET;
+            (
+                head + glam::vec3(0.0, NAMEPLATE_HEIGHT, 0.0),
+                remote.name.as_str(),
+            )
+        })
+        .filter(|(point, _)| point.distance(camera.eye) < NAMEPLATE_DISTANCE)
+        .collect::<Vec<_>>();
+    if nameplates.is_empty() {
+        return;
+    }
+    let view_projection = camera.build_projection_matrix() * camera.build_view_matrix();
+    hud::add_nameplates(batch, view_projection, &nameplates);
+}
+
 impl UIPipeline {
-    // Creates the rectangle coords for displaying the block that would be placed if something is placed.
-    fn create_screen_quad(aspect_ratio: f32, tex_coords: [[f32; 2]; 4]) -> Vec<f32> {
-        vec![
-            -0.9 * aspect_ratio,
-            -0.9,
-            tex_coords[0][0],
-            tex_coords[0][1],
-            -0.9 * aspect_ratio,
-            -0.6,
-            tex_coords[1][0],
-            tex_coords[1][1],
-            -0.6 * aspect_ratio,
-            -0.6,
-            tex_coords[2][0],
-            tex_coords[2][1],
-            -0.9 * aspect_ratio,
-            -0.9,
-            tex_coords[0][0],
-            tex_coords[0][1],
-            -0.6 * aspect_ratio,
-            -0.6,
-            tex_coords[2][0],
-            tex_coords[2][1],
-            -0.6 * aspect_ratio,
-            -0.9,
-            tex_coords[3][0],
-            tex_coords[3][1],
-        ]
+    // Lays the HUD out again for the new screen size
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.layout = HudLayout::new(width as f32, height as f32);
+    }
+    fn create_buffers(device: &wgpu::Device, quad_capacity: usize) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("hud_vertex_buffer"),
+            size: (std::mem::size_of::<HudVertex>() * 4 * quad_capacity) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("hud_index_buffer"),
+            size: (std::mem::size_of::<u32>() * 6 * quad_capacity) as u64,
+            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (vertex_buffer, index_buffer)
     }
     fn get_vertex_data_layout() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            array_stride: std::mem::size_of::<HudVertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &[
                 // Position
@@ -203,6 +293,18 @@ impl UIPipeline {
                     offset: std::mem::size_of::<[f32; 2]>() as u64,
                     shader_location: 1,
                 },
+                // Color
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Float32x4,
+                    offset: std::mem::size_of::<[f32; 4]>() as u64,
+                    shader_location: 2,
+                },
+                // Mode
+                wgpu::VertexAttribute {
+                    format: wgpu::VertexFormat::Uint32,
+                    offset: std::mem::size_of::<[f32; 8]>() as u64,
+                    shader_location: 3,
+                },
             ],
         }
     }
@@ -0,0 +1,203 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::{player::Player, state::State};
+
+use super::{pipeline_manager::PipelineManager, Pipeline};
+
+const UNDERWATER_COLOR: [f32; 4] = [0.02, 0.18, 0.45, 0.35];
+const UNDERWATER_FOG_DENSITY: f32 = 0.12;
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct UnderwaterUniforms {
+    color: [f32; 4],
+    znear: f32,
+    zfar: f32,
+    density: f32,
+    _padding: f32,
+}
+
+// Post effect that tints the screen and adds a thick fog when the camera is under water.
+pub struct UnderwaterPipeline {
+    pub pipeline: wgpu::RenderPipeline,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    pub uniform_buffer: wgpu::Buffer,
+}
+
+impl Pipeline for UnderwaterPipeline {
+    fn render(
+        &self,
+        _state: &State,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        player: &std::sync::RwLockReadGuard<'_, Player>,
+        _meshes: &crate::mesh_pool::MeshSnapshot,
+    ) {
+        if !player.head_in_water {
+            return;
+        }
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("underwater"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+    fn update(
+        &mut self,
+        _pipeline_manager: &PipelineManager,
+        state: &State,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let player = state.player.read().unwrap();
+        let uniforms = UnderwaterUniforms {
+            color: UNDERWATER_COLOR,
+            znear: player.camera.znear,
+            zfar: player.camera.zfar,
+            density: UNDERWATER_FOG_DENSITY,
+            _padding: 0.0,
+        };
+        state
+            .queue
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        Ok(())
+    }
+    fn init(state: &State, pipeline_manager: &PipelineManager) -> Self {
+        let swapchain_capabilities = state.surface.get_capabilities(&state.adapter);
+        let swapchain_format = swapchain_capabilities.formats[0];
+        let shader_source = include_str!("../shaders/underwater.wgsl");
+
+        let shader = state
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: None,
+                source: wgpu::ShaderSource::Wgsl(shader_source.into()),
+            });
+
+        let uniform_buffer = state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("underwater_uniforms"),
+                contents: bytemuck::cast_slice(&[UnderwaterUniforms::zeroed()]),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let bind_group_layout =
+            state
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("underwater_bind_group"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Depth,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let bind_group =
+            Self::create_bind_group(state, pipeline_manager, &bind_group_layout, &uniform_buffer);
+
+        let pipeline_layout =
+            state
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: None,
+                    bind_group_layouts: &[&bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+        let render_pipeline =
+            state
+                .device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: None,
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: swapchain_format,
+                            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                });
+
+        Self {
+            pipeline: render_pipeline,
+            bind_group_layout,
+            bind_group,
+            uniform_buffer,
+        }
+    }
+}
+
+impl UnderwaterPipeline {
+    fn create_bind_group(
+        state: &State,
+        pipeline_manager: &PipelineManager,
+        layout: &wgpu::BindGroupLayout,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let main_pipeline = pipeline_manager.main_pipeline.as_ref().unwrap().borrow();
+        state.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            label: Some("underwater_bind_group"),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&main_pipeline.depth_texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+    // The depth texture is recreated on resize, so the bind group pointing to it has to be too
+    pub fn on_depth_texture_changed(&mut self, state: &State) {
+        self.bind_group = Self::create_bind_group(
+            state,
+            &state.pipeline_manager,
+            &self.bind_group_layout,
+            &self.uniform_buffer,
+        );
+    }
+}
@@ -2,26 +2,58 @@ use std::any::Any;
 use std::error::Error;
 use std::f32::consts;
 use std::sync::{Arc, RwLock};
-use std::time::{Duration, Instant};
 
 use glam::{vec3, Vec2, Vec3};
 use wgpu::util::DeviceExt;
 
 use crate::blocks::block::{Block, FaceDirections};
 use crate::blocks::block_type::BlockType;
-use crate::collision::RayResult;
-use crate::persistence::{Loadable, Saveable};
+use crate::collision::{self, BlockHit};
+use crate::inventory::Inventory;
+use crate::mining::MiningProgress;
+use crate::persistence::{self, Loadable, Saveable};
+use crate::region::{Clipboard, Selection};
+use crate::vitals::{self, Vitals};
+use crate::world::World;
 use crate::{collision::CollisionBox, world::CHUNK_SIZE};
 
 const SENSITIVITY: f32 = 0.001;
-const CAMERA_SPEED: f32 = 10.0;
-const GRAVITY: f32 = 10.0;
-pub static PLAYER_VIEW_OFFSET: Vec3 = vec3(0.4, 1.0, 0.4); /* this is kind of a hack, we should fix the camera's eye */
 
-lazy_static! {
-    static ref JUMP_DURATION: Duration = Duration::from_secs_f32(0.1);
-}
-const JUMP_HEIGHT: f32 = 1.5;
+// Physics constants, distances are in blocks and times in seconds.
+pub const GRAVITY: f32 = 28.0;
+pub const TERMINAL_VELOCITY: f32 = 60.0;
+pub const JUMP_VELOCITY: f32 = 9.0; // Reaches ~1.45 blocks
+const WALK_SPEED: f32 = 4.3;
+const SPRINT_SPEED: f32 = 5.6;
+const CROUCH_SPEED: f32 = 1.3;
+pub const GROUND_ACCELERATION: f32 = 60.0;
+pub const AIR_ACCELERATION: f32 = 12.0;
+pub const STEP_HEIGHT: f32 = 0.5;
+// Swimming
+const SWIM_SPEED: f32 = 2.2;
+const SWIM_ACCELERATION: f32 = 20.0;
+pub const SWIM_VERTICAL_ACCELERATION: f32 = 24.0;
+// Upwards acceleration when fully submerged, it scales with how much of the body is under water.
+// It's a bit higher than GRAVITY so that the player floats with the eyes just above the surface.
+pub const BUOYANCY: f32 = 32.0;
+// Fraction of the velocity lost per second while in water
+pub const WATER_DRAG: f32 = 3.0;
+// Vertical speed given when swimming against a wall, so that the player can climb out of the water
+const WATER_EXIT_VELOCITY: f32 = 5.0;
+// Physics is integrated in steps of at most this length, so that a slow frame can't tunnel through blocks
+pub const MAX_PHYSICS_STEP: f32 = 1.0 / 60.0;
+const MAX_FRAME_TIME: f32 = 0.25;
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+pub const PLAYER_EYE_HEIGHT: f32 = 1.6;
+pub const PLAYER_REACH: f32 = 5.0;
+// Eye position of new players
+pub const DEFAULT_SPAWN_POINT: Vec3 = vec3(-4.0, 50.0, 4.0);
+const DEFAULT_YAW: f32 = consts::FRAC_PI_2;
+// Blocks are meshed centered on their position, but their collision boxes span [position, position + 1).
+// Adding this to a rendered position gives the same point in collision space.
+pub static BLOCK_COLLISION_OFFSET: Vec3 = vec3(0.5, 0.5, 0.5);
 
 pub struct CameraController {
     pub movement_vector: Vec3,
@@ -34,18 +66,96 @@ impl Default for CameraController {
         }
     }
 }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameMode {
+    // Blocks break instantly
+    Creative,
+    // Blocks take time to mine and drop an item
+    Survival,
+}
+
+impl GameMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "creative" | "c" | "1" => Some(GameMode::Creative),
+            "survival" | "s" | "0" => Some(GameMode::Survival),
+            _ => None,
+        }
+    }
+}
+
 pub struct Player {
     pub camera: Camera,
+    pub game_mode: GameMode,
     pub current_chunk: (i32, i32),
+    pub velocity: Vec3,
     pub on_ground: bool,
     pub is_jumping: bool,
+    pub is_sprinting: bool,
+    pub is_crouching: bool,
     pub in_water: bool,
-    pub jump_action_start: Option<Instant>,
+    pub head_in_water: bool,
     pub is_ghost: bool,
-    pub placing_block: BlockType,
+    // Blocks per second while flying
+    pub fly_speed: f32,
+    pub vitals: Vitals,
+    // Eye position where the player respawns
+    pub spawn_point: Vec3,
+    // Max distance at which blocks can be picked
+    pub reach: f32,
+    pub inventory: Inventory,
+    // Whether the mining button is held down
+    pub is_mining: bool,
+    pub mining: Option<MiningProgress>,
     pub facing_block: Option<Arc<RwLock<Block>>>,
     pub facing_face: Option<FaceDirections>,
+    pub selection: Selection,
+    pub clipboard: Option<Clipboard>,
+}
+// Horizontal velocity after accelerating towards the target one for delta_time, the vertical velocity is kept
+pub fn accelerate(velocity: Vec3, target: Vec3, acceleration: f32, delta_time: f32) -> Vec3 {
+    let horizontal = vec3(velocity.x, 0.0, velocity.z);
+    let change = vec3(target.x, 0.0, target.z) - horizontal;
+    let max_change = acceleration * delta_time;
+    let horizontal = if change.length() > max_change {
+        horizontal + change.normalize() * max_change
+    } else {
+        vec3(target.x, 0.0, target.z)
+    };
+    vec3(horizontal.x, velocity.y, horizontal.z)
 }
+
+// Fraction [0, 1] of the box's height that is under water
+pub fn submerged_fraction<F>(collision: &CollisionBox, block_type_at: &F) -> f32
+where
+    F: Fn(Vec3) -> Option<BlockType>,
+{
+    let center = collision.center();
+    let mut submerged = 0.0;
+    for y in f32::floor(collision.min_y) as i32..=f32::floor(collision.max_y) as i32 {
+        let block = vec3(f32::floor(center.x), y as f32, f32::floor(center.z));
+        if block_type_at(block) == Some(BlockType::Water) {
+            let top = f32::min(collision.max_y, y as f32 + 1.0);
+            let bottom = f32::max(collision.min_y, y as f32);
+            submerged += f32::max(top - bottom, 0.0);
+        }
+    }
+    submerged / (collision.max_y - collision.min_y)
+}
+
+// Collision box of a player whose eyes are at the position, in collision space
+pub fn player_collision(eye: Vec3) -> CollisionBox {
+    let feet = eye - vec3(0.0, PLAYER_EYE_HEIGHT, 0.0);
+    CollisionBox::new(
+        feet.x - PLAYER_WIDTH / 2.0,
+        feet.y,
+        feet.z - PLAYER_WIDTH / 2.0,
+        PLAYER_WIDTH,
+        PLAYER_HEIGHT,
+        PLAYER_WIDTH,
+    )
+}
+
 impl Player {
     pub fn update(&mut self) {
         self.camera.queue.write_buffer(
@@ -58,94 +168,65 @@ impl Player {
     pub fn to_relative_position(&self) -> glam::Vec3 {
         todo!();
     }
-    pub fn get_collision(&self) -> crate::collision::CollisionBox {
-        crate::collision::CollisionBox::new(
-            self.camera.eye.x - 0.4,
-            self.camera.eye.y - 1.8,
-            self.camera.eye.z - 0.4,
-            0.8,
-            2.0,
-            0.8,
-        )
+    // Eye position in collision space
+    pub fn get_eye_position(&self) -> Vec3 {
+        self.camera.eye + BLOCK_COLLISION_OFFSET
     }
-    pub fn next_placing_block(&mut self, offset: i32) {
-        // Delta is {1, -1}
-        let placing_block_id = self.placing_block.to_id();
-        let mut next_block_id = (((placing_block_id as i32 + offset)
-            + (BlockType::MAX_ID + 1) as i32)
-            % (BlockType::MAX_ID + 1) as i32);
-
-        if next_block_id == BlockType::Water.to_id() as i32 {
-            next_block_id += offset;
-        }
-
-        self.placing_block = BlockType::from_id(next_block_id as u32);
+    pub fn get_collision(&self) -> CollisionBox {
+        player_collision(self.get_eye_position())
     }
     // Gets the block that the player is facing
-    pub fn get_facing_block(
-        &mut self,
-        blocks: &Vec<Arc<RwLock<Block>>>,
-    ) -> Option<(CollisionBox, FaceDirections)> {
-        let forward = self.camera.get_forward_dir();
-        let mut ray_results: Vec<RayResult> = vec![];
-
-        let ray = crate::collision::Ray {
-            direction: forward,
-            origin: self.camera.eye + PLAYER_VIEW_OFFSET,
-        };
-
-        for block in blocks.iter() {
-            if block.read().unwrap().block_type == BlockType::Water {
-                continue;
-            }
-
-            if let Some(intersection_points) =
-                ray.intersects_box(&block.read().unwrap().collision_box)
-            {
-                ray_results.push(RayResult {
-                    points: intersection_points,
-                    collision: block.read().unwrap().collision_box.clone(),
-                })
-            }
+    pub fn get_facing_block(&self, world: &World) -> Option<BlockHit> {
+        world.raycast(
+            self.get_eye_position(),
+            self.camera.get_forward_dir(),
+            self.reach,
+            false,
+        )
+    }
+    pub fn respawn(&mut self) {
+        self.camera.eye = self.spawn_point;
+        self.velocity = Vec3::ZERO;
+        self.vitals = Vitals::default();
+        self.is_mining = false;
+        self.mining = None;
+    }
+    // Resets the player to a new one whose eyes are at the spawn point
+    pub fn reset(&mut self, spawn_point: Vec3) {
+        (self.camera.eye, self.camera.yaw, self.camera.pitch) = (spawn_point, DEFAULT_YAW, 0.0);
+        self.camera.needs_update = true;
+        (self.vitals, self.spawn_point) = (Vitals::default(), spawn_point);
+        self.inventory = Inventory::starter();
+        self.current_chunk = self.calc_current_chunk();
+        self.set_game_mode(GameMode::Creative);
+        self.velocity = Vec3::ZERO;
+        self.facing_block = None;
+        self.facing_face = None;
+        self.selection = Selection::default();
+        self.clipboard = None;
+    }
+    // Resets the player to the state saved in the open world, or to a new player
+    pub fn load_saved(&mut self) {
+        self.reset(DEFAULT_SPAWN_POINT);
+        if let Ok((eye, yaw, pitch)) = Camera::load(Box::new(())) {
+            (self.camera.eye, self.camera.yaw, self.camera.pitch) = (eye, yaw, pitch);
         }
-
-        let mut block_collision: Option<&CollisionBox> = None;
-        let mut max_distance = f32::MAX;
-        let mut point: Option<Vec3> = None;
-
-        for result in ray_results.iter() {
-            let mut closest_point = result.points[0];
-            if result.points[1].distance(self.camera.eye) < closest_point.distance(self.camera.eye)
-            {
-                closest_point = result.points[1];
-            }
-
-            if closest_point.distance(self.camera.eye) < max_distance {
-                max_distance = closest_point.distance(self.camera.eye);
-                block_collision = Some(&result.collision);
-                point = Some(closest_point);
-            }
+        if let Ok((vitals, spawn_point)) = Player::load(Box::new(())) {
+            (self.vitals, self.spawn_point) = (vitals, spawn_point);
         }
-        let mut face_direction = None;
-
-        match (block_collision, point) {
-            (Some(block_collision), Some(point)) => {
-                // TODO: This can be precomputed
-                let point_dir = ((block_collision.center() - point).normalize()) * -1.0;
-
-                let face_directions = FaceDirections::all();
-                let mut best_dot = -1.0;
-                for face in face_directions.iter() {
-                    let dot = point_dir.dot(face.get_normal_vector());
-                    if dot > best_dot {
-                        best_dot = dot;
-                        face_direction = Some(face);
-                    }
-                }
-                Some((block_collision.clone(), *face_direction.unwrap()))
-            }
-            _ => None,
+        if let Ok(inventory) = Inventory::load(Box::new(())) {
+            self.inventory = inventory;
         }
+        self.current_chunk = self.calc_current_chunk();
+    }
+    pub fn teleport(&mut self, position: Vec3) {
+        self.camera.eye = position;
+        self.velocity = Vec3::ZERO;
+    }
+    pub fn set_game_mode(&mut self, game_mode: GameMode) {
+        self.game_mode = game_mode;
+        self.is_mining = false;
+        self.mining = None;
     }
     pub fn calc_current_chunk(&self) -> (i32, i32) {
         (
@@ -154,104 +235,118 @@ impl Player {
         )
     }
 
-    /* TODO: This probably can be optimized */
-    pub fn move_camera(
-        &mut self,
-        direction: &Vec3,
-        delta_time: f32,
-        blocks: &Vec<Arc<RwLock<Block>>>,
-    ) {
-        let input_direction = direction;
-        let player_collision = self.get_collision();
-
-        let forward = self.camera.get_forward_dir();
-
-        let mut velocity = vec3(0.0, 0.0, 0.0);
-
-        // z axis
-        if input_direction.z > 0.0 {
-            velocity += forward * CAMERA_SPEED * delta_time;
-        } else if input_direction.z < 0.0 {
-            velocity -= forward * CAMERA_SPEED * delta_time;
-        }
-
-        let right = Vec3::cross(forward, Vec3::Y);
-
-        if input_direction.x > 0.0 {
-            velocity -= right * CAMERA_SPEED * delta_time;
-        } else if input_direction.x < 0.0 {
-            velocity += right * CAMERA_SPEED * delta_time;
-        }
+    pub fn move_camera(&mut self, direction: &Vec3, delta_time: f32, world: &World) {
+        let block_type_at = |position: Vec3| world.get_block_type_absolute(&position);
 
         /* Ignore collisions if ghost */
         if self.is_ghost {
-            velocity *= 4.0;
-            self.camera.eye += velocity;
-            return;
+            let forward = self.camera.get_forward_dir();
+            let right = Vec3::cross(forward, Vec3::Y);
+            let movement = forward * direction.z - right * direction.x + Vec3::Y * direction.y;
+            self.velocity = Vec3::ZERO;
+            self.camera.eye += movement * self.fly_speed * delta_time;
+        } else {
+            let mut remaining = f32::min(delta_time, MAX_FRAME_TIME);
+            while remaining > 0.0 {
+                let step = f32::min(remaining, MAX_PHYSICS_STEP);
+                self.physics_step(direction, step, &block_type_at);
+                remaining -= step;
+            }
         }
+        self.update_water_state(&block_type_at);
+        if self.game_mode == GameMode::Survival {
+            self.vitals.update_breath(delta_time, self.head_in_water);
+        }
+    }
+    fn update_water_state<F>(&mut self, block_type_at: &F)
+    where
+        F: Fn(Vec3) -> Option<BlockType>,
+    {
+        self.in_water = submerged_fraction(&self.get_collision(), block_type_at) > 0.0;
+        self.head_in_water =
+            block_type_at(self.get_eye_position().floor()) == Some(BlockType::Water);
+    }
+    // Horizontal velocity the player wants to reach given the input direction
+    fn target_velocity(&self, direction: &Vec3) -> Vec3 {
+        let forward = self.camera.get_forward_dir();
+        let forward = vec3(forward.x, 0.0, forward.z).normalize_or_zero();
+        let right = Vec3::cross(forward, Vec3::Y);
+        // Clamped rather than normalized, so a gamepad stick that isn't fully tilted walks slower
+        let wish_direction = (forward * direction.z - right * direction.x).clamp_length_max(1.0);
+
+        let speed = if self.in_water && !self.on_ground {
+            SWIM_SPEED
+        } else if self.is_crouching {
+            CROUCH_SPEED
+        } else if self.is_sprinting {
+            SPRINT_SPEED
+        } else {
+            WALK_SPEED
+        };
+        wish_direction * speed
+    }
+    fn physics_step<F>(&mut self, direction: &Vec3, delta_time: f32, block_type_at: &F)
+    where
+        F: Fn(Vec3) -> Option<BlockType>,
+    {
+        let is_solid = |position: Vec3| block_type_at(position).is_some_and(|b| b.is_solid());
+        self.update_water_state(block_type_at);
+
+        let target = self.target_velocity(direction);
+        let acceleration = if self.in_water {
+            SWIM_ACCELERATION
+        } else if self.on_ground {
+            GROUND_ACCELERATION
+        } else {
+            AIR_ACCELERATION
+        };
+        self.velocity = accelerate(self.velocity, target, acceleration, delta_time);
 
-        self.in_water = false;
-        let can_move_z = player_collision.clone() + glam::vec3(0.0, 0.0, velocity.z);
-        let can_move_x = player_collision.clone() + glam::vec3(velocity.x, 0.0, 0.0);
-
-        for block in blocks.iter() {
-            let block_read = block.read().unwrap();
-            if can_move_z.intersects(&block_read.collision_box)
-                && block_read.block_type != BlockType::Water
-            {
-                velocity.z = 0.0;
+        if self.in_water {
+            let submerged = submerged_fraction(&self.get_collision(), block_type_at);
+            self.velocity.y += (BUOYANCY * submerged - GRAVITY) * delta_time;
+            if self.is_jumping {
+                self.velocity.y += SWIM_VERTICAL_ACCELERATION * delta_time;
             }
-
-            if can_move_x.intersects(&block_read.collision_box)
-                && block_read.block_type != BlockType::Water
-            {
-                velocity.x = 0.0;
+            if self.is_crouching {
+                self.velocity.y -= SWIM_VERTICAL_ACCELERATION * delta_time;
             }
-
-            if player_collision.intersects(&block_read.collision_box)
-                && block_read.block_type == BlockType::Water
-            {
-                self.in_water = true;
+            self.velocity *= f32::max(1.0 - WATER_DRAG * delta_time, 0.0);
+        } else {
+            if self.is_jumping && self.on_ground {
+                self.velocity.y = JUMP_VELOCITY;
             }
+            self.velocity.y -= GRAVITY * delta_time;
         }
+        self.velocity.y = f32::max(self.velocity.y, -TERMINAL_VELOCITY);
 
-        velocity.y -= GRAVITY * delta_time;
-        self.on_ground = false;
+        let collision = self.get_collision();
+        let delta = self.velocity * delta_time;
+        // Try to step up on blocks that are at most STEP_HEIGHT high
+        let result = if self.on_ground {
+            collision::sweep_with_step(&collision, delta, STEP_HEIGHT, &is_solid)
+        } else {
+            collision::sweep(&collision, delta, &is_solid)
+        };
 
-        if self.in_water {
-            // Slow down gravity in water
-            velocity.y *= 0.7;
+        self.on_ground = result.blocked.y && delta.y <= 0.0;
+        if self.on_ground && delta.y < 0.0 && !self.in_water && self.game_mode == GameMode::Survival
+        {
+            self.vitals.damage(vitals::fall_damage(-self.velocity.y));
         }
-        if self.is_jumping {
-            let now = Instant::now();
-            let delta_jump = now
-                - self
-                    .jump_action_start
-                    .expect("If it's jumping this should be set");
-            if delta_jump <= *JUMP_DURATION {
-                velocity.y = JUMP_HEIGHT * delta_time * 10.0; /* Multiply by 10 bcs animation time is 0.1  */
-            } else {
-                self.is_jumping = false;
-                self.jump_action_start = None;
-            }
+        let against_wall = result.blocked.x || result.blocked.z;
+        if self.in_water && self.is_jumping && against_wall {
+            self.velocity.y = f32::max(self.velocity.y, WATER_EXIT_VELOCITY);
+        } else if result.blocked.y {
+            self.velocity.y = 0.0;
         }
-
-        let can_move_y = player_collision.clone() + glam::vec3(0.0, velocity.y, 0.0);
-        for block in blocks.iter() {
-            if can_move_y.intersects(&block.read().unwrap().collision_box)
-                && block.read().unwrap().block_type != BlockType::Water
-            {
-                velocity.y = 0.0;
-                self.on_ground = true; // This can make it infinite to jump if there is a block above
-            }
+        if result.blocked.x {
+            self.velocity.x = 0.0;
         }
-
-        // fly up
-        if input_direction.y > 0.0 {
-            velocity.y = 2.0;
+        if result.blocked.z {
+            self.velocity.z = 0.0;
         }
-
-        self.camera.eye += velocity;
+        self.camera.eye += result.movement;
     }
 }
 pub struct Camera {
@@ -277,11 +372,8 @@ impl Camera {
         device: Arc<wgpu::Device>,
         queue: Arc<wgpu::Queue>,
     ) -> Camera {
-        let (eye, yaw, pitch) = if let Ok((eye, yaw, pitch)) = Camera::load(Box::new(())) {
-            (eye, yaw, pitch)
-        } else {
-            (glam::vec3(-4.0, 50.0, 4.0), consts::FRAC_PI_2, 0.0)
-        };
+        // Moved to the saved position by Player::load_saved when a world is opened
+        let (eye, yaw, pitch) = (DEFAULT_SPAWN_POINT, DEFAULT_YAW, 0.0);
 
         let position_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             contents: bytemuck::cast_slice(&[eye]),
@@ -356,26 +448,51 @@ impl Camera {
     }
 }
 
-impl Saveable<glam::Vec3> for Camera {
+impl Saveable<Player> for Player {
     fn save(&self) -> Result<(), Box<dyn Error>> {
-        if std::fs::create_dir("data").is_ok() {
-            println!("Created dir");
-        }
+        persistence::create_world_dir()?;
+        let camera = &self.camera;
         let data = format!(
-            "{},{},{},{},{}",
-            self.eye.x, self.eye.y, self.eye.z, self.yaw, self.pitch
+            "{},{},{},{},{},{},{},{},{},{}",
+            camera.eye.x,
+            camera.eye.y,
+            camera.eye.z,
+            camera.yaw,
+            camera.pitch,
+            self.vitals.health,
+            self.vitals.breath,
+            self.spawn_point.x,
+            self.spawn_point.y,
+            self.spawn_point.z
         );
 
-        let player_file_name = "data/player";
-        std::fs::write(player_file_name, data.as_bytes())?;
+        std::fs::write(persistence::world_path("player"), data.as_bytes())?;
 
         Ok(())
     }
 }
 
+// Loads the vitals and spawn point, the position is loaded by the camera
+impl Loadable<(Vitals, Vec3)> for Player {
+    fn load(_: Box<dyn Any>) -> Result<(Vitals, Vec3), Box<dyn Error>> {
+        let data = String::from_utf8(std::fs::read(persistence::world_path("player"))?)?;
+        let values = data
+            .trim()
+            .split(',')
+            .skip(5)
+            .map(|value| value.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()?;
+        let [health, breath, x, y, z] = values[..] else {
+            return Err("Missing player vitals".into());
+        };
+
+        Ok((Vitals::new(health, breath), glam::vec3(x, y, z)))
+    }
+}
+
 impl Loadable<(glam::Vec3, f32, f32)> for Camera {
     fn load(_: Box<dyn Any>) -> Result<(Vec3, f32, f32), Box<dyn Error>> {
-        let data = String::from_utf8(std::fs::read("data/player")?)?;
+        let data = String::from_utf8(std::fs::read(persistence::world_path("player"))?)?;
         let mut data = data.split(',');
         let x = data.next().unwrap().parse::<f32>().unwrap();
         let y = data.next().unwrap().parse::<f32>().unwrap();
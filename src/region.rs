@@ -0,0 +1,231 @@
+use glam::Vec3;
+
+use crate::blocks::block_type::BlockType;
+
+// Largest selection a region command edits, bigger ones would stall the game
+pub const MAX_VOLUME: usize = 64 * 64 * 64;
+
+// A block to set at an absolute position, None is air
+pub type BlockChange = (Vec3, Option<BlockType>);
+
+// Bulk edits on the selected region or the clipboard
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegionCommand {
+    Fill(Option<BlockType>),
+    Replace(Option<BlockType>, Option<BlockType>),
+    Hollow(BlockType),
+    Walls(BlockType),
+    Copy,
+    // Pastes with the clipboard's min corner at the position
+    Paste(Vec3),
+    Rotate(i32),
+    Mirror(usize),
+}
+
+// Box of blocks between two corners, both included
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Region {
+    pub fn new(corner_a: Vec3, corner_b: Vec3) -> Self {
+        Self {
+            min: corner_a.min(corner_b).floor(),
+            max: corner_a.max(corner_b).floor(),
+        }
+    }
+    // Number of blocks on each axis
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min + Vec3::ONE
+    }
+    pub fn volume(&self) -> usize {
+        let size = self.size();
+        (size.x * size.y * size.z) as usize
+    }
+    pub fn positions(&self) -> impl Iterator<Item = Vec3> + '_ {
+        let (min, max) = (self.min, self.max);
+        (min.x as i32..=max.x as i32).flat_map(move |x| {
+            (min.y as i32..=max.y as i32).flat_map(move |y| {
+                (min.z as i32..=max.z as i32).map(move |z| glam::vec3(x as f32, y as f32, z as f32))
+            })
+        })
+    }
+    fn is_on_shell(&self, position: Vec3) -> bool {
+        position.cmpeq(self.min).any() || position.cmpeq(self.max).any()
+    }
+    fn is_on_walls(&self, position: Vec3) -> bool {
+        position.x == self.min.x
+            || position.x == self.max.x
+            || position.z == self.min.z
+            || position.z == self.max.z
+    }
+    pub fn fill(&self, block_type: Option<BlockType>) -> Vec<BlockChange> {
+        self.positions().map(|p| (p, block_type)).collect()
+    }
+    // Sets the outer shell to the block type and empties the inside
+    pub fn hollow(&self, block_type: BlockType) -> Vec<BlockChange> {
+        self.positions()
+            .map(|p| (p, self.is_on_shell(p).then_some(block_type)))
+            .collect()
+    }
+    // Sets only the four vertical sides, the inside is left untouched
+    pub fn walls(&self, block_type: BlockType) -> Vec<BlockChange> {
+        self.positions()
+            .filter(|p| self.is_on_walls(*p))
+            .map(|p| (p, Some(block_type)))
+            .collect()
+    }
+    pub fn replace<F>(
+        &self,
+        from: Option<BlockType>,
+        to: Option<BlockType>,
+        block_at: F,
+    ) -> Vec<BlockChange>
+    where
+        F: Fn(Vec3) -> Option<BlockType>,
+    {
+        self.positions()
+            .filter(|p| block_at(*p) == from)
+            .map(|p| (p, to))
+            .collect()
+    }
+    pub fn copy<F>(&self, block_at: F) -> Clipboard
+    where
+        F: Fn(Vec3) -> Option<BlockType>,
+    {
+        Clipboard {
+            size: self.size(),
+            blocks: self
+                .positions()
+                .map(|p| (p - self.min, block_at(p)))
+                .collect(),
+        }
+    }
+}
+
+// The two corners picked with the selection tool
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Selection {
+    pub first: Option<Vec3>,
+    pub second: Option<Vec3>,
+}
+
+impl Selection {
+    // A single corner selects just that block
+    pub fn region(&self) -> Option<Region> {
+        match (self.first, self.second) {
+            (Some(a), Some(b)) => Some(Region::new(a, b)),
+            (Some(a), None) | (None, Some(a)) => Some(Region::new(a, a)),
+            (None, None) => None,
+        }
+    }
+    // The selected region, if it's small enough to be edited
+    pub fn editable_region(&self) -> Result<Region, String> {
+        let region = self.region().ok_or("No region selected")?;
+        if region.volume() > MAX_VOLUME {
+            return Err(format!(
+                "The selection has {} blocks, the most is {MAX_VOLUME}",
+                region.volume()
+            ));
+        }
+        Ok(region)
+    }
+}
+
+// Copied blocks, positions are relative to the region's min corner
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    pub size: Vec3,
+    pub blocks: Vec<BlockChange>,
+}
+
+impl Clipboard {
+    // Rotates clockwise around the y axis by 90 degrees per turn
+    pub fn rotate(&mut self, quarter_turns: i32) {
+        for _ in 0..quarter_turns.rem_euclid(4) {
+            let size = self.size;
+            for (offset, _) in self.blocks.iter_mut() {
+                *offset = glam::vec3(size.z - 1.0 - offset.z, offset.y, offset.x);
+            }
+            self.size = glam::vec3(size.z, size.y, size.x);
+        }
+    }
+    // Flips the blocks along the axis (0: x, 1: y, 2: z)
+    pub fn mirror(&mut self, axis: usize) {
+        let size = self.size;
+        for (offset, _) in self.blocks.iter_mut() {
+            offset[axis] = size[axis] - 1.0 - offset[axis];
+        }
+    }
+    // Changes needed to paste with the min corner at the origin
+    pub fn paste(&self, origin: Vec3) -> Vec<BlockChange> {
+        let origin = origin.floor();
+        self.blocks
+            .iter()
+            .map(|(offset, block_type)| (origin + *offset, *block_type))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Region, Selection, MAX_VOLUME};
+    use crate::blocks::block_type::BlockType;
+
+    #[test]
+    fn should_normalize_the_corners() {
+        let region = Region::new(glam::vec3(3.0, 0.0, -2.0), glam::vec3(1.0, 2.0, 1.0));
+        assert_eq!(region.min, glam::vec3(1.0, 0.0, -2.0));
+        assert_eq!(region.max, glam::vec3(3.0, 2.0, 1.0));
+        assert_eq!(region.volume(), 3 * 3 * 4);
+        assert_eq!(region.positions().count(), region.volume());
+    }
+
+    #[test]
+    fn should_build_hollow_boxes_and_walls() {
+        let region = Region::new(glam::Vec3::ZERO, glam::vec3(2.0, 2.0, 2.0));
+        let hollow = region.hollow(BlockType::Stone);
+        assert_eq!(hollow.iter().filter(|(_, b)| b.is_none()).count(), 1);
+        assert_eq!(region.walls(BlockType::Stone).len(), 8 * 3);
+    }
+
+    #[test]
+    fn should_rotate_and_mirror_the_clipboard() {
+        let region = Region::new(glam::Vec3::ZERO, glam::vec3(2.0, 0.0, 0.0));
+        let mut clipboard = region.copy(|p| (p.x == 0.0).then_some(BlockType::Wood));
+        clipboard.rotate(1);
+        assert_eq!(clipboard.size, glam::vec3(1.0, 1.0, 3.0));
+        let wood = clipboard.blocks.iter().find(|(_, b)| b.is_some()).unwrap();
+        assert_eq!(wood.0, glam::vec3(0.0, 0.0, 0.0));
+
+        clipboard.mirror(2);
+        let wood = clipboard.blocks.iter().find(|(_, b)| b.is_some()).unwrap();
+        assert_eq!(wood.0, glam::vec3(0.0, 0.0, 2.0));
+
+        let pasted = clipboard.paste(glam::vec3(10.0, 5.0, 10.0));
+        assert!(pasted.contains(&(glam::vec3(10.0, 5.0, 12.0), Some(BlockType::Wood))));
+    }
+
+    #[test]
+    fn should_select_a_single_block_with_one_corner() {
+        let selection = Selection {
+            first: Some(glam::vec3(1.0, 2.0, 3.0)),
+            second: None,
+        };
+        assert_eq!(selection.region().unwrap().volume(), 1);
+    }
+
+    #[test]
+    fn should_refuse_selections_too_big_to_edit() {
+        let mut selection = Selection {
+            first: Some(glam::Vec3::ZERO),
+            second: Some(glam::vec3(63.0, 63.0, 63.0)),
+        };
+        assert_eq!(selection.editable_region().unwrap().volume(), MAX_VOLUME);
+        selection.second = Some(glam::vec3(63.0, 64.0, 63.0));
+        assert!(selection.editable_region().is_err());
+        assert!(Selection::default().editable_region().is_err());
+    }
+}
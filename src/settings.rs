@@ -0,0 +1,342 @@
+use std::any::Any;
+use std::error::Error;
+
+use crate::input::{Action, Bindings};
+use crate::net::protocol::MAX_NAME_LENGTH;
+use crate::net::DEFAULT_PORT;
+use crate::persistence::{Loadable, Saveable};
+
+// Shared by every world, unlike the saves in persistence::WORLDS_DIR
+const SETTINGS_FILE: &str = "data/settings";
+// Prefix of the keys binding an action, e.g. "bind.jump = Space"
+const BINDING_PREFIX: &str = "bind.";
+const PLAYER_NAME_KEY: &str = "player_name";
+const SERVER_ADDRESS_KEY: &str = "server_address";
+
+pub const RENDER_DISTANCE_RANGE: (u32, u32) = (2, 16);
+pub const FOV_RANGE: (f32, f32) = (30.0, 110.0);
+pub const MOUSE_SENSITIVITY_RANGE: (f32, f32) = (0.1, 5.0);
+pub const FLY_SPEED_RANGE: (f32, f32) = (1.0, 100.0);
+pub const STICK_DEAD_ZONE_RANGE: (f32, f32) = (0.0, 0.5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    RenderDistance,
+    Fov,
+    MouseSensitivity,
+    FlySpeed,
+    StickDeadZone,
+    Vsync,
+    DebugOverlay,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 7] = [
+        Setting::RenderDistance,
+        Setting::Fov,
+        Setting::MouseSensitivity,
+        Setting::FlySpeed,
+        Setting::StickDeadZone,
+        Setting::Vsync,
+        Setting::DebugOverlay,
+    ];
+    // Key in the settings file
+    pub fn key(&self) -> &'static str {
+        match self {
+            Setting::RenderDistance => "render_distance",
+            Setting::Fov => "fov",
+            Setting::MouseSensitivity => "mouse_sensitivity",
+            Setting::FlySpeed => "fly_speed",
+            Setting::StickDeadZone => "stick_dead_zone",
+            Setting::Vsync => "vsync",
+            Setting::DebugOverlay => "debug_overlay",
+        }
+    }
+    pub fn label(&self) -> &'static str {
+        match self {
+            Setting::RenderDistance => "Render distance",
+            Setting::Fov => "FOV",
+            Setting::MouseSensitivity => "Sensitivity",
+            Setting::FlySpeed => "Fly speed",
+            Setting::StickDeadZone => "Stick dead zone",
+            Setting::Vsync => "V-Sync",
+            Setting::DebugOverlay => "Debug overlay",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    // Chunks loaded from the player's chunk to the edge, in every direction
+    pub render_distance: u32,
+    // Vertical field of view in degrees
+    pub fov: f32,
+    // Multiplier of the base mouse sensitivity
+    pub mouse_sensitivity: f32,
+    // Blocks per second while flying
+    pub fly_speed: f32,
+    // Part of the gamepad sticks' travel, from the center, that is ignored
+    pub stick_dead_zone: f32,
+    // Fifo presentation when on, frames aren't capped otherwise
+    pub vsync: bool,
+    pub debug_overlay: bool,
+    pub bindings: Bindings,
+    // Shown to the other players of a server
+    pub player_name: String,
+    // Last server joined, filled in on the join server screen
+    pub server_address: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            render_distance: 10,
+            fov: 45.0,
+            mouse_sensitivity: 1.0,
+            fly_speed: 40.0,
+            stick_dead_zone: 0.15,
+            vsync: true,
+            debug_overlay: false,
+            bindings: Bindings::default(),
+            player_name: "Player".to_string(),
+            server_address: format!("127.0.0.1:{DEFAULT_PORT}"),
+        }
+    }
+}
+
+impl Settings {
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        if self.vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        }
+    }
+    pub fn chunks_per_row(&self) -> u32 {
+        self.render_distance * 2
+    }
+    pub fn value(&self, setting: Setting) -> String {
+        match setting {
+            Setting::RenderDistance => self.render_distance.to_string(),
+            Setting::Fov => format!("{:.0}", self.fov),
+            Setting::MouseSensitivity => format!("{:.1}", self.mouse_sensitivity),
+            Setting::FlySpeed => format!("{:.0}", self.fly_speed),
+            Setting::StickDeadZone => format!("{:.2}", self.stick_dead_zone),
+            Setting::Vsync => on_off(self.vsync).to_string(),
+            Setting::DebugOverlay => on_off(self.debug_overlay).to_string(),
+        }
+    }
+    // Changes the setting by steps increments, toggles flip on any step
+    pub fn adjust(&mut self, setting: Setting, steps: i32) {
+        let steps = steps as f32;
+        match setting {
+            Setting::RenderDistance => {
+                let (min, max) = RENDER_DISTANCE_RANGE;
+                self.render_distance = (self.render_distance as i32 + steps as i32)
+                    .clamp(min as i32, max as i32) as u32
+            }
+            Setting::Fov => self.fov = (self.fov + steps * 5.0).clamp(FOV_RANGE.0, FOV_RANGE.1),
+            Setting::MouseSensitivity => {
+                let (min, max) = MOUSE_SENSITIVITY_RANGE;
+                // Rounded so that repeated steps don't accumulate float errors
+                self.mouse_sensitivity =
+                    ((self.mouse_sensitivity + steps * 0.1) * 10.0).round() / 10.0;
+                self.mouse_sensitivity = self.mouse_sensitivity.clamp(min, max);
+            }
+            Setting::FlySpeed => {
+                self.fly_speed =
+                    (self.fly_speed + steps * 5.0).clamp(FLY_SPEED_RANGE.0, FLY_SPEED_RANGE.1)
+            }
+            Setting::StickDeadZone => {
+                let (min, max) = STICK_DEAD_ZONE_RANGE;
+                self.stick_dead_zone =
+                    ((self.stick_dead_zone + steps * 0.05) * 100.0).round() / 100.0;
+                self.stick_dead_zone = self.stick_dead_zone.clamp(min, max);
+            }
+            Setting::Vsync => self.vsync = !self.vsync,
+            Setting::DebugOverlay => self.debug_overlay = !self.debug_overlay,
+        }
+    }
+    // Sets the setting from its value in the settings file
+    pub fn set(&mut self, setting: Setting, value: &str) -> Result<(), Box<dyn Error>> {
+        match setting {
+            Setting::RenderDistance => {
+                let (min, max) = RENDER_DISTANCE_RANGE;
+                self.render_distance = value.parse::<u32>()?.clamp(min, max);
+            }
+            Setting::Fov => self.fov = parse_in_range(value, FOV_RANGE)?,
+            Setting::MouseSensitivity => {
+                self.mouse_sensitivity = parse_in_range(value, MOUSE_SENSITIVITY_RANGE)?
+            }
+            Setting::FlySpeed => self.fly_speed = parse_in_range(value, FLY_SPEED_RANGE)?,
+            Setting::StickDeadZone => {
+                self.stick_dead_zone = parse_in_range(value, STICK_DEAD_ZONE_RANGE)?
+            }
+            Setting::Vsync => self.vsync = value.parse()?,
+            Setting::DebugOverlay => self.debug_overlay = value.parse()?,
+        }
+        Ok(())
+    }
+    // One "key = value" line per setting, then one per action with its comma separated bindings
+    pub fn serialize(&self) -> String {
+        let settings = Setting::ALL
+            .iter()
+            .map(|setting| {
+                let value = match setting {
+                    Setting::RenderDistance => self.render_distance.to_string(),
+                    Setting::Fov => self.fov.to_string(),
+                    Setting::MouseSensitivity => self.mouse_sensitivity.to_string(),
+                    Setting::FlySpeed => self.fly_speed.to_string(),
+                    Setting::StickDeadZone => self.stick_dead_zone.to_string(),
+                    Setting::Vsync => self.vsync.to_string(),
+                    Setting::DebugOverlay => self.debug_overlay.to_string(),
+                };
+                format!("{} = {}\n", setting.key(), value)
+            })
+            .collect::<String>();
+        let bindings = Action::all()
+            .into_iter()
+            .map(|action| {
+                format!(
+                    "{}{} = {}\n",
+                    BINDING_PREFIX,
+                    action.key(),
+                    self.bindings.describe(action)
+                )
+            })
+            .collect::<String>();
+        let multiplayer = format!(
+            "{PLAYER_NAME_KEY} = {}\n{SERVER_ADDRESS_KEY} = {}\n",
+            self.player_name, self.server_address
+        );
+        settings + &multiplayer + &bindings
+    }
+    // Unknown keys and invalid values are skipped, keeping their defaults
+    pub fn deserialize(data: &str) -> Settings {
+        let mut settings = Settings::default();
+        for line in data.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                eprintln!("Invalid settings line: {line}");
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+            if let Some(action) = key.strip_prefix(BINDING_PREFIX) {
+                match Action::from_key(action) {
+                    Some(action) => settings.bindings.set(action, Bindings::parse(value)),
+                    None => eprintln!("Unknown action {action}"),
+                }
+                continue;
+            }
+            if key == PLAYER_NAME_KEY && !value.is_empty() && value.len() <= MAX_NAME_LENGTH {
+                settings.player_name = value.to_string();
+                continue;
+            }
+            if key == SERVER_ADDRESS_KEY {
+                settings.server_address = value.to_string();
+                continue;
+            }
+            let Some(setting) = Setting::ALL.iter().find(|setting| setting.key() == key) else {
+                continue;
+            };
+            if let Err(err) = settings.set(*setting, value) {
+                eprintln!("Invalid value for {key}: {err}");
+            }
+        }
+        settings
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+impl Saveable<Settings> for Settings {
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        std::fs::create_dir_all("data")?;
+        std::fs::write(SETTINGS_FILE, self.serialize().as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl Loadable<Settings> for Settings {
+    fn load(_: Box<dyn Any>) -> Result<Settings, Box<dyn Error>> {
+        let data = String::from_utf8(std::fs::read(SETTINGS_FILE)?)?;
+        Ok(Settings::deserialize(&data))
+    }
+}
+
+// Clamped to the range, NaN and infinities are refused as clamping would keep them
+fn parse_in_range(value: &str, (min, max): (f32, f32)) -> Result<f32, Box<dyn Error>> {
+    let value = value.parse::<f32>()?;
+    if !value.is_finite() {
+        return Err(format!("Invalid number {value}").into());
+    }
+    Ok(value.clamp(min, max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamepad::GamepadButton;
+    use crate::input::Binding;
+    use winit::event::MouseButton;
+    use winit::keyboard::KeyCode;
+
+    #[test]
+    fn serialization_round_trip() {
+        let mut settings = Settings::default();
+        settings.adjust(Setting::RenderDistance, 3);
+        settings.adjust(Setting::MouseSensitivity, -2);
+        settings.adjust(Setting::Vsync, 1);
+        settings.player_name = "Alex".to_string();
+        settings.server_address = "example.com".to_string();
+
+        let loaded = Settings::deserialize(&settings.serialize());
+        assert_eq!(loaded, settings);
+        assert_eq!(loaded.render_distance, 13);
+        assert_eq!(loaded.mouse_sensitivity, 0.8);
+        assert!(!loaded.vsync);
+    }
+
+    #[test]
+    fn bindings_round_trip() {
+        let mut settings = Settings::default();
+        settings
+            .bindings
+            .bind(Action::Jump, Binding::Mouse(MouseButton::Middle), false)
+            .unwrap();
+        settings.bindings.clear(Action::Mirror);
+
+        let loaded = Settings::deserialize(&settings.serialize());
+        assert_eq!(loaded.bindings, settings.bindings);
+        assert!(loaded.bindings.get(Action::Mirror).is_empty());
+        assert_eq!(
+            loaded.bindings.get(Action::Jump),
+            [
+                Binding::Key(KeyCode::Space),
+                Binding::Gamepad(GamepadButton::South),
+                Binding::Mouse(MouseButton::Middle)
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_values_keep_the_defaults() {
+        let settings =
+            Settings::deserialize("fov = wide\nrender_distance = 100\nunknown = 1\nnonsense");
+        assert_eq!(settings.fov, Settings::default().fov);
+        assert_eq!(settings.render_distance, RENDER_DISTANCE_RANGE.1);
+        let settings = Settings::deserialize("fov = NaN\nfly_speed = inf\nmouse_sensitivity = 1e9");
+        assert_eq!(settings.fov, Settings::default().fov);
+        assert_eq!(settings.fly_speed, Settings::default().fly_speed);
+        assert_eq!(settings.mouse_sensitivity, MOUSE_SENSITIVITY_RANGE.1);
+    }
+}
@@ -0,0 +1,67 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+
+@group(0) @binding(0)
+var<uniform> projection: mat4x4<f32>;
+@group(0) @binding(1)
+var<uniform> view: mat4x4<f32>;
+@group(1) @binding(0)
+var<uniform> stage: f32;
+
+const STAGES = 10.0;
+// Cells per face side
+const CELLS = 3.0;
+const CRACK_WIDTH = 0.06;
+
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = projection * view * vec4<f32>(in.position, 1.0);
+    out.uv = in.uv;
+    return out;
+}
+
+fn hash(p: vec2<f32>) -> vec2<f32> {
+    let q = vec2<f32>(dot(p, vec2<f32>(127.1, 311.7)), dot(p, vec2<f32>(269.5, 183.3)));
+    return fract(sin(q) * 43758.5453);
+}
+
+// Cracks are the edges of a voronoi pattern, more cells crack open at every stage
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = in.uv * CELLS;
+    let cell = floor(uv);
+    let local = fract(uv);
+
+    var closest = 8.0;
+    var second = 8.0;
+    var closest_cell = cell;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbour = cell + vec2<f32>(f32(x), f32(y));
+            let d = distance(local, neighbour - cell + hash(neighbour));
+            if d < closest {
+                second = closest;
+                closest = d;
+                closest_cell = neighbour;
+            } else if d < second {
+                second = d;
+            }
+        }
+    }
+
+    let is_open = hash(closest_cell + 17.0).x < (stage + 1.0) / STAGES;
+    if !is_open || second - closest > CRACK_WIDTH {
+        discard;
+    }
+    return vec4<f32>(0.0, 0.0, 0.0, 0.7);
+}
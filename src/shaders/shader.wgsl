@@ -36,7 +36,8 @@ var diffuse: texture_2d<f32>;
 @group(0) @binding(4)
 var t_sampler: sampler;
 @group(1) @binding(0)
-var <uniform> current_chunk: vec2<i32>;
+// Offset of each chunk, indexed by the instance the chunk is drawn as
+var <storage, read> chunk_offsets: array<vec2<i32>>;
 @group(2) @binding(0)
 var <uniform> player_position: vec3<f32>;
 
@@ -46,6 +47,7 @@ fn vs_main(in: VertexInput, instance_data: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
 
 
+    let current_chunk = chunk_offsets[instance_data.instance_index];
     let chunk_offset = vec3<f32>(f32(current_chunk.x) * 16.0, 0.0, f32(current_chunk.y) * 16.0);
     let block_position = in.position + chunk_offset;
 
@@ -1,16 +1,16 @@
 struct VertexInput {
-    @builtin(vertex_index) vertex_index: u32,
     @location(0) position: vec2<f32>,
-    @location(1) uv: vec2<f32>
-
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) mode: u32,
 }
 
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) pos: vec2<f32>,
-    @location(1) uv: vec2<f32>,
-
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) @interpolate(flat) mode: u32,
 }
 
 
@@ -19,41 +19,38 @@ var diffuse: texture_2d<f32>;
 @group(0) @binding(4)
 var t_sampler: sampler;
 @group(1) @binding(0)
-var<uniform> resolution: vec2<f32>;
+var font: texture_2d<f32>;
 @group(1) @binding(1)
-var<uniform> blockid: u32;
+var font_sampler: sampler;
+
+// Must match the modes in hud.rs
+const MODE_BLOCK_ATLAS = 1u;
+const MODE_FONT = 2u;
 
 
 @vertex
 fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     out.clip_position = vec4<f32>(in.position, 0.0, 1.0);
-    out.pos = vec2<f32>(in.position);
     out.uv = in.uv;
+    out.color = in.color;
+    out.mode = in.mode;
 
     return out;
 }
 
 
-struct FragmentInput {
-        @builtin(position) clip_position: vec4<f32>,
-        @location(0) pos: vec2<f32>,
-        @location(1) uv: vec2<f32>,
-}
-
-
 @fragment
-fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
-    var color: vec4<f32>;
-    // Normalize in range 0->1
-
-    color = textureSample(diffuse, t_sampler, in.uv);
-    // let norm = (in.pos + 1.0) * 0.5;
-    // let coords = norm * vec3<f32>(resolution, 1.0);
-
-    // let v = distance(coords, vec3<f32>(500.0, 500.0, 1.0));
-
-    // color = vec4<f32>(vec3<f32>(v), 1.0);
-
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Sampled outside of the branches, as sampling needs uniform control flow
+    let block_color = textureSample(diffuse, t_sampler, in.uv);
+    let glyph_alpha = textureSample(font, font_sampler, in.uv).a;
+
+    var color = in.color;
+    if in.mode == MODE_BLOCK_ATLAS {
+        color *= block_color;
+    } else if in.mode == MODE_FONT {
+        color.a *= glyph_alpha;
+    }
     return color;
 }
@@ -0,0 +1,36 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+struct UnderwaterUniforms {
+    color: vec4<f32>,
+    znear: f32,
+    zfar: f32,
+    density: f32,
+}
+
+@group(0) @binding(0)
+var depth_texture: texture_depth_2d;
+@group(0) @binding(1)
+var<uniform> uniforms: UnderwaterUniforms;
+
+
+// Fullscreen triangle, no vertex buffer needed
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    let uv = vec2<f32>(f32((vertex_index << 1u) & 2u), f32(vertex_index & 2u));
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    return out;
+}
+
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let depth = textureLoad(depth_texture, vec2<i32>(in.clip_position.xy), 0);
+    // Undo the perspective projection to get the view space distance
+    let distance = uniforms.znear * uniforms.zfar / (uniforms.zfar - depth * (uniforms.zfar - uniforms.znear));
+    let fog = 1.0 - exp(-distance * uniforms.density);
+
+    return vec4<f32>(uniforms.color.rgb, max(uniforms.color.a, fog));
+}
@@ -1,26 +1,56 @@
+use glam::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::error::Error;
 use std::sync::Arc;
 use std::sync::{Mutex, RwLock};
+use std::thread::JoinHandle;
 use winit::event::MouseButton;
 use winit::{
     dpi::PhysicalSize,
     event::KeyEvent,
-    keyboard::{KeyCode, PhysicalKey},
-    window::Window,
+    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
+    window::{CursorGrabMode, Window},
 };
 
 use crate::blocks::block::Block;
-use crate::blocks::block_type::BlockType;
-use crate::persistence::Saveable;
+use crate::commands;
+use crate::console::Console;
+use crate::debug::FrameStats;
+use crate::entity::mob::NearbyPlayer;
+use crate::entity::EntityKind;
+use crate::gamepad::{GamepadButton, GamepadEvent, GamepadState};
+use crate::hud::{self, HudLayout, Rect};
+use crate::input::{Action, Binding, Bindings, InputState};
+use crate::inventory::Inventory;
+use crate::menu::{Button, Menu, MenuAction, Screen};
+use crate::mesh_pool::DrawMode;
+use crate::mining::MiningProgress;
+use crate::net::client::{Connection, RemotePlayer, Welcome};
+use crate::net::protocol::{ClientMessage, ServerMessage};
+use crate::persistence::{self, Loadable, Saveable};
 use crate::pipelines::pipeline_manager::PipelineManager;
 use crate::pipelines::Pipeline;
+use crate::region::{RegionCommand, Selection};
+use crate::settings::{Setting, Settings};
 use crate::utils::{ChunkFromPosition, RelativeFromAbsolute};
+use crate::vitals::Vitals;
 use crate::{
     material::Texture,
     pipeline::Uniforms,
-    player::{Camera, CameraController, Player},
-    world::World,
+    player::{
+        Camera, CameraController, GameMode, Player, BLOCK_COLLISION_OFFSET, DEFAULT_SPAWN_POINT,
+        PLAYER_REACH,
+    },
+    world::{PlaceBlockError, World},
 };
 
+// Mouse pixels per second that a gamepad's look stick at full tilt turns the camera by
+const STICK_LOOK_SPEED: f32 = 2500.0;
+// Seconds between the position updates sent to a server
+const POSITION_SEND_INTERVAL: f32 = 1.0 / 20.0;
+// Chunks requested from a server at once, the nearest missing ones first
+const MAX_PENDING_CHUNKS: usize = 16;
+
 pub struct State {
     pub surface: wgpu::Surface,
     pub instance: wgpu::Instance,
@@ -33,14 +63,39 @@ pub struct State {
     pub player: Arc<RwLock<Player>>,
     pub world: World,
     pub camera_controller: CameraController,
+    pub frame_stats: FrameStats,
+    pub settings: Settings,
+    // Keys and buttons held down in game
+    pub input: InputState,
+    pub gamepad: GamepadState,
+    pub console: Console,
+    // Whether items were left on the ground last frame because they didn't fit in the inventory
+    inventory_full: bool,
+    pub menu: Menu,
+    // Name of the open world, or address of the server, None on the main menu
+    pub world_name: Option<String>,
+    // Set while playing on a server, which owns the world
+    pub connection: Option<Connection>,
+    // Connection being made on its own thread, so the window doesn't freeze until the server answers
+    joining: Option<JoinHandle<Result<(Connection, Welcome), String>>>,
+    // Other players of the server, by id
+    pub remote_players: HashMap<u32, RemotePlayer>,
+    // Seconds until the player's position is sent again
+    position_send_timer: f32,
+    // In window pixels, used to click the menu buttons
+    pub cursor_position: Vec2,
+    // Set by the quit button, the event loop exits when it sees it
+    pub exit_requested: bool,
 }
 
 impl State {
     pub async fn new(window: Arc<Mutex<Window>>) -> Self {
-        let windowbrw = window.lock().unwrap();
-        let size = windowbrw.inner_size();
         let instance = wgpu::Instance::default();
-        let surface = unsafe { instance.create_surface(&*windowbrw).unwrap() };
+        let (size, surface) = {
+            let windowbrw = window.lock().unwrap();
+            let surface = unsafe { instance.create_surface(&*windowbrw).unwrap() };
+            (windowbrw.inner_size(), surface)
+        };
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
@@ -55,7 +110,8 @@ impl State {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: wgpu::Features::empty(),
+                    // Chunks are drawn with fewer calls when the adapter supports indirect multi-draws
+                    features: adapter.features() & DrawMode::FEATURES,
                     limits: wgpu::Limits::default(),
                 },
                 None,
@@ -68,40 +124,59 @@ impl State {
         let swapchain_capabilities = surface.get_capabilities(&adapter);
         let swapchain_format = swapchain_capabilities.formats[0];
 
+        let settings = Settings::load(Box::new(())).unwrap_or_default();
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: swapchain_format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: settings.present_mode(),
             alpha_mode: swapchain_capabilities.alpha_modes[0],
             view_formats: vec![],
         };
 
-        let camera = Camera::new(
+        let mut camera = Camera::new(
             surface_config.width as f32,
             surface_config.height as f32,
             device.clone(),
             queue.clone(),
         );
+        camera.fovy = settings.fov.to_radians();
         let current_chunk = camera.eye.get_chunk_from_position_absolute();
+        // Replaced by the saved player when a world is opened
         let player = Arc::new(RwLock::new(Player {
             camera,
-            placing_block: BlockType::Dirt,
+            vitals: Vitals::default(),
+            spawn_point: DEFAULT_SPAWN_POINT,
+            game_mode: GameMode::Creative,
+            inventory: Inventory::starter(),
+            is_mining: false,
+            mining: None,
             in_water: false,
+            head_in_water: false,
             current_chunk,
+            velocity: glam::Vec3::ZERO,
             is_jumping: false,
+            is_sprinting: false,
+            is_crouching: false,
             on_ground: false,
             facing_block: None,
             facing_face: None,
-            jump_action_start: None,
             is_ghost: false,
+            fly_speed: settings.fly_speed,
+            reach: PLAYER_REACH,
+            selection: Selection::default(),
+            clipboard: None,
         }));
 
         surface.configure(&device, &surface_config);
 
+        if let Err(err) = persistence::migrate_legacy_save() {
+            eprintln!("Failed to move the old save into a world: {err}");
+        }
+        // Stays empty until a world is opened from the main menu
         let mut world = World::init_world(device.clone(), queue.clone());
-        world.init_chunks(Arc::clone(&player));
+        world.chunks_per_row = settings.chunks_per_row();
 
         let mut state = Self {
             player,
@@ -114,6 +189,11 @@ impl State {
                 highlight_selected_pipeline: None,
                 translucent_pipeline: None,
                 ui_pipeline: None,
+                underwater_pipeline: None,
+                selection_pipeline: None,
+                crack_pipeline: None,
+                dropped_items_pipeline: None,
+                models_pipeline: None,
             },
             device,
             world,
@@ -121,18 +201,439 @@ impl State {
             surface,
             adapter,
             camera_controller: CameraController::default(),
+            frame_stats: FrameStats::default(),
+            settings,
+            input: InputState::default(),
+            gamepad: GamepadState::default(),
+            console: Console::default(),
+            inventory_full: false,
+            menu: Menu::new(persistence::list_worlds()),
+            world_name: None,
+            connection: None,
+            joining: None,
+            remote_players: HashMap::new(),
+            position_send_timer: 0.0,
+            cursor_position: Vec2::ZERO,
+            exit_requested: false,
         };
+        commands::register_builtins(&mut state.console.commands);
+        state.menu.player_name = state.settings.player_name.clone();
+        state.menu.server_address = state.settings.server_address.clone();
         state.pipeline_manager = PipelineManager::init(&state);
+        state.set_cursor_grab(false);
 
         state
     }
+    pub fn open_world(&mut self, name: &str) {
+        persistence::set_world(name);
+        self.player.write().unwrap().load_saved();
+        self.reset_world();
+        self.world.init_chunks(Arc::clone(&self.player));
+        self.world_name = Some(name.to_string());
+        self.resume();
+    }
+    // Replaces the world with an empty one and clears the console
+    fn reset_world(&mut self) {
+        self.world.dispose();
+        // The renderer is kept so the pipelines' chunk layout stays valid
+        self.world = World::new(self.world.renderer.clone());
+        self.world.chunks_per_row = self.settings.chunks_per_row();
+        self.console = Console::default();
+        commands::register_builtins(&mut self.console.commands);
+    }
+    // Starts joining the server typed on the join server screen, `poll_join` finishes once it answers
+    fn join_server(&mut self) -> Result<(), Box<dyn Error>> {
+        let name = self.menu.player_name.clone();
+        let address = self.menu.server_address.clone();
+        if name.is_empty() {
+            return Err("Enter a name".into());
+        }
+        if self.joining.is_some() {
+            return Ok(());
+        }
+        self.settings.player_name = name.clone();
+        self.settings.server_address = address.clone();
+        self.save_settings();
+
+        let render_distance = self.settings.render_distance;
+        self.joining = Some(std::thread::spawn(move || {
+            Connection::connect(&address, &name, render_distance).map_err(|err| err.to_string())
+        }));
+        self.menu.message = Some("Connecting...".to_string());
+        Ok(())
+    }
+    // Enters the server once the connection is made, its chunks are requested as the player moves
+    fn poll_join(&mut self) {
+        if !self.joining.as_ref().is_some_and(JoinHandle::is_finished) {
+            return;
+        }
+        let result = self.joining.take().unwrap().join();
+        // The player went back from the join screen meanwhile
+        if self.menu.screen != Screen::JoinServer {
+            return;
+        }
+        let (connection, welcome) = match result {
+            Ok(Ok(joined)) => joined,
+            Ok(Err(err)) => {
+                self.menu.message = Some(format!("Cannot join: {err}"));
+                return;
+            }
+            Err(_) => {
+                self.menu.message = Some("Cannot join: the connection failed".to_string());
+                return;
+            }
+        };
+        let address = self.settings.server_address.clone();
+        self.menu.message = None;
+        // Nothing of the player is kept between sessions on a server
+        let mut player = self.player.write().unwrap();
+        player.reset(welcome.spawn - BLOCK_COLLISION_OFFSET);
+        player.set_game_mode(welcome.game_mode);
+        std::mem::drop(player);
+        self.reset_world();
+        self.world.remote = true;
+        self.world.set_time(welcome.time);
+        self.connection = Some(connection);
+        self.remote_players.clear();
+        self.world_name = Some(address);
+        self.menu.connected = true;
+        self.resume();
+    }
+    // Leaves the server and shows why on the main menu
+    fn disconnect(&mut self, reason: &str) {
+        self.close_world();
+        self.menu.message = Some(reason.to_string());
+    }
+    // Saves the open world, or leaves the server, and goes back to the main menu
+    pub fn close_world(&mut self) {
+        if self.world_name.take().is_none() {
+            return;
+        }
+        // Servers save their own world
+        if self.connection.take().is_none() {
+            self.save_state();
+        }
+        self.remote_players.clear();
+        self.menu.connected = false;
+        self.reset_world();
+        self.menu.worlds = persistence::list_worlds();
+        self.menu.show(Screen::MainMenu);
+        self.set_cursor_grab(false);
+    }
+    fn create_world(&mut self) -> Result<(), Box<dyn Error>> {
+        let name = self.menu.world_name.trim().to_string();
+        if !persistence::is_valid_world_name(&name) {
+            return Err("Use up to 32 letters, digits, spaces, - or _".into());
+        }
+        if self.menu.worlds.contains(&name) {
+            return Err(format!("A world named \"{name}\" already exists").into());
+        }
+        persistence::set_world(&name);
+        persistence::create_world_dir()?;
+        self.open_world(&name);
+        Ok(())
+    }
+    pub fn pause(&mut self) {
+        self.menu.show(Screen::Paused);
+        self.release_held_inputs();
+        self.set_cursor_grab(false);
+    }
+    pub fn resume(&mut self) {
+        self.menu.show(Screen::Playing);
+        self.set_cursor_grab(true);
+    }
+    // Keys and buttons released while a menu or the console is open never reach the game
+    fn release_held_inputs(&mut self) {
+        self.input.clear();
+        self.camera_controller.movement_vector = Vec3::ZERO;
+        let mut player = self.player.write().unwrap();
+        player.is_jumping = false;
+        player.is_sprinting = false;
+        player.is_crouching = false;
+        player.is_mining = false;
+        player.mining = None;
+    }
+    pub fn set_cursor_grab(&self, grab: bool) {
+        let window = self.window.lock().unwrap();
+        let result = if grab {
+            window
+                .set_cursor_grab(CursorGrabMode::Confined)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(err) = result {
+            eprintln!("Failed to change the cursor grab: {err}");
+        }
+        window.set_cursor_visible(!grab);
+    }
+    pub fn change_setting(&mut self, setting: Setting, steps: i32) {
+        let previous = self.settings.clone();
+        self.settings.adjust(setting, steps);
+        self.apply_settings(&previous);
+        self.save_settings();
+    }
+    fn save_settings(&self) {
+        if let Err(err) = self.settings.save() {
+            eprintln!("Failed to save the settings: {err}");
+        }
+    }
+    // Binds the key, button or scroll direction the controls screen is waiting for
+    fn capture_binding(&mut self, binding: Binding) {
+        let Some((action, replace)) = self.menu.capturing else {
+            return;
+        };
+        match self.settings.bindings.bind(action, binding, replace) {
+            Ok(()) => {
+                self.menu.capturing = None;
+                self.menu.message = None;
+                self.save_settings();
+            }
+            // Keeps waiting for another binding
+            Err(err) => self.menu.message = Some(err.to_string()),
+        }
+    }
+    // Applies the settings that changed since previous without restarting
+    fn apply_settings(&mut self, previous: &Settings) {
+        let settings = self.settings.clone();
+        if settings.vsync != previous.vsync {
+            self.surface_config.present_mode = settings.present_mode();
+            self.surface.configure(&self.device, &self.surface_config);
+        }
+        if settings.render_distance != previous.render_distance {
+            let center = self.player.read().unwrap().current_chunk;
+            if self.world_name.is_some() {
+                self.world
+                    .set_chunks_per_row(settings.chunks_per_row(), center);
+            } else {
+                self.world.chunks_per_row = settings.chunks_per_row();
+            }
+            let main_pipeline = self
+                .pipeline_manager
+                .main_pipeline
+                .as_ref()
+                .unwrap()
+                .borrow();
+            self.queue.write_buffer(
+                &main_pipeline.chunks_per_row_buffer,
+                0,
+                bytemuck::cast_slice(&[self.world.chunks_per_row]),
+            );
+        }
+        let mut player = self.player.write().unwrap();
+        player.fly_speed = settings.fly_speed;
+        player.camera.fovy = settings.fov.to_radians();
+        std::mem::drop(player);
+        self.write_projection();
+    }
+    // Uploads the projection matrix after the field of view or the aspect ratio changed
+    fn write_projection(&self) {
+        let uniforms = Uniforms::from(&self.player.read().unwrap().camera);
+        self.queue.write_buffer(
+            &self
+                .pipeline_manager
+                .main_pipeline
+                .as_ref()
+                .unwrap()
+                .borrow()
+                .projection_buffer,
+            0,
+            bytemuck::cast_slice(&[uniforms.projection]),
+        );
+    }
+    fn menu_buttons(&self) -> Vec<(Rect, Button)> {
+        let layout = HudLayout::new(
+            self.surface_config.width as f32,
+            self.surface_config.height as f32,
+        );
+        hud::layout_menu(layout, self.menu.rows(&self.settings))
+    }
+    pub fn run_menu_action(&mut self, action: MenuAction) {
+        let Some(action) = self.menu.apply(action) else {
+            return;
+        };
+        match action {
+            MenuAction::OpenWorld(name) => self.open_world(&name),
+            MenuAction::CreateWorld => {
+                if let Err(err) = self.create_world() {
+                    self.menu.message = Some(err.to_string());
+                }
+            }
+            MenuAction::JoinServer => {
+                if let Err(err) = self.join_server() {
+                    self.menu.message = Some(format!("Cannot join: {err}"));
+                }
+            }
+            MenuAction::DeleteWorld(name) => {
+                if let Err(err) = persistence::delete_world(&name) {
+                    eprintln!("Failed to delete world {name}: {err}");
+                }
+                self.menu.worlds = persistence::list_worlds();
+                self.menu.scroll = 0;
+                self.menu.show(Screen::MainMenu);
+            }
+            MenuAction::Resume => self.resume(),
+            MenuAction::AdjustSetting(setting, steps) => self.change_setting(setting, steps),
+            MenuAction::ClearBinding(action) => {
+                self.settings.bindings.clear(action);
+                self.save_settings();
+            }
+            MenuAction::ResetBindings => {
+                self.settings.bindings = Bindings::default();
+                self.save_settings();
+            }
+            MenuAction::SaveAndQuit => self.close_world(),
+            MenuAction::Quit => self.exit_requested = true,
+            _ => {}
+        }
+    }
+    // Receives every key while a menu is open
+    pub fn handle_menu_keypress(&mut self, event: KeyEvent) {
+        if !event.state.is_pressed() {
+            return;
+        }
+        if self.menu.capturing.is_some() {
+            match event.physical_key {
+                PhysicalKey::Code(KeyCode::Escape) => {
+                    self.menu.capturing = None;
+                    self.menu.message = None;
+                }
+                PhysicalKey::Code(key) if !event.repeat => self.capture_binding(Binding::Key(key)),
+                _ => {}
+            }
+            return;
+        }
+        match event.logical_key {
+            Key::Named(NamedKey::Escape) if event.repeat => {}
+            Key::Named(
+                key @ (NamedKey::Escape
+                | NamedKey::Enter
+                | NamedKey::ArrowLeft
+                | NamedKey::ArrowRight
+                | NamedKey::ArrowUp
+                | NamedKey::ArrowDown
+                | NamedKey::Tab),
+            ) => self.navigate_menu(key),
+            Key::Named(NamedKey::Backspace) => self.menu.backspace(),
+            _ => {
+                if let Some(text) = event.text {
+                    self.menu.type_text(&text);
+                }
+            }
+        }
+    }
+    // Moves through the menu with the keyboard or a gamepad's buttons
+    fn navigate_menu(&mut self, key: NamedKey) {
+        let buttons = self.menu_buttons();
+        match key {
+            NamedKey::Escape => {
+                if let Some(action) = self.menu.back_action() {
+                    self.run_menu_action(action);
+                }
+            }
+            NamedKey::Enter => {
+                let selected = buttons.get(self.menu.selected);
+                if let Some((_, button)) = selected {
+                    self.run_menu_action(button.action.clone());
+                }
+            }
+            NamedKey::ArrowLeft | NamedKey::ArrowRight => {
+                let steps = if key == NamedKey::ArrowLeft { -1 } else { 1 };
+                // Settings are changed in place, other screens move the selection
+                match buttons.get(self.menu.selected) {
+                    Some((_, button)) => match button.action {
+                        MenuAction::AdjustSetting(setting, _) => {
+                            self.change_setting(setting, steps)
+                        }
+                        _ => self.menu.move_selection(steps, buttons.len()),
+                    },
+                    None => self.menu.move_selection(steps, buttons.len()),
+                }
+            }
+            NamedKey::ArrowUp => self.menu.move_selection(-1, buttons.len()),
+            NamedKey::ArrowDown | NamedKey::Tab => self.menu.move_selection(1, buttons.len()),
+            _ => {}
+        }
+    }
+    pub fn handle_gamepad_event(&mut self, event: GamepadEvent) {
+        for (binding, pressed) in self.gamepad.handle_event(event) {
+            let Binding::Gamepad(button) = binding else {
+                continue;
+            };
+            if !pressed {
+                self.handle_binding(binding, false);
+                continue;
+            }
+            if self.menu.capturing.is_some() {
+                // Start cancels like Escape, so it can't be bound
+                if button == GamepadButton::Start {
+                    self.menu.capturing = None;
+                    self.menu.message = None;
+                } else {
+                    self.capture_binding(binding);
+                }
+            } else if self.menu.is_open() {
+                let key = match button {
+                    GamepadButton::South => NamedKey::Enter,
+                    GamepadButton::East | GamepadButton::Start => NamedKey::Escape,
+                    GamepadButton::DPadUp => NamedKey::ArrowUp,
+                    GamepadButton::DPadDown => NamedKey::ArrowDown,
+                    GamepadButton::DPadLeft => NamedKey::ArrowLeft,
+                    GamepadButton::DPadRight => NamedKey::ArrowRight,
+                    _ => continue,
+                };
+                self.navigate_menu(key);
+            } else if self.console.open {
+                if button == GamepadButton::East {
+                    self.console.close();
+                }
+            } else if button == GamepadButton::Start {
+                self.pause();
+            } else {
+                self.handle_binding(binding, true);
+            }
+        }
+        // The sticks move the player without being bindings
+        if !self.menu.is_open() && !self.console.open {
+            self.update_held_actions();
+        }
+    }
+    pub fn handle_cursor_moved(&mut self, position: Vec2) {
+        self.cursor_position = position;
+        if self.menu.is_open() {
+            let hovered = self
+                .menu_buttons()
+                .iter()
+                .position(|(rect, _)| rect.contains(position));
+            if let Some(hovered) = hovered {
+                self.menu.selected = hovered;
+            }
+        }
+    }
+    fn on_menu_click(&mut self, button: MouseButton) {
+        if self.menu.capturing.is_some() {
+            self.capture_binding(Binding::Mouse(button));
+            return;
+        }
+        if button != MouseButton::Left {
+            return;
+        }
+        let clicked = self
+            .menu_buttons()
+            .into_iter()
+            .find(|(rect, _)| rect.contains(self.cursor_position));
+        if let Some((_, button)) = clicked {
+            self.run_menu_action(button.action);
+        }
+    }
     pub fn save_state(&mut self) {
-        self.player
-            .read()
-            .unwrap()
-            .camera
+        let player = self.player.read().unwrap();
+        player.save().expect("Failed to save player state");
+        player
+            .inventory
             .save()
-            .expect("Failed to save camera state");
+            .expect("Failed to save inventory state");
+        std::mem::drop(player);
         self.world.save_state();
     }
     pub fn dispose(&mut self) {
@@ -141,114 +642,345 @@ impl State {
         std::mem::drop(self.queue.to_owned());
     }
     pub fn handle_keypress(&mut self, event: KeyEvent) {
-        let is_pressed: f32 = if event.state.is_pressed() { 1. } else { 0. };
+        let PhysicalKey::Code(key) = event.physical_key else {
+            return;
+        };
+        // Escape isn't rebindable, so there's always a way back to the menus
+        if key == KeyCode::Escape {
+            if event.state.is_pressed() && !event.repeat {
+                self.pause();
+            }
+            return;
+        }
+        self.handle_binding(Binding::Key(key), event.state.is_pressed());
+    }
+    // Runs the actions bound to a key, mouse button or scroll direction
+    pub fn handle_binding(&mut self, binding: Binding, pressed: bool) {
+        if !pressed {
+            self.input.release(binding);
+            self.update_held_actions();
+            return;
+        }
+        // Key repeats aren't new presses
+        if !self.input.press(binding) {
+            return;
+        }
+        for action in self.settings.bindings.actions(binding) {
+            self.run_action(action);
+        }
+        self.update_held_actions();
+    }
+    // Sets the movement and the player's flags from the held actions
+    fn update_held_actions(&mut self) {
+        let bindings = &self.settings.bindings;
+        let active = |action| self.input.is_active(action, bindings);
+        self.camera_controller.movement_vector = self.input.movement(
+            bindings,
+            self.gamepad.movement(self.settings.stick_dead_zone),
+        );
         let mut player = self.player.write().unwrap();
-
-        match event {
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyW),
-                ..
-            } => self.camera_controller.movement_vector.z = 1.0 * is_pressed,
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyS),
-                ..
-            } => self.camera_controller.movement_vector.z = -1.0 * is_pressed,
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyO),
-                state: winit::event::ElementState::Released,
-                ..
-            } => {
-                if player.placing_block == BlockType::Dirt {
-                    player.placing_block = BlockType::Grass
-                } else {
-                    player.placing_block = BlockType::Dirt
+        player.is_jumping = active(Action::Jump);
+        player.is_sprinting = active(Action::Sprint);
+        player.is_crouching = active(Action::Crouch);
+        // Blocks are mined over time in `update` while breaking is held in survival
+        player.is_mining = active(Action::Break) && player.game_mode == GameMode::Survival;
+        if !player.is_mining {
+            player.mining = None;
+        }
+    }
+    // Runs an action once, when one of its bindings is pressed
+    fn run_action(&mut self, action: Action) {
+        match action {
+            Action::Break => self.break_block(),
+            Action::Place => self.place_block(),
+            Action::NextSlot => self.player.write().unwrap().inventory.scroll(1),
+            Action::PreviousSlot => self.player.write().unwrap().inventory.scroll(-1),
+            Action::HotbarSlot(slot) => self.player.write().unwrap().inventory.select(slot),
+            Action::ToggleFly => {
+                let mut player = self.player.write().unwrap();
+                player.is_ghost = !player.is_ghost;
+            }
+            Action::ToggleGameMode if self.connection.is_some() => {
+                self.console.print("The server decides the game mode");
+            }
+            Action::ToggleGameMode => {
+                let mut player = self.player.write().unwrap();
+                let game_mode = match player.game_mode {
+                    GameMode::Creative => GameMode::Survival,
+                    GameMode::Survival => GameMode::Creative,
+                };
+                player.set_game_mode(game_mode);
+                std::mem::drop(player);
+                self.console.print(&format!("Game mode: {:?}", game_mode));
+            }
+            Action::ToggleDebug => self.change_setting(Setting::DebugOverlay, 1),
+            Action::Undo | Action::Redo => {
+                let obstacles = [self.player.read().unwrap().get_collision()];
+                let (result, name) = match action {
+                    Action::Undo => (self.world.undo(&obstacles), "undo"),
+                    _ => (self.world.redo(&obstacles), "redo"),
+                };
+                if let Err(err) = result {
+                    self.console.print(&format!("Cannot {name}: {err}"));
                 }
             }
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyA),
-                ..
-            } => self.camera_controller.movement_vector.x = -1.0 * is_pressed,
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyD),
-                ..
-            } => self.camera_controller.movement_vector.x = 1.0 * is_pressed,
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyE),
-                ..
-            } => self.camera_controller.movement_vector.y = 1.0 * is_pressed,
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyQ),
-                ..
-            } => self.camera_controller.movement_vector.y = -1.0 * is_pressed,
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyK),
-                state: winit::event::ElementState::Pressed,
-                ..
-            } => player.next_placing_block(-1),
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyJ),
-                state: winit::event::ElementState::Pressed,
-                ..
-            } => player.next_placing_block(1),
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::Space),
-                state: winit::event::ElementState::Pressed,
-                ..
-            } => {
-                if player.on_ground || player.in_water {
-                    player.is_jumping = true;
-                    player.jump_action_start = Some(std::time::Instant::now());
+            Action::OpenChat => self.open_console(""),
+            Action::OpenCommand => self.open_console("/"),
+            Action::SelectFirstCorner => self.select_corner(true),
+            Action::SelectSecondCorner => self.select_corner(false),
+            action => self.run_region_action(action),
+        }
+    }
+    fn open_console(&mut self, prefix: &str) {
+        self.console.open(prefix);
+        self.release_held_inputs();
+    }
+    // Receives every key while the console is open
+    pub fn handle_console_keypress(&mut self, event: KeyEvent) {
+        if !event.state.is_pressed() {
+            return;
+        }
+        match event.logical_key {
+            Key::Named(NamedKey::Escape) => self.console.close(),
+            Key::Named(NamedKey::Enter) => {
+                if let Some(line) = self.console.submit() {
+                    self.run_console_line(&line);
                 }
             }
-            KeyEvent {
-                physical_key: PhysicalKey::Code(KeyCode::KeyG),
-                state: winit::event::ElementState::Pressed,
-                ..
-            } => {
-                player.is_ghost = !player.is_ghost;
+            Key::Named(NamedKey::Backspace) => self.console.backspace(),
+            Key::Named(NamedKey::ArrowUp) => self.console.history_previous(),
+            Key::Named(NamedKey::ArrowDown) => self.console.history_next(),
+            _ => {
+                if let Some(text) = event.text {
+                    self.console.type_text(&text);
+                }
             }
-            _ => {}
         }
     }
+    // Runs slash commands, anything else is a chat message
+    pub fn run_console_line(&mut self, line: &str) {
+        if !line.starts_with('/') {
+            // The server echoes the message back to everyone
+            match self.connection.as_mut() {
+                Some(connection) => connection.send(&ClientMessage::Chat(line.to_string())),
+                None => self
+                    .console
+                    .print(&format!("<{}> {line}", self.settings.player_name)),
+            }
+            return;
+        }
+        let (command, args) = match self.console.commands.parse(line) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                self.console.print(&err.to_string());
+                return;
+            }
+        };
+        match command.run(self, &args) {
+            Ok(Some(output)) => self.console.print(&output),
+            Ok(None) => {}
+            Err(err) => self
+                .console
+                .print(&format!("{err}\nUsage: {}", command.usage)),
+        }
+    }
+    pub fn on_release(&mut self, button: MouseButton) {
+        self.handle_binding(Binding::Mouse(button), false);
+    }
     pub fn on_click(&mut self, button: MouseButton) {
+        if self.menu.is_open() {
+            self.on_menu_click(button);
+            return;
+        }
+        if self.console.open {
+            return;
+        }
+        self.handle_binding(Binding::Mouse(button), true);
+    }
+    // Breaks the facing block at once in creative, survival mines it while the action is held
+    // Creative players don't collect the blocks they break, nor use up the ones they place
+    fn break_block(&mut self) {
         let player = self.player.read().unwrap();
-        if let Some(facing_block) = player.facing_block.as_ref() {
-            let facing_face = player
-                .facing_face
-                .expect("Cannot be not facing a face if it's facing a block");
-            match button {
-                MouseButton::Left => {
-                    self.world.remove_block(facing_block.clone());
+        if player.game_mode == GameMode::Survival {
+            return;
+        }
+        let Some(facing_block) = player.facing_block.clone() else {
+            return;
+        };
+        if let Err(err) = remove_block(&mut self.world, self.connection.as_mut(), facing_block) {
+            self.console.print(&format!("Cannot break block: {err}"));
+        }
+    }
+    fn place_block(&mut self) {
+        let mut player = self.player.write().unwrap();
+        let Some(facing_block) = player.facing_block.clone() else {
+            return;
+        };
+        let facing_face = player
+            .facing_face
+            .expect("Cannot be not facing a face if it's facing a block");
+        let Some(placing_block) = player.inventory.selected_block_type() else {
+            return;
+        };
+        let new_block_abs_position =
+            facing_block.read().unwrap().absolute_position + facing_face.get_normal_vector();
+
+        let chunk = new_block_abs_position.get_chunk_from_position_absolute();
+        let position = new_block_abs_position.relative_from_absolute();
+
+        let new_block = Arc::new(RwLock::new(Block::new(position, chunk, placing_block)));
+
+        // Checked on the absolute position too, as the relative one is clamped to the world's bottom
+        let obstacles = [player.get_collision()];
+        let result = match self.connection.as_mut() {
+            Some(connection) => self
+                .world
+                .can_place_block(&new_block_abs_position, &obstacles)
+                .map(|_| connection.place_block(new_block_abs_position.as_ivec3(), placing_block)),
+            None => self.world.place_block(new_block, &obstacles),
+        };
+        match result {
+            Ok(()) => {
+                if player.game_mode == GameMode::Survival {
+                    player.inventory.take_selected();
                 }
-                MouseButton::Right => {
-                    let block_borrow = facing_block.read().unwrap();
-                    let new_block_abs_position =
-                        block_borrow.absolute_position + facing_face.get_normal_vector();
-
-                    let chunk = new_block_abs_position.get_chunk_from_position_absolute();
-                    let position = new_block_abs_position.relative_from_absolute();
-
-                    println!("Placing block {:?}", player.placing_block);
-                    let new_block = Arc::new(RwLock::new(Block::new(
-                        position,
-                        chunk,
-                        player.placing_block,
-                    )));
-
-                    self.world.place_block(new_block);
+            }
+            Err(err) => self.console.print(&format!("Cannot place block: {err}")),
+        }
+    }
+    // Sets a corner of the selection to the block the player is facing
+    pub fn select_corner(&mut self, first: bool) {
+        let mut player = self.player.write().unwrap();
+        let Some(block) = player.facing_block.as_ref() else {
+            return;
+        };
+        let position = block.read().unwrap().absolute_position;
+        if first {
+            player.selection.first = Some(position);
+        } else {
+            player.selection.second = Some(position);
+        }
+    }
+    pub fn run_region_command(&mut self, command: RegionCommand) -> Result<(), Box<dyn Error>> {
+        let mut player = self.player.write().unwrap();
+        let block_at = |position: Vec3| self.world.get_block_type_absolute(&position);
+
+        let changes = match command {
+            RegionCommand::Copy => {
+                let region = player.selection.editable_region()?;
+                player.clipboard = Some(region.copy(block_at));
+                return Ok(());
+            }
+            RegionCommand::Rotate(quarter_turns) => {
+                let clipboard = player.clipboard.as_mut().ok_or("The clipboard is empty")?;
+                clipboard.rotate(quarter_turns);
+                return Ok(());
+            }
+            RegionCommand::Mirror(axis) => {
+                let clipboard = player.clipboard.as_mut().ok_or("The clipboard is empty")?;
+                clipboard.mirror(axis);
+                return Ok(());
+            }
+            RegionCommand::Paste(origin) => {
+                let clipboard = player.clipboard.as_ref().ok_or("The clipboard is empty")?;
+                clipboard.paste(origin)
+            }
+            command => {
+                let region = player.selection.editable_region()?;
+                match command {
+                    RegionCommand::Fill(block_type) => region.fill(block_type),
+                    RegionCommand::Replace(from, to) => region.replace(from, to, block_at),
+                    RegionCommand::Hollow(block_type) => region.hollow(block_type),
+                    RegionCommand::Walls(block_type) => region.walls(block_type),
+                    _ => unreachable!(),
                 }
-                _ => {}
             }
+        };
+        std::mem::drop(player);
+        if self.connection.is_some() {
+            return Err("Region edits aren't available on a server".into());
+        }
+        self.world.edit_blocks(changes);
+        Ok(())
+    }
+    // Position where a new block would be placed: in front of the facing block, or at the player's feet
+    fn get_placing_position(&self) -> Vec3 {
+        let player = self.player.read().unwrap();
+        match (player.facing_block.as_ref(), player.facing_face) {
+            (Some(block), Some(face)) => {
+                block.read().unwrap().absolute_position + face.get_normal_vector()
+            }
+            _ => player.get_collision().min().floor(),
         }
     }
-    pub fn handle_wheel(&mut self, delta: f32) {
-        self.player
-            .write()
+    fn run_region_action(&mut self, action: Action) {
+        let placing_block = self.player.read().unwrap().inventory.selected_block_type();
+        let facing_block_type = self
+            .player
+            .read()
             .unwrap()
-            .next_placing_block(delta as i32);
+            .facing_block
+            .as_ref()
+            .map(|b| b.read().unwrap().block_type);
+
+        let command = match action {
+            Action::RegionFill => placing_block.map(|block| RegionCommand::Fill(Some(block))),
+            Action::RegionClear => Some(RegionCommand::Fill(None)),
+            Action::RegionReplace => {
+                // Without a faced block it would replace the air, filling the whole region
+                let Some(from) = facing_block_type else {
+                    self.console.print("Face the block type to replace");
+                    return;
+                };
+                placing_block.map(|block| RegionCommand::Replace(Some(from), Some(block)))
+            }
+            Action::RegionHollow => placing_block.map(RegionCommand::Hollow),
+            Action::RegionWalls => placing_block.map(RegionCommand::Walls),
+            Action::Copy => Some(RegionCommand::Copy),
+            Action::Paste => Some(RegionCommand::Paste(self.get_placing_position())),
+            Action::Rotate => Some(RegionCommand::Rotate(1)),
+            Action::Mirror => Some(RegionCommand::Mirror(0)),
+            _ => return,
+        };
+        let Some(command) = command else {
+            self.console.print("No block selected in the hotbar");
+            return;
+        };
+        if let Err(err) = self.run_region_command(command) {
+            self.console
+                .print(&format!("Cannot run {:?}: {err}", command));
+        }
+    }
+    pub fn handle_wheel(&mut self, delta: f32) {
+        if delta == 0.0 {
+            return;
+        }
+        let binding = if delta > 0.0 {
+            Binding::ScrollUp
+        } else {
+            Binding::ScrollDown
+        };
+        if self.menu.capturing.is_some() {
+            self.capture_binding(binding);
+            return;
+        }
+        if self.menu.is_open() {
+            self.run_menu_action(MenuAction::ScrollList(-delta.signum() as i32));
+            return;
+        }
+        if self.console.open {
+            return;
+        }
+        // Scrolling has no release, every tick is a press
+        self.handle_binding(binding, true);
+        self.input.release(binding);
     }
     pub fn handle_mouse(&mut self, delta: &glam::Vec2) {
-        self.player.write().unwrap().camera.move_target(delta)
+        if self.menu.is_open() || self.console.open {
+            return;
+        }
+        let delta = *delta * self.settings.mouse_sensitivity;
+        self.player.write().unwrap().camera.move_target(&delta)
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
@@ -256,6 +988,9 @@ impl State {
             self.surface_config.width = new_size.width.max(1);
             self.surface_config.height = new_size.height.max(1);
             self.surface.configure(&self.device, &self.surface_config);
+            self.player.write().unwrap().camera.aspect_ratio =
+                new_size.width as f32 / new_size.height as f32;
+            self.write_projection();
             let new_depth = Texture::create_depth_texture(self);
             self.pipeline_manager
                 .main_pipeline
@@ -263,27 +998,125 @@ impl State {
                 .unwrap()
                 .borrow_mut()
                 .set_depth_texture(new_depth);
+            self.pipeline_manager
+                .underwater_pipeline
+                .as_ref()
+                .unwrap()
+                .borrow_mut()
+                .on_depth_texture_changed(self);
+            self.pipeline_manager
+                .ui_pipeline
+                .as_ref()
+                .unwrap()
+                .borrow_mut()
+                .resize(new_size.width, new_size.height);
         }
     }
     pub fn update(&mut self, delta_time: f32) {
-        let nearby_blocks = self.world.get_blocks_nearby(Arc::clone(&self.player));
-
+        self.frame_stats.record(delta_time);
+        self.poll_join();
+        // The server keeps going while the menus are open
+        self.update_connection(delta_time);
+        if self.menu.is_open() {
+            // The world is frozen while a menu is open
+            self.pipeline_manager.update(self).expect("Update failed");
+            return;
+        }
+        self.console.update(delta_time);
+        if !self.console.open {
+            // The stick moves without sending bindings, so the movement follows it every frame
+            self.camera_controller.movement_vector = self.input.movement(
+                &self.settings.bindings,
+                self.gamepad.movement(self.settings.stick_dead_zone),
+            );
+        }
         let mut player = self.player.write().unwrap();
-        player.move_camera(
-            &self.camera_controller.movement_vector,
-            delta_time,
-            &nearby_blocks,
-        );
+        let look = self.gamepad.look(self.settings.stick_dead_zone);
+        if look != Vec2::ZERO && !self.console.open {
+            let delta = look * STICK_LOOK_SPEED * self.settings.mouse_sensitivity * delta_time;
+            player.camera.move_target(&delta);
+        }
+        // On a server the player waits for the chunk it is in instead of falling through it
+        let chunk_loaded = self
+            .world
+            .chunks
+            .read()
+            .unwrap()
+            .contains_key(&player.calc_current_chunk());
+        if chunk_loaded || !self.world.remote {
+            player.move_camera(
+                &self.camera_controller.movement_vector,
+                delta_time,
+                &self.world,
+            );
+        }
+        if player.vitals.is_dead() {
+            self.console.print("You died");
+            player.respawn();
+        }
         player.update();
-        if let Some((block, face_dir)) = player.get_facing_block(&nearby_blocks) {
-            let block = self.world.get_blocks_absolute(&block.to_block_position());
-
-            player.facing_block = block;
-            player.facing_face = Some(face_dir);
+        if let Some(hit) = player.get_facing_block(&self.world) {
+            player.facing_block = self.world.get_blocks_absolute(&hit.block_position);
+            player.facing_face = Some(hit.face);
         } else {
             player.facing_block = None;
             player.facing_face = None;
         }
+        if player.is_mining {
+            match player.facing_block.clone() {
+                Some(block) => {
+                    let (position, block_type) = {
+                        let block = block.read().unwrap();
+                        (block.absolute_position, block.block_type)
+                    };
+                    // Progress is lost when looking at another block
+                    if player.mining.map(|mining| mining.position) != Some(position) {
+                        player.mining = Some(MiningProgress::new(position, block_type));
+                        // The server only lets survival players break the blocks they mined
+                        if let Some(connection) = self.connection.as_mut() {
+                            connection.send(&ClientMessage::StartMining(position.as_ivec3()));
+                        }
+                    }
+                    if player.mining.as_mut().unwrap().advance(delta_time) {
+                        player.mining = None;
+                        // On a server the mined block goes straight to the inventory, once it's broken
+                        match remove_block(&mut self.world, self.connection.as_mut(), block) {
+                            Ok(()) if self.connection.is_none() => {
+                                self.world.spawn_dropped_item(block_type, position)
+                            }
+                            Ok(()) => {}
+                            Err(err) => self.console.print(&format!("Cannot break block: {err}")),
+                        }
+                    }
+                }
+                None => player.mining = None,
+            }
+        }
+
+        let nearby = NearbyPlayer::from_eye(
+            player.get_eye_position(),
+            player.inventory.selected_block_type(),
+            player.is_sprinting,
+        );
+        self.world.tick(delta_time, &[nearby]);
+        let collision = player.get_collision();
+        let collector = (collision.min() + collision.max()) / 2.0;
+        let inventory = &mut player.inventory;
+        let mut inventory_full = false;
+        self.world.entities.retain(|entity| match entity.kind {
+            // Items stay on the ground if they don't fit in the inventory
+            EntityKind::Item(block_type) if entity.can_be_picked_up(collector) => {
+                let left = inventory.add(block_type, 1) > 0;
+                inventory_full |= left;
+                left
+            }
+            _ => true,
+        });
+        // Told once, not every frame the player stands on the items
+        if inventory_full && !self.inventory_full {
+            self.console.print("Inventory is full");
+        }
+        self.inventory_full = inventory_full;
 
         let uniforms = Uniforms::from(&player.camera);
 
@@ -301,13 +1134,103 @@ impl State {
         // Drop write lock
         std::mem::drop(player);
 
-        self.world.update(
-            Arc::clone(&self.player),
-            Arc::clone(&self.queue),
-            Arc::clone(&self.device),
-        );
+        self.world.update(Arc::clone(&self.player));
         self.pipeline_manager.update(self).expect("Update failed");
     }
+    // Applies what the server sent, requests the missing chunks in range and sends the player's position
+    fn update_connection(&mut self, delta_time: f32) {
+        let Some(connection) = self.connection.as_mut() else {
+            return;
+        };
+        let messages = match connection.poll() {
+            Ok(messages) => messages,
+            Err(err) => {
+                self.disconnect(&format!("Disconnected: {err}"));
+                return;
+            }
+        };
+        connection.poll_positions(&mut self.remote_players);
+        let refused = connection.take_refused_placements();
+        let broken = connection.take_broken_blocks();
+        let mut player = self.player.write().unwrap();
+        // Only survival players use up the blocks they place and collect the ones they break, like on the server
+        if player.game_mode == GameMode::Survival {
+            for block_type in refused.into_iter().chain(broken) {
+                if player.inventory.add(block_type, 1) > 0 {
+                    self.console.print("Inventory is full");
+                }
+            }
+        }
+        std::mem::drop(player);
+        for player in self.remote_players.values_mut() {
+            player.advance(delta_time);
+        }
+
+        let mut chunks = vec![];
+        let mut changes = vec![];
+        for message in messages {
+            match message {
+                ServerMessage::ChunkData { x, y, blocks } => chunks.push((x, y, blocks)),
+                ServerMessage::BlockChanged {
+                    position,
+                    block_type,
+                } => changes.push((position.as_vec3(), block_type)),
+                ServerMessage::Chat(text) => self.console.print(&text),
+                ServerMessage::PlayerJoined { player_id, name } => {
+                    self.remote_players
+                        .insert(player_id, RemotePlayer::new(name));
+                }
+                ServerMessage::PlayerLeft { player_id } => {
+                    self.remote_players.remove(&player_id);
+                }
+                ServerMessage::Time(time) => self.world.set_time(time),
+                ServerMessage::Rejected(reason) => {
+                    self.disconnect(&reason);
+                    return;
+                }
+                ServerMessage::Welcome { .. } => {}
+            }
+        }
+
+        let center = self.player.read().unwrap().current_chunk;
+        let in_range = self.world.chunks_in_range(center);
+        // Chunks the player moved away from while they were sent
+        chunks.retain(|(x, y, _)| in_range.contains(&(*x, *y)));
+        if !chunks.is_empty() {
+            self.world.insert_chunks(chunks);
+        }
+        if !changes.is_empty() {
+            self.world.set_blocks(changes);
+        }
+
+        let connection = self.connection.as_mut().unwrap();
+        connection
+            .pending_chunks
+            .retain(|key| in_range.contains(key));
+        let mut missing = {
+            let loaded = self.world.chunks.read().unwrap();
+            in_range
+                .into_iter()
+                .filter(|key| !loaded.contains_key(key) && !connection.pending_chunks.contains(key))
+                .collect::<Vec<_>>()
+        };
+        missing.sort_by_key(|(x, y)| (x - center.0).pow(2) + (y - center.1).pow(2));
+        let free = MAX_PENDING_CHUNKS.saturating_sub(connection.pending_chunks.len());
+        for (x, y) in missing.into_iter().take(free) {
+            connection.request_chunk(x, y);
+        }
+
+        self.position_send_timer -= delta_time;
+        if self.position_send_timer <= 0.0 {
+            self.position_send_timer = POSITION_SEND_INTERVAL;
+            let player = self.player.read().unwrap();
+            connection.send_position(
+                player.get_eye_position(),
+                player.camera.yaw,
+                player.camera.pitch,
+            );
+        }
+    }
     pub fn draw(&mut self) {
         let frame = self
             .surface
@@ -322,11 +1245,8 @@ impl State {
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("command_encoder"),
             });
-        let chunk_map = self.world.chunks.read().unwrap();
-        let chunks = chunk_map
-            .values()
-            .map(|f| f.read().unwrap())
-            .collect::<Vec<_>>();
+        // Chunks may be generated or edited meanwhile, the snapshot keeps this frame's meshes alive
+        let meshes = self.world.renderer().meshes.snapshot();
 
         let player = self.player.read().unwrap();
         // Draw main pipeline
@@ -336,7 +1256,31 @@ impl State {
             .as_ref()
             .unwrap()
             .borrow()
-            .render(self, &mut encoder, &view, &player, &chunks);
+            .render(self, &mut encoder, &view, &player, &meshes);
+
+        let _ = &self
+            .pipeline_manager
+            .dropped_items_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .render(self, &mut encoder, &view, &player, &meshes);
+
+        let _ = &self
+            .pipeline_manager
+            .models_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .render(self, &mut encoder, &view, &player, &meshes);
+
+        let _ = &self
+            .pipeline_manager
+            .crack_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .render(self, &mut encoder, &view, &player, &meshes);
 
         // Draw translucent pipeline
         let _ = &self
@@ -345,7 +1289,23 @@ impl State {
             .as_ref()
             .unwrap()
             .borrow()
-            .render(self, &mut encoder, &view, &player, &chunks);
+            .render(self, &mut encoder, &view, &player, &meshes);
+
+        let _ = &self
+            .pipeline_manager
+            .underwater_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .render(self, &mut encoder, &view, &player, &meshes);
+
+        let _ = &self
+            .pipeline_manager
+            .selection_pipeline
+            .as_ref()
+            .unwrap()
+            .borrow()
+            .render(self, &mut encoder, &view, &player, &meshes);
 
         let _ = &self
             .pipeline_manager
@@ -353,7 +1313,7 @@ impl State {
             .as_ref()
             .unwrap()
             .borrow()
-            .render(self, &mut encoder, &view, &player, &chunks);
+            .render(self, &mut encoder, &view, &player, &meshes);
 
         let _ = &self
             .pipeline_manager
@@ -361,13 +1321,25 @@ impl State {
             .as_ref()
             .unwrap()
             .borrow()
-            .render(self, &mut encoder, &view, &player, &chunks);
+            .render(self, &mut encoder, &view, &player, &meshes);
 
         self.queue.submit(Some(encoder.finish()));
         frame.present();
     }
 }
 
-pub struct Config {
-    pub polygon_mode: wgpu::PolygonMode,
+// Removes the block, or asks the server to when connected, its answer then removes it
+fn remove_block(
+    world: &mut World,
+    connection: Option<&mut Connection>,
+    block: Arc<RwLock<Block>>,
+) -> Result<(), PlaceBlockError> {
+    match connection {
+        Some(connection) => {
+            let block = block.read().unwrap();
+            connection.break_block(block.absolute_position.as_ivec3(), block.block_type);
+            Ok(())
+        }
+        None => world.remove_block(block),
+    }
 }
@@ -55,7 +55,7 @@ impl Structure for Tree {
                 BlockType::Leaf,
             )))
         });
-        
+
         blocks.chain(leafs_iter).collect::<Vec<_>>()
     }
 }
@@ -120,56 +120,6 @@ pub(crate) mod noise {
     }
 }
 
-pub(crate) mod threadpool {
-    use std::{
-        sync::{mpsc, Arc, Mutex},
-        thread,
-    };
-
-    pub struct Worker {
-        id: usize,
-        thread: thread::JoinHandle<()>,
-    }
-    impl Worker {
-        pub fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-            let thread = thread::spawn(move || loop {
-                let receiver = receiver.lock().unwrap();
-                if let Ok(job) = receiver.recv() {
-                    job();
-                }
-            });
-            Worker { id, thread }
-        }
-    }
-    pub struct ThreadPool {
-        workers: Vec<Worker>,
-        sender: mpsc::Sender<Job>,
-    }
-    type Job = Box<dyn FnOnce() + Send + 'static>;
-    impl ThreadPool {
-        pub fn execute<F>(&self, f: F)
-        where
-            F: FnOnce() + Send + 'static,
-        {
-            let job = Box::new(f);
-            self.sender.send(job).unwrap();
-        }
-        pub fn new(size: usize) -> ThreadPool {
-            assert!(size > 0);
-
-            let (sender, receiver) = mpsc::channel();
-            let receiver = Arc::new(Mutex::new(receiver));
-
-            let mut workers = Vec::with_capacity(size);
-
-            for id in 0..size {
-                workers.push(Worker::new(id, Arc::clone(&receiver)))
-            }
-            ThreadPool { workers, sender }
-        }
-    }
-}
-
 /* Utility traits */
 pub trait ChunkFromPosition {
     fn get_chunk_from_position_absolute(&self) -> (i32, i32);
@@ -198,6 +148,7 @@ impl ChunkFromPosition for glam::Vec3 {
     }
 }
 
+#[cfg(test)]
 mod tests {
     use crate::utils::{ChunkFromPosition, RelativeFromAbsolute};
     #[test]
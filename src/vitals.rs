@@ -0,0 +1,106 @@
+use crate::player::GRAVITY;
+
+pub const MAX_HEALTH: f32 = 20.0;
+// Seconds the player can stay with the head under water
+pub const MAX_BREATH: f32 = 10.0;
+// Breath recovered per second out of the water
+const BREATH_REFILL_SPEED: f32 = 5.0;
+const DROWNING_DAMAGE: f32 = 2.0;
+// Seconds between drowning damage
+const DROWNING_INTERVAL: f32 = 1.0;
+// Falls up to this many blocks don't hurt, every block after it costs one health point
+pub const SAFE_FALL_DISTANCE: f32 = 3.0;
+
+// Damage taken when hitting the ground at landing_speed
+pub fn fall_damage(landing_speed: f32) -> f32 {
+    let fall_distance = landing_speed * landing_speed / (2.0 * GRAVITY);
+    f32::max(fall_distance - SAFE_FALL_DISTANCE, 0.0).floor()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vitals {
+    pub health: f32,
+    pub breath: f32,
+    drowning_timer: f32,
+}
+
+impl Default for Vitals {
+    fn default() -> Self {
+        Self {
+            health: MAX_HEALTH,
+            breath: MAX_BREATH,
+            drowning_timer: 0.0,
+        }
+    }
+}
+
+impl Vitals {
+    pub fn new(health: f32, breath: f32) -> Self {
+        Self {
+            health: health.clamp(0.0, MAX_HEALTH),
+            breath: breath.clamp(0.0, MAX_BREATH),
+            drowning_timer: 0.0,
+        }
+    }
+    pub fn damage(&mut self, amount: f32) {
+        self.health = f32::max(self.health - amount, 0.0);
+    }
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0.0
+    }
+    // Consumes breath while the head is under water, and drowns once it runs out
+    pub fn update_breath(&mut self, delta_time: f32, head_in_water: bool) {
+        if !head_in_water {
+            self.breath = f32::min(self.breath + BREATH_REFILL_SPEED * delta_time, MAX_BREATH);
+            self.drowning_timer = 0.0;
+            return;
+        }
+        // Time spent without breath during this update
+        let out_of_breath = f32::max(delta_time - self.breath, 0.0);
+        self.breath = f32::max(self.breath - delta_time, 0.0);
+        self.drowning_timer += out_of_breath;
+        while self.drowning_timer >= DROWNING_INTERVAL {
+            self.drowning_timer -= DROWNING_INTERVAL;
+            self.damage(DROWNING_DAMAGE);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn landing_speed(fall_distance: f32) -> f32 {
+        f32::sqrt(2.0 * GRAVITY * fall_distance)
+    }
+
+    #[test]
+    fn short_falls_are_safe() {
+        assert_eq!(fall_damage(landing_speed(1.5)), 0.0);
+        assert_eq!(fall_damage(landing_speed(SAFE_FALL_DISTANCE)), 0.0);
+    }
+
+    #[test]
+    fn fall_damage_grows_with_height() {
+        assert_eq!(fall_damage(landing_speed(5.5)), 2.0);
+        assert_eq!(fall_damage(landing_speed(23.5)), MAX_HEALTH);
+    }
+
+    #[test]
+    fn drowns_after_breath_runs_out() {
+        let mut vitals = Vitals::default();
+        vitals.update_breath(MAX_BREATH, true);
+        assert_eq!(vitals.breath, 0.0);
+        assert_eq!(vitals.health, MAX_HEALTH);
+
+        vitals.update_breath(DROWNING_INTERVAL, true);
+        assert_eq!(vitals.health, MAX_HEALTH - DROWNING_DAMAGE);
+    }
+
+    #[test]
+    fn breath_refills_out_of_water() {
+        let mut vitals = Vitals::new(MAX_HEALTH, 0.0);
+        vitals.update_breath(MAX_BREATH / BREATH_REFILL_SPEED, false);
+        assert_eq!(vitals.breath, MAX_BREATH);
+    }
+}
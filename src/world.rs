@@ -1,15 +1,23 @@
 use crate::blocks::block_type::BlockType;
+use crate::chunk::{BlockEntry, ChunkRenderer};
+use crate::collision::{self, BlockHit, CollisionBox};
+use crate::debug::TimingStats;
+use crate::entity::mob::{MobKind, NearbyPlayer};
+use crate::entity::pathfinding::{self, PathJobs};
+use crate::entity::spawning::{self, MOB_CAP, SPAWN_INTERVAL};
+use crate::entity::{Entity, EntityKind};
+use crate::history::{BlockEdit, EditHistory};
+use crate::jobs::{JobHandle, JobResult, JobSystem, Priority};
 use crate::persistence::Saveable;
+use crate::region::BlockChange;
 use crate::utils::{ChunkFromPosition, RelativeFromAbsolute};
-use crate::{blocks::block::Block, chunk::Chunk, player::Player, utils::threadpool::ThreadPool};
-use glam::Vec3;
+use crate::{blocks::block::Block, chunk::Chunk, player::Player};
+use glam::{ivec3, IVec3, Vec3};
 use std::borrow::Borrow;
 use std::collections::HashMap;
 use std::sync::RwLock;
-use std::{
-    sync::{mpsc, Arc},
-    thread,
-};
+use std::time::Instant;
+use std::{sync::Arc, thread};
 
 pub const RNG_SEED: u64 = 0;
 pub const CHUNK_SIZE: u32 = 16;
@@ -21,14 +29,12 @@ pub const MAX_TREES_PER_CHUNK: u32 = 3;
 pub const CHUNKS_PER_ROW: u32 = 20;
 pub const CHUNKS_REGION: u32 = CHUNKS_PER_ROW * CHUNKS_PER_ROW;
 pub const WATER_HEIGHT_LEVEL: u8 = 5;
-// Lower bound of chunk
-pub const LB: i32 = -((CHUNKS_PER_ROW / 2) as i32);
-// Upper bound of chunk
-pub const UB: i32 = if CHUNKS_PER_ROW % 2 == 0 {
-    (CHUNKS_PER_ROW / 2 - 1) as i32
-} else {
-    (CHUNKS_PER_ROW / 2) as i32
-};
+// Real seconds in a game day
+pub const DAY_LENGTH: f32 = 1200.0;
+// Hour of the day when a world is opened
+const START_TIME: f32 = 8.0;
+// Sky brightness at midnight
+const MIN_DAYLIGHT: f32 = 0.1;
 
 pub type NoiseData = Vec<f32>;
 pub type WorldChunk = Arc<RwLock<Chunk>>;
@@ -38,34 +44,132 @@ pub type ChunkMap = Arc<RwLock<HashMap<(i32, i32), WorldChunk>>>;
 // and it will be save since every chunk has its own lock.
 pub struct World {
     pub chunks: ChunkMap,
-    pub thread_pool: Option<ThreadPool>,
+    // None once the world is disposed
+    pub jobs: Option<JobSystem>,
+    // Chunks being generated or loaded in the background
+    pub pending_chunks: HashMap<(i32, i32), JobHandle<Chunk>>,
     pub seed: u32,
+    // Chunks loaded on each row of the square around the player
+    pub chunks_per_row: u32,
     pub noise_data: Arc<NoiseData>,
-    pub chunk_data_layout: Arc<wgpu::BindGroupLayout>,
-    pub device: Arc<wgpu::Device>,
-    pub queue: Arc<wgpu::Queue>,
+    // None in headless worlds, which never build chunk meshes
+    pub renderer: Option<Arc<ChunkRenderer>>,
+    pub history: EditHistory,
+    // Entities of the loaded chunks, they are saved with the chunk they're in
+    pub entities: Vec<Entity>,
+    pub paths: PathJobs,
+    // Seconds before trying to spawn a mob
+    pub spawn_timer: f32,
+    // Written by render_chunks, which only borrows the world immutably
+    pub mesh_timings: RwLock<TimingStats>,
+    // Hour of the day, in [0, 24)
+    pub time: f32,
+    // Chunks of remote worlds come from a server, they are never generated nor saved
+    pub remote: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaceBlockError {
+    IntersectsPlayer,
+    OutOfBounds,
+    ChunkNotLoaded,
+    NotReplaceable(BlockType),
+}
+
+impl std::fmt::Display for PlaceBlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlaceBlockError::IntersectsPlayer => write!(f, "the block would intersect the player"),
+            PlaceBlockError::OutOfBounds => write!(f, "the block is outside the world height"),
+            PlaceBlockError::ChunkNotLoaded => write!(f, "the chunk is not loaded"),
+            PlaceBlockError::NotReplaceable(block_type) => {
+                write!(f, "there is already a {:?} block", block_type)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlaceBlockError {}
+
 impl World {
-    pub fn place_block(&mut self, block: Arc<RwLock<Block>>) {
+    pub fn advance_time(&mut self, delta_time: f32) {
+        self.set_time(self.time + delta_time / DAY_LENGTH * 24.0);
+    }
+    pub fn set_time(&mut self, hours: f32) {
+        self.time = hours.rem_euclid(24.0);
+    }
+    // Brightness of the sky, 1 at noon and MIN_DAYLIGHT from dusk to dawn
+    pub fn daylight(&self) -> f32 {
+        let sun_height = ((self.time - 6.0) / 24.0 * std::f32::consts::TAU).sin();
+        (sun_height * 2.0 + 0.5).clamp(MIN_DAYLIGHT, 1.0)
+    }
+    pub fn is_inside_height(position: &Vec3) -> bool {
+        position.y >= 0.0 && position.y < CHUNK_HEIGHT as f32
+    }
+    // Checks if a block can be placed at the absolute position without overlapping any of the obstacles
+    pub fn can_place_block(
+        &self,
+        position: &Vec3,
+        obstacles: &[CollisionBox],
+    ) -> Result<(), PlaceBlockError> {
+        if !World::is_inside_height(position) {
+            return Err(PlaceBlockError::OutOfBounds);
+        }
+        let chunk_map = self.chunks.read().unwrap();
+        let chunk = chunk_map
+            .get(&position.get_chunk_from_position_absolute())
+            .ok_or(PlaceBlockError::ChunkNotLoaded)?;
+
+        // Only air and fluids can be replaced
+        if let Some(block_type) = chunk
+            .read()
+            .unwrap()
+            .block_type_at(&position.relative_from_absolute())
+        {
+            if block_type.is_solid() {
+                return Err(PlaceBlockError::NotReplaceable(block_type));
+            }
+        }
+
+        let block_collision = CollisionBox::from_block_position(position.x, position.y, position.z);
+        if obstacles.iter().any(|o| o.overlaps(&block_collision)) {
+            return Err(PlaceBlockError::IntersectsPlayer);
+        }
+        Ok(())
+    }
+    pub fn place_block(
+        &mut self,
+        block: Arc<RwLock<Block>>,
+        obstacles: &[CollisionBox],
+    ) -> Result<(), PlaceBlockError> {
         let block_borrow = block.read().unwrap();
+        self.can_place_block(&block_borrow.absolute_position, obstacles)?;
+
         let mut chunks_to_rerender = vec![block_borrow.get_chunk_coords()];
         chunks_to_rerender.append(&mut block_borrow.get_neighbour_chunks_coords());
 
         let chunk_map = self.chunks.read().unwrap();
         let chunk = chunk_map
             .get(&chunks_to_rerender[0])
-            .expect("Cannot delete a block from unloaded chunk");
+            .ok_or(PlaceBlockError::ChunkNotLoaded)?;
 
-        {
+        let previous = {
             let mut chunk_lock = chunk.write().unwrap();
+            let previous = chunk_lock.block_type_at(&block_borrow.position);
             chunk_lock.add_block(block.clone(), true);
+            previous
             // Drop chunk lock write
-        }
-
-        self.render_chunks(chunks_to_rerender)
+        };
+        self.history.record(BlockEdit {
+            position: block_borrow.absolute_position,
+            previous,
+            new: Some(block_borrow.block_type),
+        });
+
+        self.render_chunks(chunks_to_rerender);
+        Ok(())
     }
-    pub fn remove_block(&mut self, block: Arc<RwLock<Block>>) {
+    pub fn remove_block(&mut self, block: Arc<RwLock<Block>>) -> Result<(), PlaceBlockError> {
         let mut has_adjacent_water = false;
         let mut chunks_to_rerender = vec![];
         {
@@ -76,13 +180,19 @@ impl World {
             let chunk_map = self.chunks.read().unwrap();
             let chunk = chunk_map
                 .get(&chunks_to_rerender[0])
-                .expect("Cannot delete a block from unloaded chunk");
+                .ok_or(PlaceBlockError::ChunkNotLoaded)?;
 
             {
                 let mut chunk_lock = chunk.write().unwrap();
                 chunk_lock.remove_block(&(block_borrow.position));
                 // Drop chunk lock write
             }
+            self.history.begin_group();
+            self.history.record(BlockEdit {
+                position: block_borrow.absolute_position,
+                previous: Some(block_borrow.block_type),
+                new: None,
+            });
 
             for offset in [
                 glam::vec3(1.0, 0.0, 0.0),
@@ -92,11 +202,10 @@ impl World {
             ] {
                 let position = block_borrow.absolute_position + offset;
                 let chunk_pos = position.get_chunk_from_position_absolute();
-                let chunk = chunk_map
-                    .get(&chunk_pos)
-                    .expect("Should be loaded chunk")
-                    .read()
-                    .unwrap();
+                let Some(chunk) = chunk_map.get(&chunk_pos) else {
+                    continue;
+                };
+                let chunk = chunk.read().unwrap();
 
                 if chunk.block_type_at(&position.relative_from_absolute()) == Some(BlockType::Water)
                 {
@@ -106,19 +215,125 @@ impl World {
         }
 
         // if it has a nearby block of water, replace the removed block with a water block.
-        if has_adjacent_water {
+        let result = if has_adjacent_water {
             let mut blockbrw = block.write().unwrap();
             blockbrw.block_type = BlockType::Water;
             std::mem::drop(blockbrw);
-            self.place_block(block);
+            self.place_block(block, &[])
         } else {
             self.render_chunks(chunks_to_rerender);
+            Ok(())
+        };
+        self.history.end_group();
+        result
+    }
+    // Sets the blocks at the given absolute positions, None removes the block. Edits aren't recorded in the
+    // history and blocks in unloaded chunks are skipped. Every touched chunk is re-rendered once.
+    pub fn set_blocks<I>(&mut self, blocks: I)
+    where
+        I: IntoIterator<Item = BlockChange>,
+    {
+        let mut chunks_to_rerender: Vec<(i32, i32)> = vec![];
+        {
+            let chunk_map = self.chunks.read().unwrap();
+            for (position, block_type) in blocks {
+                if !World::is_inside_height(&position) {
+                    continue;
+                }
+                let chunk_coords = position.get_chunk_from_position_absolute();
+                let Some(chunk) = chunk_map.get(&chunk_coords) else {
+                    continue;
+                };
+                let relative_position = position.relative_from_absolute();
+                let mut chunk = chunk.write().unwrap();
+                match block_type {
+                    Some(block_type) => {
+                        let block = Block::new(relative_position, chunk_coords, block_type);
+                        chunk.add_block(Arc::new(RwLock::new(block)), true)
+                    }
+                    None => chunk.remove_block(&relative_position),
+                }
+
+                let neighbours = Block::neighbour_chunks_coords(&relative_position, chunk_coords);
+                for coords in std::iter::once(chunk_coords).chain(neighbours) {
+                    if !chunks_to_rerender.contains(&coords) {
+                        chunks_to_rerender.push(coords);
+                    }
+                }
+            }
+        }
+        self.render_chunks(chunks_to_rerender);
+    }
+    // Applies a batch of changes as a single undoable action
+    pub fn edit_blocks(&mut self, mut changes: Vec<BlockChange>) {
+        changes.retain(|(position, _)| World::is_inside_height(position));
+        self.history.begin_group();
+        for (position, new) in changes.iter() {
+            let previous = self.get_block_type_absolute(position);
+            self.history.record(BlockEdit {
+                position: *position,
+                previous,
+                new: *new,
+            });
+        }
+        self.history.end_group();
+        self.set_blocks(changes);
+    }
+    // Reverts the last action, returns false if there is none. It stays in the history, and nothing changes,
+    // while one of its blocks is in an unloaded chunk or would be solid inside one of the obstacles.
+    pub fn undo(&mut self, obstacles: &[CollisionBox]) -> Result<bool, PlaceBlockError> {
+        let Some(group) = self.history.next_undo() else {
+            return Ok(false);
+        };
+        let changes: Vec<_> = group
+            .iter()
+            .rev()
+            .map(|e| (e.position, e.previous))
+            .collect();
+        self.can_set_blocks(&changes, obstacles)?;
+        self.history.undo();
+        self.set_blocks(changes);
+        Ok(true)
+    }
+    // Same as undo, for the last undone action
+    pub fn redo(&mut self, obstacles: &[CollisionBox]) -> Result<bool, PlaceBlockError> {
+        let Some(group) = self.history.next_redo() else {
+            return Ok(false);
+        };
+        let changes: Vec<_> = group.iter().map(|e| (e.position, e.new)).collect();
+        self.can_set_blocks(&changes, obstacles)?;
+        self.history.redo();
+        self.set_blocks(changes);
+        Ok(true)
+    }
+    fn can_set_blocks(
+        &self,
+        changes: &[BlockChange],
+        obstacles: &[CollisionBox],
+    ) -> Result<(), PlaceBlockError> {
+        let chunk_map = self.chunks.read().unwrap();
+        for (position, block_type) in changes {
+            if !chunk_map.contains_key(&position.get_chunk_from_position_absolute()) {
+                return Err(PlaceBlockError::ChunkNotLoaded);
+            }
+            let block_collision =
+                CollisionBox::from_block_position(position.x, position.y, position.z);
+            if block_type.is_some_and(|b| b.is_solid())
+                && obstacles.iter().any(|o| o.overlaps(&block_collision))
+            {
+                return Err(PlaceBlockError::IntersectsPlayer);
+            }
         }
+        Ok(())
     }
     pub fn get_blocks_absolute(&self, position: &Vec3) -> Option<Arc<RwLock<Block>>> {
+        World::block_in(&self.chunks, position)
+    }
+    // Lookups that don't borrow the world, for the jobs of the thread pool
+    fn block_in(chunks: &ChunkMap, position: &Vec3) -> Option<Arc<RwLock<Block>>> {
         let (chunk_x, chunk_y) = position.get_chunk_from_position_absolute();
 
-        let chunk_map = self.chunks.read().unwrap();
+        let chunk_map = chunks.read().unwrap();
         let chunk = chunk_map.get(&(chunk_x, chunk_y))?;
         let chunk = chunk.read().unwrap();
 
@@ -127,267 +342,435 @@ impl World {
 
         Some(block)
     }
-    pub fn get_blocks_nearby(&self, player: Arc<RwLock<Player>>) -> Vec<Arc<RwLock<Block>>> {
-        let player = player.read().unwrap();
-        let mut positions = vec![];
-        let mut nearby_blocks = vec![];
-
-        for i in -5..=5 {
-            for j in -5..=5 {
-                for h in -5..=5 {
-                    positions.push(player.camera.eye + glam::vec3(i as f32, h as f32, j as f32));
-                }
-            }
+    pub fn get_block_type_absolute(&self, position: &Vec3) -> Option<BlockType> {
+        World::block_type_in(&self.chunks, position)
+    }
+    pub fn block_type_in(chunks: &ChunkMap, position: &Vec3) -> Option<BlockType> {
+        if position.y < 0.0 {
+            return None;
         }
-
-        for position in positions.iter() {
-            if let Some(block) = self.get_blocks_absolute(position) {
-                nearby_blocks.push(block)
-            };
+        let block = World::block_in(chunks, position)?;
+        let block_type = block.read().unwrap().block_type;
+        Some(block_type)
+    }
+    // Blocks in unloaded chunks are treated as empty
+    pub fn is_solid_block_at(&self, position: &Vec3) -> bool {
+        self.get_block_type_absolute(position)
+            .is_some_and(|block_type| block_type.is_solid())
+    }
+    // Drops an item at the center of the block
+    pub fn spawn_dropped_item(&mut self, block_type: BlockType, block_position: Vec3) {
+        self.entities
+            .push(Entity::dropped_item(block_type, block_position));
+    }
+    // Spawns a mob standing at the position, in collision space
+    pub fn spawn_mob(&mut self, kind: MobKind, position: Vec3) {
+        self.entities.push(Entity::mob(kind, position));
+    }
+    fn is_chunk_loaded(&self, key: (i32, i32)) -> bool {
+        self.chunks.read().unwrap().contains_key(&key)
+    }
+    // Light reaching the block: the daylight under the open sky, none under a roof as no block emits light
+    pub fn light_at(&self, block: IVec3) -> f32 {
+        let roofed = (block.y + 1..i32::from(CHUNK_HEIGHT))
+            .any(|y| self.is_solid_block_at(&ivec3(block.x, y, block.z).as_vec3()));
+        if roofed {
+            0.0
+        } else {
+            self.daylight()
         }
-
-        nearby_blocks
     }
-    pub fn update(
-        &mut self,
-        player: Arc<RwLock<Player>>,
-        queue: Arc<wgpu::Queue>,
-        device: Arc<wgpu::Device>,
-    ) {
-        let mut player_write = player.write().unwrap();
-        let current_chunk = player_write.calc_current_chunk();
-
-        // Update loaded chunks based on player position
-        if current_chunk != player_write.current_chunk {
-            let delta = (
-                current_chunk.0 - player_write.current_chunk.0,
-                current_chunk.1 - player_write.current_chunk.1,
-            );
-            let o = if CHUNKS_PER_ROW % 2 == 0 { 1 } else { 0 };
-            let p = CHUNKS_PER_ROW as i32 / 2;
-            let new_chunks_offset = if delta.1 > 0 || delta.0 > 0 {
-                p - o
-            } else {
-                -p
-            };
-            let old_chunks_offset = if delta.1 > 0 || delta.0 > 0 {
-                -p
-            } else {
-                p - o
+    // Highest block of the column, water included
+    pub fn surface_at(&self, x: i32, z: i32) -> Option<(IVec3, BlockType)> {
+        (0..i32::from(CHUNK_HEIGHT)).rev().find_map(|y| {
+            let block = ivec3(x, y, z);
+            self.get_block_type_absolute(&block.as_vec3())
+                .map(|block_type| (block, block_type))
+        })
+    }
+    // Spawns a mob now and then on the surface around the players, where its biome and the light allow it
+    fn spawn_mobs(&mut self, delta_time: f32, players: &[NearbyPlayer]) {
+        self.spawn_timer -= delta_time;
+        if self.spawn_timer > 0.0 {
+            return;
+        }
+        self.spawn_timer = SPAWN_INTERVAL;
+        let mobs = self
+            .entities
+            .iter()
+            .filter(|entity| matches!(entity.kind, EntityKind::Mob(_)))
+            .count();
+        if mobs >= MOB_CAP {
+            return;
+        }
+        let Some((x, z)) = spawning::spawn_column(&mut rand::thread_rng(), players) else {
+            return;
+        };
+        let Some((surface, block_type)) = self.surface_at(x, z) else {
+            return;
+        };
+        let kind = MobKind::Pig;
+        let feet = surface + IVec3::Y;
+        let block_type_at = |block: Vec3| self.get_block_type_absolute(&block);
+        if kind.can_spawn(block_type, surface.y, self.light_at(feet))
+            && pathfinding::is_walkable(feet, kind.height_in_blocks(), &block_type_at)
+        {
+            self.spawn_mob(kind, feet.as_vec3() + Vec3::new(0.5, 0.0, 0.5));
+        }
+    }
+    // Mobs decide where to go, their paths are searched on the thread pool
+    fn update_mobs(&mut self, delta_time: f32, players: &[NearbyPlayer]) {
+        let finished = self.paths.finished();
+        for entity in self.entities.iter_mut() {
+            let EntityKind::Mob(mob) = &mut entity.kind else {
+                continue;
             };
-
-            let mut new_chunks_positions: Vec<(i32, i32)> = vec![];
-            let chunk_y_remove = player_write.current_chunk.1 + old_chunks_offset;
-            let chunk_x_remove = player_write.current_chunk.0 + old_chunks_offset;
-
-            if delta.0 != 0 {
-                for i in LB + current_chunk.1..=UB + current_chunk.1 {
-                    new_chunks_positions.push((current_chunk.0 + new_chunks_offset, i));
-                }
+            if let Some((_, path)) = finished
+                .iter()
+                .find(|(ticket, _)| mob.pending_path == Some(*ticket))
+            {
+                mob.set_path(path.clone().unwrap_or_default(), entity.position);
             }
-            if delta.1 != 0 {
-                for i in LB + current_chunk.0..=UB + current_chunk.0 {
-                    new_chunks_positions.push((i, current_chunk.1 + new_chunks_offset));
-                }
+            let key = entity.position.get_chunk_from_position_absolute();
+            if !self.chunks.read().unwrap().contains_key(&key) {
+                continue;
             }
-
-            // Filter out the duplicate ones (in case we moved diagonally)
-            new_chunks_positions = new_chunks_positions
-                .iter()
-                .filter_map(|c| {
-                    if new_chunks_positions.contains(c) {
-                        Some(*c)
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            let mut keys_to_remove = vec![];
-            for key in self.chunks.read().unwrap().keys() {
-                // let chunk = chunk.read().unwrap();
-                if (delta.1 != 0 && key.1 == chunk_y_remove)
-                    || (delta.0 != 0 && key.0 == chunk_x_remove)
-                {
-                    keys_to_remove.push(*key);
+            let previous = mob.pending_path;
+            let goal = mob.think(delta_time, entity.position, players);
+            // The search running is useless once the mob changed its mind
+            if let Some(ticket) = previous {
+                if goal.is_some() || mob.pending_path.is_none() {
+                    self.paths.cancel(ticket);
                 }
             }
-
-            // Save the unloaded chunks
-            let (sender, receiver) = mpsc::channel();
-            for key in keys_to_remove.iter() {
-                let chunk = self
-                    .chunks
-                    .write()
-                    .unwrap()
-                    .remove(key)
-                    .expect("Something went wrong");
-                let sender = sender.clone();
-                self.thread_pool.as_ref().unwrap().execute(move || {
-                    let chunk = chunk.write().unwrap();
-                    if chunk.modified {
-                        chunk.save().unwrap();
-                    }
-                    sender.send(()).unwrap();
-                })
+            if let Some(goal) = goal {
+                let start = entity.position.floor().as_ivec3();
+                let ticket = self.paths.request(
+                    self.jobs.as_ref().unwrap(),
+                    &self.chunks,
+                    start,
+                    goal,
+                    mob.kind.height_in_blocks(),
+                );
+                mob.pending_path = Some(ticket);
             }
-
-            for _ in keys_to_remove.iter() {
-                receiver.recv().unwrap();
+        }
+    }
+    // Entities only move inside the loaded chunks, so that they're saved when their chunk is unloaded
+    pub fn update_entities(&mut self, delta_time: f32) {
+        let mut entities = std::mem::take(&mut self.entities);
+        let block_type_at = |block: Vec3| self.get_block_type_absolute(&block);
+        for entity in entities.iter_mut() {
+            if !self.is_chunk_loaded(entity.position.get_chunk_from_position_absolute()) {
+                continue;
             }
-
-            let chunks_added = new_chunks_positions.len();
-            let (sender, receiver) = mpsc::channel();
-
-            for i in 0..new_chunks_positions.len() {
-                let new_chunk_pos = new_chunks_positions[i];
-                let sender = sender.clone();
-                let noise_data = Arc::clone(&self.noise_data);
-                let chunk_data_layout = Arc::clone(&self.chunk_data_layout);
-                let device = Arc::clone(&device);
-                let queue = Arc::clone(&queue);
-
-                self.thread_pool.as_ref().unwrap().execute(move || {
-                    let chunk = Chunk::new(
-                        new_chunk_pos.0,
-                        new_chunk_pos.1,
-                        noise_data,
-                        device,
-                        queue,
-                        chunk_data_layout,
-                    );
-                    sender.send(chunk).unwrap()
-                })
+            let previous = entity.position;
+            entity.update(delta_time, &block_type_at);
+            if !self.is_chunk_loaded(entity.position.get_chunk_from_position_absolute()) {
+                entity.position = previous;
+                entity.velocity = Vec3::ZERO;
             }
-
-            for _ in 0..chunks_added {
-                let chunk = receiver.recv().unwrap();
-                self.chunks
-                    .write()
-                    .unwrap()
-                    .insert((chunk.x, chunk.y), Arc::new(RwLock::new(chunk)));
+        }
+        entities.retain(|entity| !entity.is_expired());
+        self.entities = entities;
+    }
+    // Removes the entities in the chunks from the world, grouped by chunk
+    fn take_entities(&mut self, keys: &[(i32, i32)]) -> HashMap<(i32, i32), Vec<Entity>> {
+        let mut taken: HashMap<(i32, i32), Vec<Entity>> = HashMap::new();
+        let mut kept = vec![];
+        for entity in std::mem::take(&mut self.entities) {
+            let key = entity.position.get_chunk_from_position_absolute();
+            if keys.contains(&key) {
+                taken.entry(key).or_default().push(entity);
+            } else {
+                kept.push(entity);
             }
-            self.handle_outside_blocks();
-            // Re-render only the last inserted chunks
-            self.render_chunks(new_chunks_positions);
         }
+        self.entities = kept;
+        taken
+    }
+    // Returns the first block hit by the ray, fluids are only hit if `hit_fluids` is set
+    pub fn raycast(
+        &self,
+        origin: Vec3,
+        direction: Vec3,
+        reach: f32,
+        hit_fluids: bool,
+    ) -> Option<BlockHit> {
+        collision::raycast_blocks(origin, direction, reach, &|position| {
+            self.get_block_type_absolute(&position)
+                .is_some_and(|block_type| block_type.is_solid() || hit_fluids)
+        })
+    }
+    // Advances the simulation, mobs react to the players given. Remote worlds leave the mobs to the server.
+    pub fn tick(&mut self, delta_time: f32, players: &[NearbyPlayer]) {
+        self.advance_time(delta_time);
+        if !self.remote {
+            self.update_mobs(delta_time, players);
+            self.spawn_mobs(delta_time, players);
+        }
+        self.update_entities(delta_time);
+    }
+    pub fn update(&mut self, player: Arc<RwLock<Player>>) {
+        let mut player_write = player.write().unwrap();
+        let current_chunk = player_write.calc_current_chunk();
+
+        // Update loaded chunks based on player position, they're added as they finish loading
+        if current_chunk != player_write.current_chunk {
+            self.request_chunks_around(current_chunk);
+        }
+        self.receive_chunks();
 
         player_write.current_chunk = current_chunk;
-        std::mem::drop(player_write);
-        // Update visible chunks based on player position and direction
-        {
-            let (sender, receiver) = mpsc::channel();
-            for chunk in self.chunks.read().unwrap().values() {
-                let chunk = Arc::clone(chunk);
-                let sender = sender.clone();
-                let player = Arc::clone(&player);
-                self.thread_pool.as_ref().unwrap().execute(move || {
-                    let mut chunk = chunk.write().unwrap();
-                    chunk.visible = chunk.is_visible(player);
-                    sender.send(()).unwrap();
-                });
-            }
-            for _ in self.chunks.read().unwrap().iter() {
-                receiver.recv().unwrap();
-            }
+        // The meshes built since the last frame are drawn from now on, culled by the player's view
+        if let Some(renderer) = self.renderer.as_ref() {
+            renderer.meshes.publish(&player_write.camera);
         }
     }
+    // Waits for the jobs left, saving the chunks being unloaded
     pub fn dispose(&mut self) {
-        self.thread_pool = None;
+        for (_, handle) in self.pending_chunks.drain() {
+            handle.cancel();
+        }
+        // Waits for the mesh jobs, then the renderer kept by the next world stops drawing these chunks
+        self.jobs = None;
+        if let Some(renderer) = self.renderer.as_ref() {
+            renderer.meshes.clear();
+        }
+    }
+    fn jobs(&self) -> &JobSystem {
+        self.jobs.as_ref().expect("The world was disposed")
     }
 
     pub fn save_state(&self) {
-        for chunk in self.chunks.read().unwrap().values() {
-            let chunkbrw = chunk.read().unwrap();
-            if chunkbrw.modified {
+        if self.remote {
+            return;
+        }
+        for (key, chunk) in self.chunks.read().unwrap().iter() {
+            let mut chunkbrw = chunk.write().unwrap();
+            chunkbrw.entities = self
+                .entities
+                .iter()
+                .filter(|entity| entity.position.get_chunk_from_position_absolute() == *key)
+                .cloned()
+                .collect();
+            if chunkbrw.modified || !chunkbrw.entities.is_empty() {
                 chunkbrw.save().expect("failed to save");
             }
+            chunkbrw.entities.clear();
         }
     }
     pub fn init_chunks(&mut self, player: Arc<RwLock<Player>>) {
-        let (sender, receiver) = mpsc::channel();
-        let player = player.read().unwrap();
-
-        for chunk_x in LB + player.current_chunk.0..=UB + player.current_chunk.0 {
-            for chunk_y in LB + player.current_chunk.1..=UB + player.current_chunk.1 {
-                let sender = sender.clone();
-                let noise_data = Arc::clone(&self.noise_data);
-                let chunk_data_layout = Arc::clone(&self.chunk_data_layout);
-                let device = Arc::clone(&self.device);
-                let queue = Arc::clone(&self.queue);
-                self.thread_pool.as_ref().unwrap().execute(move || {
-                    let chunk = Chunk::new(
-                        chunk_x,
-                        chunk_y,
-                        noise_data,
-                        device,
-                        queue,
-                        chunk_data_layout,
-                    );
-                    sender.send(chunk).unwrap();
-                });
-            }
+        let current_chunk = player.read().unwrap().current_chunk;
+        self.load_chunks_around(current_chunk);
+    }
+    // Changes how many chunks are loaded on each row, loading or unloading chunks around the center to match
+    pub fn set_chunks_per_row(&mut self, chunks_per_row: u32, center: (i32, i32)) {
+        if chunks_per_row == self.chunks_per_row {
+            return;
         }
+        self.chunks_per_row = chunks_per_row;
+        self.load_chunks_around(center);
+    }
+    // Inclusive offsets of the loaded chunks from the player's chunk
+    fn chunk_bounds(&self) -> (i32, i32) {
+        let lower = -((self.chunks_per_row / 2) as i32);
+        let upper = if self.chunks_per_row.is_multiple_of(2) {
+            (self.chunks_per_row / 2) as i32 - 1
+        } else {
+            (self.chunks_per_row / 2) as i32
+        };
+        (lower, upper)
+    }
+    // Keys of the chunks in range of the center
+    pub fn chunks_in_range(&self, center: (i32, i32)) -> Vec<(i32, i32)> {
+        let (lower, upper) = self.chunk_bounds();
+        (lower..=upper)
+            .flat_map(|x| (lower..=upper).map(move |y| (center.0 + x, center.1 + y)))
+            .collect()
+    }
+    // Unloads the chunks out of range of the center, saving the modified ones, and generates or loads the missing ones
+    pub fn load_chunks_around(&mut self, center: (i32, i32)) {
+        self.request_chunks_around(center);
+        self.wait_for_chunks();
+    }
+    // Same as load_chunks_around, but the missing chunks are loaded in the background, nearest first.
+    // The ones still loading that went out of range are cancelled.
+    pub fn request_chunks_around(&mut self, center: (i32, i32)) {
+        let (lower, upper) = self.chunk_bounds();
+        let in_range = |key: &(i32, i32)| {
+            (lower..=upper).contains(&(key.0 - center.0))
+                && (lower..=upper).contains(&(key.1 - center.1))
+        };
+
+        let keys_to_remove = self
+            .chunks
+            .read()
+            .unwrap()
+            .keys()
+            .filter(|key| !in_range(key))
+            .copied()
+            .collect::<Vec<_>>();
+        self.unload_chunks(&keys_to_remove);
+        self.pending_chunks.retain(|key, handle| {
+            if !in_range(key) {
+                handle.cancel();
+            }
+            in_range(key)
+        });
 
-        for _ in 0..CHUNKS_PER_ROW * CHUNKS_PER_ROW {
-            let chunk = receiver.recv().expect("Some chunks are missing");
+        // Remote worlds receive their chunks from the server instead
+        if !self.remote {
+            for key in self.chunks_in_range(center) {
+                self.request_chunk(key, Priority::chunk(center, key));
+            }
+        }
+    }
+    // Starts generating or loading the chunk unless it's loaded or loading already
+    fn request_chunk(&mut self, key: (i32, i32), priority: Priority) {
+        if self.pending_chunks.contains_key(&key) || self.is_chunk_loaded(key) {
+            return;
+        }
+        let noise_data = Arc::clone(&self.noise_data);
+        let handle = self
+            .jobs()
+            .spawn(priority, move || Chunk::new(key.0, key.1, noise_data));
+        self.pending_chunks.insert(key, handle);
+    }
+    // Adds the chunks that finished loading
+    pub fn receive_chunks(&mut self) {
+        let mut chunks = vec![];
+        self.pending_chunks
+            .retain(|_, handle| match handle.try_result() {
+                JobResult::Pending => true,
+                JobResult::Done(chunk) => {
+                    chunks.push(chunk);
+                    false
+                }
+                JobResult::Lost => false,
+            });
+        self.insert_loaded_chunks(chunks);
+    }
+    // Blocks until every chunk requested is loaded
+    pub fn wait_for_chunks(&mut self) {
+        let chunks = self
+            .pending_chunks
+            .drain()
+            .filter_map(|(_, handle)| handle.join())
+            .collect();
+        self.insert_loaded_chunks(chunks);
+    }
+    // Removes the chunks from the world, saving the modified ones and the ones with entities
+    pub fn unload_chunks(&mut self, keys: &[(i32, i32)]) {
+        let mut entities = self.take_entities(keys);
+        let mut handles = vec![];
+        for key in keys.iter() {
+            let Some(chunk) = self.chunks.write().unwrap().remove(key) else {
+                continue;
+            };
+            if let Some(renderer) = self.renderer.as_ref() {
+                renderer.meshes.remove(key.0, key.1);
+            }
+            if self.remote {
+                continue;
+            }
+            let entities = entities.remove(key).unwrap_or_default();
+            handles.push(self.jobs().spawn(Priority::IMMEDIATE, move || {
+                let mut chunk = chunk.write().unwrap();
+                chunk.entities = entities;
+                if chunk.modified || !chunk.entities.is_empty() {
+                    chunk.save().unwrap();
+                }
+            }));
+        }
+        for handle in handles {
+            handle.join().expect("failed to save");
+        }
+    }
+    // Generates or loads the given chunks that aren't loaded yet, waiting for them
+    pub fn load_chunks(&mut self, keys: Vec<(i32, i32)>) {
+        for key in keys.iter() {
+            self.request_chunk(*key, Priority::IMMEDIATE);
+        }
+        let chunks = keys
+            .iter()
+            .filter_map(|key| self.pending_chunks.remove(key))
+            .filter_map(JobHandle::join)
+            .collect();
+        self.insert_loaded_chunks(chunks);
+    }
+    fn insert_loaded_chunks(&mut self, chunks: Vec<Chunk>) {
+        if chunks.is_empty() {
+            return;
+        }
+        let mut keys = vec![];
+        for mut chunk in chunks {
+            self.entities.append(&mut chunk.entities);
+            keys.push((chunk.x, chunk.y));
             self.chunks
                 .write()
                 .unwrap()
                 .insert((chunk.x, chunk.y), Arc::new(RwLock::new(chunk)));
         }
-
         self.handle_outside_blocks();
-        // this is kinda slow
-        self.render_chunks(self.chunks.read().unwrap().keys().collect::<Vec<_>>());
+        // Re-render only the inserted chunks
+        self.render_chunks(keys);
     }
-    // chunks: slice containing the chunk to re-render
+    // Adds chunks received from a server, their loaded neighbours are re-rendered too so that the shared faces
+    // are culled
+    pub fn insert_chunks(&mut self, chunks: Vec<(i32, i32, Vec<BlockEntry>)>) {
+        let mut chunks_to_rerender: Vec<(i32, i32)> = vec![];
+        for (x, y, entries) in chunks {
+            let chunk = Chunk::from_entries(x, y, &entries, Arc::clone(&self.noise_data));
+            self.chunks
+                .write()
+                .unwrap()
+                .insert((x, y), Arc::new(RwLock::new(chunk)));
+            for key in [(x, y), (x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if !chunks_to_rerender.contains(&key) {
+                    chunks_to_rerender.push(key);
+                }
+            }
+        }
+        self.render_chunks(chunks_to_rerender);
+    }
+    // chunks: slice containing the chunk to re-render, headless worlds skip it as nothing is drawn
     fn render_chunks<I>(&self, chunk_keys: Vec<I>)
     where
         I: Borrow<(i32, i32)>,
     {
-        let (sender, receiver) = mpsc::channel();
-
-        for key in chunk_keys.iter() {
-            if let Some(chunk) = self.chunks.read().unwrap().get(key.borrow()) {
-                let sender = sender.clone();
-                // This is extremely slow O(n^2)
-                // let other = self.get_other_chunks(chunk.clone());
-                let chunk = chunk.clone();
+        let Some(renderer) = self.renderer.as_ref() else {
+            return;
+        };
+        let chunks: Vec<WorldChunk> = {
+            let chunk_map = self.chunks.read().unwrap();
+            chunk_keys
+                .iter()
+                .filter_map(|key| chunk_map.get(key.borrow()).cloned())
+                .collect()
+        };
+        let handles: Vec<_> = chunks
+            .into_iter()
+            .map(|chunk| {
                 let chunk_map = self.chunks.clone();
-
-                self.thread_pool.as_ref().unwrap().execute(move || {
-                    let chunk_ptr = chunk.clone();
+                let renderer = Arc::clone(renderer);
+                self.jobs().spawn(Priority::IMMEDIATE, move || {
                     let chunk = chunk.read().unwrap();
-                    let res = chunk.build_mesh(chunk_map);
-                    sender.send((res, chunk_ptr)).unwrap();
-                });
-            }
-        }
-        for _ in chunk_keys.iter() {
-            let (
-                (
-                    indices,
-                    water_indices,
-                    vertex_buffer,
-                    index_buffer,
-                    water_vertex_buffer,
-                    water_index_buffer,
-                ),
-                chunk_ptr,
-            ) = receiver.recv().expect("Some chunks didn't render");
-            let mut chunk_mut = chunk_ptr.write().unwrap();
-            chunk_mut.indices = indices;
-            chunk_mut.chunk_vertex_buffer = Some(vertex_buffer);
-            chunk_mut.chunk_index_buffer = Some(index_buffer);
-            chunk_mut.water_indices = water_indices;
-            chunk_mut.chunk_water_vertex_buffer = Some(water_vertex_buffer);
-            chunk_mut.chunk_water_index_buffer = Some(water_index_buffer);
+                    let start = Instant::now();
+                    let mesh = chunk.build_mesh(chunk_map);
+                    renderer.meshes.insert(chunk.x, chunk.y, &mesh);
+                    start.elapsed()
+                })
+            })
+            .collect();
+        for duration in handles.into_iter().filter_map(JobHandle::join) {
+            self.mesh_timings.write().unwrap().record(duration);
         }
     }
     fn handle_outside_blocks(&mut self) {
+        let chunk_map = self.chunks.read().unwrap();
         let mut blocks_to_add = vec![];
-        for chunk in self.chunks.read().unwrap().values() {
+        for chunk in chunk_map.values() {
             let mut chunkbrw = chunk.write().unwrap();
             blocks_to_add.append(&mut chunkbrw.outside_blocks);
         }
@@ -396,34 +779,214 @@ impl World {
 
         for block in blocks_to_add.iter() {
             let chunk_coords = block.read().unwrap().get_chunk_coords();
-            if let Some(chunkptr) = self.chunks.read().unwrap().get(&chunk_coords) {
+            if let Some(chunkptr) = chunk_map.get(&chunk_coords) {
                 let mut chunkbrw = chunkptr.write().unwrap();
                 chunkbrw.add_block(block.clone(), false);
-                if !chunks_to_rerender.iter().any(|c| Arc::ptr_eq(&c, chunkptr)) {
+                if !chunks_to_rerender.iter().any(|c| Arc::ptr_eq(c, chunkptr)) {
                     chunks_to_rerender.push(chunkptr.clone());
                 };
             }
         }
     }
     pub fn init_world(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        World::new(Some(Arc::new(ChunkRenderer::new(device, queue))))
+    }
+    // A world that is simulated without ever being drawn, used by the headless mode and the tests
+    pub fn headless() -> Self {
+        World::new(None)
+    }
+    pub fn new(renderer: Option<Arc<ChunkRenderer>>) -> Self {
         let noise_data = Arc::new(crate::utils::noise::create_world_noise_data(
             NOISE_SIZE, NOISE_SIZE, FREQUENCY,
         ));
-        let chunk_data_layout =
-            Arc::new(device.create_bind_group_layout(&Chunk::get_bind_group_layout()));
 
         let threads = thread::available_parallelism().unwrap();
         // let threads = usize::max(usize::from(max_threads), 8);
-        let thread_pool = ThreadPool::new(usize::from(threads));
+        let jobs = JobSystem::new(usize::from(threads));
 
         World {
-            chunk_data_layout,
+            renderer,
             chunks: Arc::new(RwLock::new(HashMap::new())),
             noise_data,
-            device,
-            queue,
             seed: 0,
-            thread_pool: Some(thread_pool),
+            chunks_per_row: CHUNKS_PER_ROW,
+            jobs: Some(jobs),
+            pending_chunks: HashMap::new(),
+            history: EditHistory::default(),
+            entities: vec![],
+            paths: PathJobs::default(),
+            spawn_timer: SPAWN_INTERVAL,
+            mesh_timings: RwLock::new(TimingStats::default()),
+            time: START_TIME,
+            remote: false,
         }
     }
+    // GPU resources of the chunks, only called by the pipelines which never run headless
+    pub fn renderer(&self) -> &ChunkRenderer {
+        self.renderer
+            .as_deref()
+            .expect("Headless worlds have no renderer")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::persistence;
+
+    #[test]
+    fn headless_world_edits_are_saved_and_loaded() {
+        let name = "headless-world-test";
+        let _world = persistence::set_test_world(name);
+        let position = glam::vec3(3.0, 200.0, 5.0);
+
+        let mut world = World::headless();
+        world.chunks_per_row = 2;
+        world.load_chunks_around((0, 0));
+        assert_eq!(world.chunks.read().unwrap().len(), 4);
+        world.edit_blocks(vec![(position, Some(BlockType::Stone))]);
+        world.spawn_mob(MobKind::Pig, glam::vec3(8.5, 220.0, 8.5));
+        world.tick(1.0, &[]);
+        world.save_state();
+        world.dispose();
+
+        let mut loaded = World::headless();
+        loaded.chunks_per_row = 2;
+        loaded.load_chunks_around((0, 0));
+        let block_type = loaded.get_block_type_absolute(&position);
+        let entities = loaded.entities.clone();
+        // Entities leave the world with their chunk
+        loaded.load_chunks_around((10, 10));
+        let unloaded_entities = loaded.entities.len();
+        loaded.dispose();
+        persistence::delete_world(name).unwrap();
+        assert_eq!(block_type, Some(BlockType::Stone));
+        assert_eq!(entities.len(), 1);
+        assert!(matches!(&entities[0].kind, EntityKind::Mob(mob) if mob.kind == MobKind::Pig));
+        assert!(entities[0].position.y < 220.0);
+        assert_eq!(unloaded_entities, 0);
+    }
+
+    #[test]
+    fn blocks_are_placed_in_loaded_air_away_from_obstacles() {
+        let _world = persistence::set_test_world("place-block-test");
+        let mut world = World::headless();
+        world.chunks_per_row = 2;
+        world.load_chunks_around((-40, -40));
+        let stone = glam::vec3(-40.0 * CHUNK_SIZE as f32, 200.0, -40.0 * CHUNK_SIZE as f32);
+        world.set_blocks(vec![(stone, Some(BlockType::Stone))]);
+        let above = stone + glam::Vec3::Y;
+        // A player standing on top of the new block, then one standing where it goes
+        let obstacles = [
+            CollisionBox::new(above.x + 0.2, above.y + 1.0, above.z + 0.2, 0.6, 1.8, 0.6),
+            CollisionBox::new(above.x + 0.2, above.y, above.z + 0.2, 0.6, 1.8, 0.6),
+        ];
+
+        let results = [
+            world.can_place_block(&above, &obstacles[..1]),
+            world.can_place_block(&above, &obstacles),
+            world.can_place_block(&stone, &[]),
+            world.can_place_block(&glam::vec3(0.0, 200.0, 0.0), &[]),
+            world.can_place_block(&(stone - glam::vec3(0.0, 201.0, 0.0)), &[]),
+        ];
+        world.dispose();
+        assert_eq!(
+            results,
+            [
+                Ok(()),
+                Err(PlaceBlockError::IntersectsPlayer),
+                Err(PlaceBlockError::NotReplaceable(BlockType::Stone)),
+                Err(PlaceBlockError::ChunkNotLoaded),
+                Err(PlaceBlockError::OutOfBounds),
+            ]
+        );
+    }
+
+    #[test]
+    fn undo_waits_for_the_chunks_and_the_player() {
+        let _world = persistence::set_test_world("undo-test");
+        let mut world = World::headless();
+        world.chunks_per_row = 2;
+        world.load_chunks_around((-40, -40));
+        let position = glam::vec3(-40.0 * CHUNK_SIZE as f32, 200.0, -40.0 * CHUNK_SIZE as f32);
+        world.edit_blocks(vec![(position, Some(BlockType::Stone))]);
+
+        let chunk = world.chunks.write().unwrap().remove(&(-40, -40)).unwrap();
+        let unloaded = world.undo(&[]);
+        let kept = world.history.undo_len();
+        world.chunks.write().unwrap().insert((-40, -40), chunk);
+        let undone = world.undo(&[]);
+
+        // The player now stands where the block would come back
+        let player = CollisionBox::new(
+            position.x + 0.2,
+            position.y,
+            position.z + 0.2,
+            0.6,
+            1.8,
+            0.6,
+        );
+        let blocked = world.redo(&[player]);
+        let redone = world.redo(&[]);
+        let block_type = world.get_block_type_absolute(&position);
+        world.dispose();
+        assert_eq!(unloaded, Err(PlaceBlockError::ChunkNotLoaded));
+        assert_eq!(kept, 1);
+        assert_eq!(undone, Ok(true));
+        assert_eq!(blocked, Err(PlaceBlockError::IntersectsPlayer));
+        assert_eq!(redone, Ok(true));
+        assert_eq!(block_type, Some(BlockType::Stone));
+    }
+
+    // Device of the default adapter, None on the machines without a GPU or a software renderer
+    fn test_device() -> Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&Default::default()))?;
+        let descriptor = wgpu::DeviceDescriptor {
+            limits: adapter.limits(),
+            ..Default::default()
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None)).ok()?;
+        Some((Arc::new(device), Arc::new(queue)))
+    }
+
+    #[test]
+    fn disposed_worlds_stop_drawing_their_chunks() {
+        let Some((device, queue)) = test_device() else {
+            eprintln!("No adapter, skipping");
+            return;
+        };
+        let _world = persistence::set_test_world("dispose-test");
+        let renderer = Arc::new(ChunkRenderer::new(device.clone(), queue.clone()));
+        let camera = crate::player::Camera::new(800.0, 600.0, device, queue);
+        let mut world = World::new(Some(Arc::clone(&renderer)));
+        world.chunks_per_row = 2;
+        world.load_chunks_around((0, 0));
+        renderer.meshes.publish(&camera);
+        let drawn = renderer.meshes.snapshot().chunks.len();
+
+        world.dispose();
+        assert_eq!(drawn, 4);
+        assert!(renderer.meshes.snapshot().chunks.is_empty());
+        renderer.meshes.publish(&camera);
+        assert!(renderer.meshes.snapshot().chunks.is_empty());
+    }
+
+    #[test]
+    fn chunks_out_of_range_stop_loading() {
+        let _world = persistence::set_test_world("pending-chunks-test");
+        let mut world = World::headless();
+        world.chunks_per_row = 2;
+        world.request_chunks_around((0, 0));
+        world.request_chunks_around((50, 50));
+        let mut pending: Vec<_> = world.pending_chunks.keys().copied().collect();
+        pending.sort();
+        assert_eq!(pending, vec![(49, 49), (49, 50), (50, 49), (50, 50)]);
+        world.wait_for_chunks();
+        let mut loaded: Vec<_> = world.chunks.read().unwrap().keys().copied().collect();
+        loaded.sort();
+        world.dispose();
+        assert_eq!(loaded, pending);
+    }
 }